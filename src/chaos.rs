@@ -0,0 +1,503 @@
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use wg_internal::network::NodeId;
+
+use logger::Logger;
+
+use crate::command::Command;
+use crate::controller::{ControllerUpdate, SimulationController};
+use crate::lint::{CLIENT_LINKS, SERVER_MIN_LINKS};
+use crate::topology::{NodeType, Topology};
+
+// parameters of the chaos engine, e.g. from the spec
+// "rate=0.5,seed=7,min_degree=2,downtime=10,duration=3600"
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChaosOptions {
+    pub rate: f64, // faults per second (on average)
+    pub seed: u64,
+    pub min_degree: usize, // links a drone must keep, clients and servers keep their own minimum
+    pub downtime: Duration, // time before a fault is undone
+    pub max_pdr: f32,      // perturbed pdr are drawn in [0, max_pdr]
+    pub duration: Option<Duration>, // the engine stops by itself after it (None runs until stopped)
+}
+
+impl Default for ChaosOptions {
+    fn default() -> Self {
+        ChaosOptions {
+            rate: 0.2,
+            seed: 0,
+            min_degree: 1,
+            downtime: Duration::from_secs(10),
+            max_pdr: 0.5,
+            duration: None,
+        }
+    }
+}
+
+// longest downtime, duration and time between faults, later instants cannot be represented
+const MAX_SECONDS: f64 = 86_400.0;
+
+fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, key))
+}
+
+fn parse_seconds(key: &str, value: &str) -> Result<Duration, String> {
+    let seconds: f64 = parse_value(key, value)?;
+    if !(0.0..=MAX_SECONDS).contains(&seconds) {
+        return Err(format!(
+            "{} must be a number of seconds in [0,{}]",
+            key, MAX_SECONDS
+        ));
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+impl ChaosOptions {
+    // list of key=value separated by commas, the missing keys keep the default value
+    pub fn from_spec(spec: &str) -> Result<ChaosOptions, String> {
+        let mut options = ChaosOptions::default();
+        for item in spec.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            let Some((key, value)) = item.split_once('=') else {
+                return Err(format!("expected key=value in '{}'", item));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "rate" => options.rate = parse_value(key, value)?,
+                "seed" => options.seed = parse_value(key, value)?,
+                "min_degree" => options.min_degree = parse_value(key, value)?,
+                "downtime" => options.downtime = parse_seconds(key, value)?,
+                "max_pdr" => options.max_pdr = parse_value(key, value)?,
+                "duration" => options.duration = Some(parse_seconds(key, value)?),
+                _ => return Err(format!("unknown chaos key '{}'", key)),
+            }
+        }
+        if !(options.rate > 0.0 && options.rate.is_finite()) {
+            return Err("rate must be greater than 0".to_string());
+        }
+        if !(0.0..=1.0).contains(&options.max_pdr) {
+            return Err(format!("max_pdr {} is not in [0,1]", options.max_pdr));
+        }
+        Ok(options)
+    }
+}
+
+// links of a node to active nodes
+fn degree(topology: &Topology, id: NodeId) -> usize {
+    topology.get(id).map_or(0, |n| {
+        n.neighbours
+            .iter()
+            .filter(|adj| topology.is_active(**adj))
+            .count()
+    })
+}
+
+// a fault is safe if no client loses its path to a server and no active node goes below its
+// minimum number of links (nodes already below it are only forbidden to lose more links)
+pub fn is_safe(topology: &Topology, command: &Command, min_degree: usize) -> bool {
+    let change = |t: &mut Topology| match *command {
+        Command::Crash(id) => {
+            t.crash(id);
+        }
+        Command::Unlink(id1, id2) => t.remove_link(id1, id2),
+        _ => {}
+    };
+    if !topology.partitioned_by(change).is_empty() {
+        return false;
+    }
+
+    let mut after = topology.clone();
+    change(&mut after);
+    let keeps_links = after.nodes().filter(|n| !n.crashed).all(|n| {
+        let minimum = match n.node_type {
+            NodeType::Drone => min_degree,
+            NodeType::Client => CLIENT_LINKS.0,
+            NodeType::Server => SERVER_MIN_LINKS,
+        };
+        let links = degree(&after, n.id);
+        links >= minimum || links >= degree(topology, n.id)
+    });
+    keeps_links
+}
+
+// random fault and the command that undoes it, nodes with a pending fault are left alone and
+// the pdr of the drones driven by a profile is not perturbed (the undo would remove the profile)
+fn pick_fault(
+    topology: &Topology,
+    options: &ChaosOptions,
+    busy: &[NodeId],
+    profiled: &[NodeId],
+    rng: &mut StdRng,
+) -> Option<(Command, Command)> {
+    let free = |id: &NodeId| !busy.contains(id);
+    let mut drones: Vec<(NodeId, f32)> = topology
+        .drones()
+        .filter(|d| !d.crashed && free(&d.id))
+        .map(|d| (d.id, d.pdr))
+        .collect();
+    drones.shuffle(rng);
+    let mut links: Vec<(NodeId, NodeId)> = topology
+        .links()
+        .into_iter()
+        .filter(|(a, b)| free(a) && free(b))
+        .collect();
+    links.shuffle(rng);
+
+    let mut kinds = [0, 1, 2];
+    kinds.shuffle(rng);
+    for kind in kinds {
+        let fault = match kind {
+            // crash of a drone
+            0 => drones
+                .iter()
+                .map(|(id, _)| (Command::Crash(*id), Command::Reactivate(*id)))
+                .find(|(fault, _)| is_safe(topology, fault, options.min_degree)),
+            // link flap
+            1 => links
+                .iter()
+                .map(|(a, b)| (Command::Unlink(*a, *b), Command::Link(*a, *b)))
+                .find(|(fault, _)| is_safe(topology, fault, options.min_degree)),
+            // pdr perturbation
+            _ => drones
+                .iter()
+                .find(|(id, _)| !profiled.contains(id))
+                .map(|(id, pdr)| {
+                    let perturbed = (rng.gen_range(0.0..=options.max_pdr) * 100.0).round() / 100.0;
+                    (Command::Pdr(*id, perturbed), Command::Pdr(*id, *pdr))
+                }),
+        };
+        if fault.is_some() {
+            return fault;
+        }
+    }
+    None
+}
+
+// time to the next fault, faults are a poisson process of the given rate
+fn interval(rate: f64, rng: &mut StdRng) -> Duration {
+    Duration::from_secs_f64((-(1.0 - rng.gen::<f64>()).ln() / rate).min(MAX_SECONDS))
+}
+
+fn nodes_of(command: &Command) -> Vec<NodeId> {
+    match *command {
+        Command::Crash(id) | Command::Reactivate(id) | Command::Pdr(id, _) => vec![id],
+        Command::Link(id1, id2) | Command::Unlink(id1, id2) => vec![id1, id2],
+        _ => vec![],
+    }
+}
+
+// true if the command was applied
+fn apply(
+    controller: &mut SimulationController,
+    logger: &Arc<Mutex<Logger>>,
+    command: &Command,
+) -> bool {
+    match controller.execute(command) {
+        Ok(_) => {
+            logger
+                .lock()
+                .unwrap()
+                .log_info(&format!("[CHAOS] {}", command));
+            true
+        }
+        Err(e) => {
+            logger
+                .lock()
+                .unwrap()
+                .log_warn(&format!("[CHAOS] {}: {}", command, e));
+            false
+        }
+    }
+}
+
+// true if a configuration has been loaded since the last call, every update is consumed
+fn reloaded(updates: &Receiver<ControllerUpdate>) -> bool {
+    let mut reloaded = false;
+    for update in updates.try_iter() {
+        reloaded |= update == ControllerUpdate::TopologyLoaded;
+    }
+    reloaded
+}
+
+fn discard(logger: &Arc<Mutex<Logger>>, pending: &mut Vec<(Instant, Command)>) {
+    if !pending.is_empty() {
+        logger.lock().unwrap().log_info(&format!(
+            "[CHAOS] Network reloaded, {} pending undos dropped",
+            pending.len()
+        ));
+    }
+    pending.clear();
+}
+
+// thread injecting random faults through the controller, every fault is undone after the
+// downtime (or when the engine is stopped) unless the network has been reloaded in between
+pub struct ChaosEngine {
+    stop: Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl ChaosEngine {
+    pub fn start(
+        controller: Arc<Mutex<SimulationController>>,
+        logger: Arc<Mutex<Logger>>,
+        options: ChaosOptions,
+    ) -> ChaosEngine {
+        let (stop, stopped) = unbounded::<()>();
+        let updates = controller.lock().unwrap().subscribe();
+
+        let handle = thread::spawn(move || {
+            logger
+                .lock()
+                .unwrap()
+                .log_info(&format!("[CHAOS] Started with {:?}", options));
+            let mut rng = StdRng::seed_from_u64(options.seed);
+            let end = options.duration.map(|duration| Instant::now() + duration);
+            let mut next_fault = Instant::now() + interval(options.rate, &mut rng);
+            let mut pending: Vec<(Instant, Command)> = vec![]; // commands undoing the faults
+
+            loop {
+                let next = pending
+                    .iter()
+                    .map(|(at, _)| *at)
+                    .fold(next_fault, Instant::min)
+                    .min(end.unwrap_or(next_fault));
+                match stopped.recv_timeout(next.saturating_duration_since(Instant::now())) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => break,
+                }
+                if end.is_some_and(|end| Instant::now() >= end) {
+                    break;
+                }
+
+                let now = Instant::now();
+                let mut controller = controller.lock().unwrap();
+                // the undos would bring the links and pdr of the old network into the new one
+                if reloaded(&updates) {
+                    discard(&logger, &mut pending);
+                }
+                let (due, rest): (Vec<_>, Vec<_>) =
+                    pending.into_iter().partition(|(at, _)| *at <= now);
+                pending = rest;
+                for (_, undo) in due {
+                    apply(&mut controller, &logger, &undo);
+                }
+
+                if next_fault <= now {
+                    let busy: Vec<NodeId> = pending.iter().flat_map(|(_, c)| nodes_of(c)).collect();
+                    let profiled: Vec<NodeId> = controller
+                        .pdr_profiles()
+                        .into_iter()
+                        .map(|(id, _)| id)
+                        .collect();
+                    match pick_fault(controller.topology(), &options, &busy, &profiled, &mut rng) {
+                        Some((fault, undo)) => {
+                            if apply(&mut controller, &logger, &fault) {
+                                pending.push((now + options.downtime, undo));
+                            }
+                        }
+                        None => logger
+                            .lock()
+                            .unwrap()
+                            .log_debug("[CHAOS] No safe fault available"),
+                    }
+                    next_fault = now + interval(options.rate, &mut rng);
+                }
+            }
+
+            // the network is left as it was found
+            let mut controller = controller.lock().unwrap();
+            if reloaded(&updates) {
+                discard(&logger, &mut pending);
+            }
+            for (_, undo) in pending {
+                apply(&mut controller, &logger, &undo);
+            }
+            logger.lock().unwrap().log_info("[CHAOS] Stopped");
+        });
+
+        ChaosEngine { stop, handle }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    // undo the pending faults and wait for the thread
+    pub fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.handle.join();
+    }
+
+    // wait until the end of the duration, forever if the engine has none
+    pub fn join(self) {
+        let _ = self.handle.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::topology::Node;
+
+    fn node(id: NodeId, node_type: NodeType, neighbours: &[NodeId]) -> Node {
+        Node {
+            id,
+            node_type,
+            pdr: 0.1,
+            crashed: false,
+            neighbours: neighbours.to_vec(),
+        }
+    }
+
+    // ring of drones 1 - 2 - 3 - 4, client 10 - drones 1 and 2, server 20 - drones 3 and 4
+    fn ring() -> Topology {
+        Topology::from_nodes(vec![
+            node(1, NodeType::Drone, &[2, 4, 10]),
+            node(2, NodeType::Drone, &[1, 3, 10]),
+            node(3, NodeType::Drone, &[2, 4, 20]),
+            node(4, NodeType::Drone, &[3, 1, 20]),
+            node(10, NodeType::Client, &[1, 2]),
+            node(20, NodeType::Server, &[3, 4]),
+        ])
+    }
+
+    #[test]
+    fn parses_specs() {
+        let options =
+            ChaosOptions::from_spec("rate=0.5,seed=7,min_degree=2,downtime=1.5,duration=60")
+                .unwrap();
+        assert_eq!(
+            options,
+            ChaosOptions {
+                rate: 0.5,
+                seed: 7,
+                min_degree: 2,
+                downtime: Duration::from_millis(1500),
+                max_pdr: 0.5,
+                duration: Some(Duration::from_secs(60)),
+            }
+        );
+        assert_eq!(
+            ChaosOptions::from_spec("").unwrap(),
+            ChaosOptions::default()
+        );
+        for spec in [
+            "rate=0",
+            "rate=inf",
+            "rate=nan",
+            "downtime=-1",
+            "downtime=inf",
+            "downtime=nan",
+            "downtime=1e300",
+            "duration=-1",
+            "duration=1e300",
+            "max_pdr=1.5",
+            "max_pdr=nan",
+            "seed",
+            "color=red",
+        ] {
+            assert!(ChaosOptions::from_spec(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn partitioning_faults_are_refused() {
+        // client 10 - drone 1 - drone 2 - server 20
+        let chain = Topology::from_nodes(vec![
+            node(1, NodeType::Drone, &[10, 2]),
+            node(2, NodeType::Drone, &[1, 20]),
+            node(10, NodeType::Client, &[1]),
+            node(20, NodeType::Server, &[2]),
+        ]);
+        assert!(!is_safe(&chain, &Command::Crash(1), 0));
+        assert!(!is_safe(&chain, &Command::Unlink(1, 2), 0));
+        assert!(!is_safe(&chain, &Command::Unlink(10, 1), 0));
+        assert!(is_safe(&ring(), &Command::Crash(1), 1));
+        assert!(is_safe(&ring(), &Command::Unlink(1, 2), 1));
+    }
+
+    #[test]
+    fn faults_keep_the_minimum_links() {
+        // without servers the client can only be protected by its minimum
+        let no_server = Topology::from_nodes(vec![
+            node(1, NodeType::Drone, &[10, 2]),
+            node(2, NodeType::Drone, &[1]),
+            node(10, NodeType::Client, &[1]),
+        ]);
+        assert!(!is_safe(&no_server, &Command::Crash(1), 0));
+        assert!(!is_safe(&no_server, &Command::Unlink(10, 1), 0));
+
+        // the server would keep a single link
+        assert!(!is_safe(&ring(), &Command::Unlink(20, 3), 1));
+        assert!(!is_safe(&ring(), &Command::Crash(4), 1));
+
+        // drones 1 and 2 would keep 2 links
+        assert!(is_safe(&ring(), &Command::Unlink(1, 2), 2));
+        assert!(!is_safe(&ring(), &Command::Unlink(1, 2), 3));
+        assert!(!is_safe(&ring(), &Command::Crash(1), 3));
+    }
+
+    #[test]
+    fn busy_and_profiled_nodes_are_left_alone() {
+        let topology = ring();
+        let options = ChaosOptions::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut picked = 0;
+        for _ in 0..200 {
+            let Some((fault, undo)) = pick_fault(&topology, &options, &[1, 2], &[3], &mut rng)
+            else {
+                continue;
+            };
+            picked += 1;
+            assert!(nodes_of(&fault).iter().all(|id| *id != 1 && *id != 2));
+            match (fault, undo) {
+                (Command::Pdr(id, pdr), Command::Pdr(undo_id, undo_pdr)) => {
+                    assert_ne!(id, 3);
+                    assert!((0.0..=options.max_pdr).contains(&pdr));
+                    assert_eq!((undo_id, undo_pdr), (id, 0.1));
+                }
+                (Command::Crash(id), Command::Reactivate(undo_id)) => assert_eq!(id, undo_id),
+                (Command::Unlink(a, b), Command::Link(c, d)) => assert_eq!((a, b), (c, d)),
+                (fault, undo) => panic!("unexpected fault {} undone by {}", fault, undo),
+            }
+        }
+        assert!(picked > 0);
+
+        // no pdr to perturb, the link 3 - 4 is the only safe fault left
+        for _ in 0..20 {
+            let fault = pick_fault(&topology, &options, &[1, 2], &[3, 4], &mut rng);
+            assert!(matches!(
+                fault,
+                Some((Command::Unlink(3, 4), _)) | Some((Command::Unlink(4, 3), _))
+            ));
+        }
+    }
+
+    #[test]
+    fn same_seed_same_faults() {
+        let topology = ring();
+        let options = ChaosOptions::default();
+        let faults = |seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..20)
+                .map(|_| {
+                    (
+                        interval(options.rate, &mut rng),
+                        pick_fault(&topology, &options, &[], &[], &mut rng),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(faults(7), faults(7));
+        assert_ne!(faults(7), faults(8));
+    }
+}
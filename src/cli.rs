@@ -0,0 +1,132 @@
+use simulation_controller::chaos::ChaosOptions;
+use simulation_controller::generator::GeneratorOptions;
+use simulation_controller::sampling::SamplingPolicy;
+use simulation_controller::scenario::read_scenario;
+
+// command line arguments of the simulation controller
+pub struct Args {
+    pub headless: bool,
+    pub allow_partition: bool, // only warn when a client gets disconnected from a server
+    pub config: String,
+    pub script: Option<String>,
+    pub scenario: Option<String>, // timed actions started once the configuration is loaded
+    pub chaos: Option<ChaosOptions>, // random faults injected once the configuration is loaded
+    pub record: Option<String>,   // trace file where events and actions are recorded
+    pub replay: Option<String>,   // trace file shown instead of running a simulation
+    pub sampling: Option<String>, // changes to the sampling policy, e.g. "dropped=100,edge_rate=5"
+    pub lint: bool,               // only check the configuration file and print the problems found
+    pub generate: Option<GeneratorOptions>, // print a generated configuration and exit
+}
+
+const USAGE: &str = "usage: simulation-controller [options]
+  --config <file.toml>   network-initializer configuration to load
+  --lint                 check the configuration file, print the problems found and exit
+  --generate <spec>      print a generated configuration and exit, e.g. \"shape=grid,drones=9,seed=1\"
+  --headless             run without the GUI, commands are read from stdin or --script
+  --script <file>        commands executed in headless mode
+  --scenario <file.toml> timed actions, e.g. actions = [\"t=5s crash 4\", \"t=8s pdr 7 0.5\"]
+  --chaos <spec>         inject random faults, e.g. \"rate=0.5,seed=7,min_degree=2,downtime=10,duration=3600\"
+  --allow-partition      only warn when a client gets disconnected from a server
+  --record <trace>       record events and actions to a JSON Lines trace
+  --replay <trace>       show a recorded trace instead of running the simulation
+  --sampling <spec>      animated events, e.g. \"dropped=100,edge_rate=5,always_nodes=3 4\"";
+
+impl Args {
+    // parse the arguments given to the process, the first one (program name) is skipped
+    pub fn parse(default_config: &str) -> Result<Args, String> {
+        let mut args = Args {
+            headless: false,
+            allow_partition: false,
+            config: default_config.to_string(),
+            script: None,
+            scenario: None,
+            chaos: None,
+            record: None,
+            replay: None,
+            sampling: None,
+            lint: false,
+            generate: None,
+        };
+
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--headless" => args.headless = true,
+                "--lint" => args.lint = true,
+                "--allow-partition" => args.allow_partition = true,
+                "--config" => match iter.next() {
+                    Some(path) => args.config = path,
+                    None => return Err(format!("--config needs a path\n{}", USAGE)),
+                },
+                "--script" => match iter.next() {
+                    Some(path) => args.script = Some(path),
+                    None => return Err(format!("--script needs a path\n{}", USAGE)),
+                },
+                "--scenario" => match iter.next() {
+                    Some(path) => {
+                        read_scenario(&path)
+                            .map_err(|e| format!("--scenario: {}\n{}", e, USAGE))?;
+                        args.scenario = Some(path);
+                    }
+                    None => return Err(format!("--scenario needs a path\n{}", USAGE)),
+                },
+                "--chaos" => match iter.next() {
+                    Some(spec) => {
+                        let options = ChaosOptions::from_spec(&spec)
+                            .map_err(|e| format!("--chaos: {}\n{}", e, USAGE))?;
+                        args.chaos = Some(options);
+                    }
+                    None => return Err(format!("--chaos needs a value\n{}", USAGE)),
+                },
+                "--record" => match iter.next() {
+                    Some(path) => args.record = Some(path),
+                    None => return Err(format!("--record needs a path\n{}", USAGE)),
+                },
+                "--replay" => match iter.next() {
+                    Some(path) => args.replay = Some(path),
+                    None => return Err(format!("--replay needs a path\n{}", USAGE)),
+                },
+                "--sampling" => match iter.next() {
+                    Some(spec) => {
+                        SamplingPolicy::default()
+                            .apply_spec(&spec)
+                            .map_err(|e| format!("--sampling: {}\n{}", e, USAGE))?;
+                        args.sampling = Some(spec);
+                    }
+                    None => return Err(format!("--sampling needs a value\n{}", USAGE)),
+                },
+                "--generate" => match iter.next() {
+                    Some(spec) => {
+                        let options = GeneratorOptions::from_spec(&spec)
+                            .map_err(|e| format!("--generate: {}\n{}", e, USAGE))?;
+                        args.generate = Some(options);
+                    }
+                    None => return Err(format!("--generate needs a value\n{}", USAGE)),
+                },
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                _ => return Err(format!("unknown argument {}\n{}", arg, USAGE)),
+            }
+        }
+
+        if args.script.is_some() && !args.headless {
+            return Err(format!(
+                "--script can only be used with --headless\n{}",
+                USAGE
+            ));
+        }
+
+        if args.replay.is_some()
+            && (args.headless
+                || args.record.is_some()
+                || args.scenario.is_some()
+                || args.chaos.is_some())
+        {
+            return Err(format!(
+                "--replay cannot be used with --headless, --record, --scenario or --chaos\n{}",
+                USAGE
+            ));
+        }
+
+        Ok(args)
+    }
+}
@@ -0,0 +1,259 @@
+use std::fmt;
+use std::time::Duration;
+
+use wg_internal::network::NodeId;
+
+use crate::group::{parse_action, parse_target, GroupAction, GroupTarget};
+use crate::inject::{parse_injection, Injection};
+use crate::pdr_profile::PdrProfile;
+use crate::topology::NodeType;
+
+// textual command accepted by the controller (one per line), e.g. "crash 4" or "pdr 3 0.4"
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Crash(NodeId),
+    Reactivate(NodeId),
+    Pdr(NodeId, f32),
+    Profile(NodeId, Option<PdrProfile>), // None removes the profile
+    Link(NodeId, NodeId),
+    Unlink(NodeId, NodeId),
+    Add(NodeType, NodeId, f32, Vec<NodeId>), // type, id, pdr (drones only) and neighbours
+    Remove(NodeId),
+    Group(GroupTarget, GroupAction), // action on every node of a group or of a list of ids
+    Inject(Injection),               // crafted packet put in the channel of a node
+    Wait(f32),                       // seconds
+    Quit,
+}
+
+// same syntax accepted by parse_command
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Crash(id) => write!(f, "crash {}", id),
+            Command::Reactivate(id) => write!(f, "reactivate {}", id),
+            Command::Pdr(id, pdr) => write!(f, "pdr {} {}", id, pdr),
+            Command::Profile(id, Some(profile)) => write!(f, "profile {} {}", id, profile),
+            Command::Profile(id, None) => write!(f, "profile {} none", id),
+            Command::Link(id1, id2) => write!(f, "link {} {}", id1, id2),
+            Command::Unlink(id1, id2) => write!(f, "unlink {} {}", id1, id2),
+            Command::Add(node_type, id, pdr, neighbours) => {
+                match node_type {
+                    NodeType::Drone => write!(f, "add drone {} {}", id, pdr)?,
+                    NodeType::Client => write!(f, "add client {}", id)?,
+                    NodeType::Server => write!(f, "add server {}", id)?,
+                }
+                for adj in neighbours {
+                    write!(f, " {}", adj)?;
+                }
+                Ok(())
+            }
+            Command::Remove(id) => write!(f, "remove {}", id),
+            Command::Group(target, action) => write!(f, "group {} {}", target, action),
+            Command::Inject(injection) => write!(f, "inject {}", injection),
+            Command::Wait(seconds) => write!(f, "wait {}", seconds),
+            Command::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+fn parse_id(token: Option<&str>, line: &str) -> Result<NodeId, String> {
+    match token {
+        Some(t) => t
+            .parse::<NodeId>()
+            .map_err(|_| format!("invalid node id '{}' in '{}'", t, line)),
+        None => Err(format!("missing node id in '{}'", line)),
+    }
+}
+
+fn parse_float(token: Option<&str>, line: &str) -> Result<f32, String> {
+    match token {
+        Some(t) => t
+            .parse::<f32>()
+            .map_err(|_| format!("invalid number '{}' in '{}'", t, line)),
+        None => Err(format!("missing number in '{}'", line)),
+    }
+}
+
+// parse a single line, empty lines and comments (#) return None
+pub fn parse_command(line: &str) -> Result<Option<Command>, String> {
+    let line = line.split('#').next().unwrap_or("").trim();
+    let mut tokens = line.split_whitespace();
+
+    let command = match tokens.next() {
+        None => return Ok(None),
+        Some("crash") => Command::Crash(parse_id(tokens.next(), line)?),
+        Some("reactivate") => Command::Reactivate(parse_id(tokens.next(), line)?),
+        Some("pdr") => {
+            let id = parse_id(tokens.next(), line)?;
+            let pdr = parse_float(tokens.next(), line)?;
+            if !(0.0..=1.0).contains(&pdr) {
+                return Err(format!("pdr must be in [0,1] in '{}'", line));
+            }
+            Command::Pdr(id, pdr)
+        }
+        // profile <id> <spec> | profile <id> none
+        Some("profile") => {
+            let id = parse_id(tokens.next(), line)?;
+            match tokens.next() {
+                Some("none") => Command::Profile(id, None),
+                Some(spec) => Command::Profile(
+                    id,
+                    Some(PdrProfile::from_spec(spec).map_err(|e| format!("{} in '{}'", e, line))?),
+                ),
+                None => return Err(format!("missing pdr profile in '{}'", line)),
+            }
+        }
+        Some("link") => Command::Link(
+            parse_id(tokens.next(), line)?,
+            parse_id(tokens.next(), line)?,
+        ),
+        Some("unlink") => Command::Unlink(
+            parse_id(tokens.next(), line)?,
+            parse_id(tokens.next(), line)?,
+        ),
+        // add drone <id> <pdr> <neighbours...> | add client|server <id> <neighbours...>
+        Some("add") => {
+            let node_type = match tokens.next() {
+                Some("drone") => NodeType::Drone,
+                Some("client") => NodeType::Client,
+                Some("server") => NodeType::Server,
+                _ => return Err(format!("expected drone, client or server in '{}'", line)),
+            };
+            let id = parse_id(tokens.next(), line)?;
+            let mut pdr = 0.0;
+            if node_type == NodeType::Drone {
+                pdr = parse_float(tokens.next(), line)?;
+                if !(0.0..=1.0).contains(&pdr) {
+                    return Err(format!("pdr must be in [0,1] in '{}'", line));
+                }
+            }
+            let neighbours = tokens
+                .by_ref()
+                .map(|t| parse_id(Some(t), line))
+                .collect::<Result<Vec<NodeId>, String>>()?;
+            Command::Add(node_type, id, pdr, neighbours)
+        }
+        Some("remove") => Command::Remove(parse_id(tokens.next(), line)?),
+        // group <name>|<id,id,...> crash|reactivate|pdr <value>|clique|chain|disconnect
+        Some("group") => {
+            let target = match tokens.next() {
+                Some(token) => parse_target(token).map_err(|e| format!("{} in '{}'", e, line))?,
+                None => return Err(format!("missing group in '{}'", line)),
+            };
+            let action =
+                parse_action(tokens.by_ref()).map_err(|e| format!("{} in '{}'", e, line))?;
+            Command::Group(target, action)
+        }
+        // inject <node> <session> <hop_index> <hops> <type> <fields...>, see parse_injection
+        Some("inject") => {
+            let args = line["inject".len()..].trim();
+            let injection = parse_injection(args).map_err(|e| format!("{} in '{}'", e, line))?;
+            return Ok(Some(Command::Inject(injection)));
+        }
+        Some("wait") => {
+            let seconds = parse_float(tokens.next(), line)?;
+            // inf, nan, negative and too large waits cannot be slept
+            if Duration::try_from_secs_f32(seconds).is_err() {
+                return Err(format!("invalid wait of {} seconds in '{}'", seconds, line));
+            }
+            Command::Wait(seconds)
+        }
+        Some("quit") | Some("exit") => Command::Quit,
+        Some(other) => return Err(format!("unknown command '{}'", other)),
+    };
+
+    if tokens.next().is_some() {
+        return Err(format!("too many arguments in '{}'", line));
+    }
+    Ok(Some(command))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse_command("crash 4"), Ok(Some(Command::Crash(4))));
+        assert_eq!(parse_command("pdr 3 0.4"), Ok(Some(Command::Pdr(3, 0.4))));
+        assert_eq!(
+            parse_command("add drone 12 0.1 1 2"),
+            Ok(Some(Command::Add(NodeType::Drone, 12, 0.1, vec![1, 2])))
+        );
+        assert_eq!(
+            parse_command("add client 20 1"),
+            Ok(Some(Command::Add(NodeType::Client, 20, 0.0, vec![1])))
+        );
+        assert_eq!(
+            parse_command("  unlink 1 8 # flap"),
+            Ok(Some(Command::Unlink(1, 8)))
+        );
+        assert_eq!(parse_command("exit"), Ok(Some(Command::Quit)));
+    }
+
+    #[test]
+    fn empty_lines_and_comments_are_skipped() {
+        assert_eq!(parse_command(""), Ok(None));
+        assert_eq!(parse_command("   "), Ok(None));
+        assert_eq!(parse_command("# crash 4"), Ok(None));
+    }
+
+    #[test]
+    fn rejects_invalid_commands() {
+        for line in [
+            "explode 4",
+            "crash",
+            "crash 256",
+            "crash -1",
+            "crash 4 5",
+            "pdr 3 1.5",
+            "pdr 3 -0.1",
+            "pdr 3 NaN",
+            "link 1",
+            "add router 3",
+            "add drone 3 2.0 1",
+        ] {
+            assert!(parse_command(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn rejects_waits_that_cannot_be_slept() {
+        assert_eq!(parse_command("wait 1.5"), Ok(Some(Command::Wait(1.5))));
+        assert_eq!(parse_command("wait 0"), Ok(Some(Command::Wait(0.0))));
+        for line in [
+            "wait inf",
+            "wait -inf",
+            "wait NaN",
+            "wait -1",
+            "wait 1e30",
+            "wait",
+        ] {
+            assert!(parse_command(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn display_is_parsed_back() {
+        for command in [
+            Command::Crash(4),
+            Command::Reactivate(4),
+            Command::Pdr(3, 0.25),
+            Command::Link(1, 8),
+            Command::Unlink(1, 8),
+            Command::Add(NodeType::Drone, 12, 0.1, vec![1, 2]),
+            Command::Add(NodeType::Server, 30, 0.0, vec![4, 5]),
+            Command::Remove(12),
+            Command::Profile(3, None),
+            Command::Wait(2.5),
+            Command::Quit,
+        ] {
+            assert_eq!(
+                parse_command(&command.to_string()),
+                Ok(Some(command.clone())),
+                "{}",
+                command
+            );
+        }
+    }
+}
@@ -0,0 +1,1184 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use crossbeam::channel::{never, unbounded, Receiver, Sender};
+use crossbeam::select;
+
+use network_initializer::channel::Channel;
+use network_initializer::factory::{create_client, create_drone, create_server};
+use network_initializer::{errors::ConfigError, NetworkInitializer};
+
+use wg_internal::controller::{DroneCommand, DroneEvent};
+use wg_internal::network::NodeId;
+use wg_internal::packet::Packet;
+
+use logger::Logger;
+
+use crate::command::Command;
+use crate::event_loop::LoopControl;
+use crate::group::{expand, read_groups, GroupAction, GroupTarget};
+use crate::inject::Injection;
+use crate::link::{LinkProfile, Relay, MAX_DELAY};
+use crate::pdr_profile::{read_pdr_profiles, DrivenPdr, PdrProfile};
+use crate::tap::{Capture, SharedCapture};
+use crate::topology::{Node, NodeType, Topology};
+use crate::trace::Recorder;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControllerError {
+    UnknownNode(NodeId),
+    NotADrone(NodeId),
+    Crashed(NodeId),
+    NotCrashed(NodeId),
+    AlreadyLinked(NodeId, NodeId),
+    NotLinked(NodeId, NodeId),
+    InvalidLink(NodeId, NodeId),
+    InvalidPdr(f32),
+    InvalidProfile(LinkProfile),
+    NoChannel(NodeId),
+    IdInUse(NodeId),
+    NotTapped(NodeId, NodeId),
+    UnknownGroup(String),
+    // actions of a group that were refused, out of the total number of actions
+    Batch {
+        failed: Vec<(Command, ControllerError)>,
+        total: usize,
+    },
+    WouldPartition(Vec<(NodeId, NodeId)>), // (client, server) pairs that would be disconnected
+}
+
+impl fmt::Display for ControllerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControllerError::UnknownNode(id) => write!(f, "node {} does not exist", id),
+            ControllerError::NotADrone(id) => write!(f, "node {} is not a drone", id),
+            ControllerError::Crashed(id) => write!(f, "node {} has crashed", id),
+            ControllerError::NotCrashed(id) => write!(f, "node {} has not crashed", id),
+            ControllerError::AlreadyLinked(id1, id2) => {
+                write!(f, "nodes {} and {} are already linked", id1, id2)
+            }
+            ControllerError::NotLinked(id1, id2) => {
+                write!(f, "nodes {} and {} are not linked", id1, id2)
+            }
+            ControllerError::InvalidLink(id1, id2) => {
+                write!(f, "nodes {} and {} cannot be linked", id1, id2)
+            }
+            ControllerError::InvalidPdr(pdr) => write!(f, "pdr {} is not in [0,1]", pdr),
+            ControllerError::InvalidProfile(profile) => write!(
+                f,
+                "delay {} ms or jitter {} ms is longer than {} ms",
+                profile.delay.as_millis(),
+                profile.jitter.as_millis(),
+                MAX_DELAY.as_millis()
+            ),
+            ControllerError::NoChannel(id) => write!(f, "no channel for node {}", id),
+            ControllerError::IdInUse(id) => write!(f, "id {} is already used", id),
+            ControllerError::NotTapped(id1, id2) => {
+                write!(f, "link {} - {} is not tapped", id1, id2)
+            }
+            ControllerError::UnknownGroup(name) => write!(f, "group {} does not exist", name),
+            ControllerError::Batch { failed, total } => {
+                let reasons: Vec<String> = failed
+                    .iter()
+                    .map(|(command, e)| format!("{} ({})", command, e))
+                    .collect();
+                write!(
+                    f,
+                    "{} of {} actions failed: {}",
+                    failed.len(),
+                    total,
+                    reasons.join("; ")
+                )
+            }
+            ControllerError::WouldPartition(pairs) => {
+                write!(f, "{}", partition_reason(pairs))
+            }
+        }
+    }
+}
+
+fn partition_reason(pairs: &[(NodeId, NodeId)]) -> String {
+    let pairs: Vec<String> = pairs
+        .iter()
+        .map(|(client, server)| format!("client {} - server {}", client, server))
+        .collect();
+    format!("the network would be partitioned: {}", pairs.join(", "))
+}
+
+// what to do when a crash or a link removal would disconnect a client from a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectivityCheck {
+    Refuse,
+    Warn,
+    Disabled,
+}
+
+// notification sent to the observers (e.g. the GUI) after every change of the topology
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControllerUpdate {
+    TopologyLoaded,
+    Crashed(NodeId),
+    Reactivated(NodeId),
+    LinkAdded(NodeId, NodeId),
+    LinkRemoved(NodeId, NodeId),
+    PdrChanged(NodeId, f32),
+    NodeAdded(NodeId),
+    NodeRemoved(NodeId),
+    LinkProfileChanged(NodeId, NodeId), // emulation of the direction from the first to the second
+    TapChanged(NodeId, NodeId),
+    PdrProfileChanged(NodeId),
+    Warning(String),
+}
+
+// owns the running simulation: its topology, the command senders and the packet channels
+pub struct SimulationController {
+    logger: Arc<Mutex<Logger>>,
+    network_initializer: Arc<Mutex<Option<NetworkInitializer>>>,
+    simulation: Option<JoinHandle<()>>,
+    receiver: Option<Receiver<DroneEvent>>,
+    event_sender: Option<Sender<DroneEvent>>, // given to the drones spawned by the controller
+    senders: HashMap<NodeId, Sender<DroneCommand>>,
+    channels: HashMap<NodeId, Channel<Packet>>,
+    topology: Topology,
+    crashed_neighbours: HashMap<NodeId, Vec<NodeId>>, // original neighbours of the crashed drones
+    link_profiles: HashMap<(NodeId, NodeId), LinkProfile>, // emulated directions (from, to)
+    relays: HashMap<(NodeId, NodeId), Relay>,
+    captures: HashMap<(NodeId, NodeId), SharedCapture>, // by link (smaller id first), kept after the tap
+    tapped: HashSet<(NodeId, NodeId)>,
+    pdr_profiles: HashMap<NodeId, DrivenPdr>,
+    groups: BTreeMap<String, Vec<NodeId>>, // named groups of nodes of the configuration
+    connectivity_check: ConnectivityCheck,
+    subscribers: Vec<Sender<ControllerUpdate>>,
+    event_loops: Vec<Sender<LoopControl>>,
+    recorder: Arc<Mutex<Option<Recorder>>>,
+}
+
+impl SimulationController {
+    pub fn new(logger: Arc<Mutex<Logger>>) -> SimulationController {
+        SimulationController {
+            logger,
+            network_initializer: Arc::new(Mutex::new(None)),
+            simulation: None,
+            receiver: None,
+            event_sender: None,
+            senders: HashMap::new(),
+            channels: HashMap::new(),
+            topology: Topology::default(),
+            crashed_neighbours: HashMap::new(),
+            link_profiles: HashMap::new(),
+            relays: HashMap::new(),
+            captures: HashMap::new(),
+            tapped: HashSet::new(),
+            pdr_profiles: HashMap::new(),
+            groups: BTreeMap::new(),
+            connectivity_check: ConnectivityCheck::Refuse,
+            subscribers: vec![],
+            event_loops: vec![],
+            recorder: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn topology(&self) -> &Topology {
+        &self.topology
+    }
+
+    // control channel of a new event loop: it receives the DroneEvent receiver of the current
+    // simulation and of the ones loaded later (the events are shared among all the loops)
+    pub fn event_loop_control(&mut self) -> Receiver<LoopControl> {
+        let (sender, receiver) = unbounded();
+        if let Some(ref events) = self.receiver {
+            let _ = sender.send(LoopControl::Events(events.clone()));
+        }
+        self.event_loops.push(sender);
+        receiver
+    }
+
+    pub fn stop_event_loops(&mut self) {
+        for event_loop in self.event_loops.drain(..) {
+            let _ = event_loop.send(LoopControl::Shutdown);
+        }
+    }
+
+    pub fn set_connectivity_check(&mut self, check: ConnectivityCheck) {
+        self.connectivity_check = check;
+    }
+
+    // record the events and the actions of the simulation to a trace file
+    pub fn start_recording(&mut self, path: &str) -> std::io::Result<()> {
+        let recorder = Recorder::create(path)?;
+        recorder.record_topology(&self.topology);
+        if let Some(previous) = self.recorder.lock().unwrap().replace(recorder) {
+            previous.finish();
+        }
+        self.logger
+            .lock()
+            .unwrap()
+            .log_info(&format!("Recording trace to {}", path));
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.lock().unwrap().take() {
+            recorder.finish();
+            self.logger.lock().unwrap().log_info("Recording stopped");
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.lock().unwrap().is_some()
+    }
+
+    // write the current topology to a configuration file that can be loaded again
+    pub fn save_topology(&self, path: &str) -> std::io::Result<()> {
+        let profiles: HashMap<NodeId, PdrProfile> = self
+            .pdr_profiles
+            .iter()
+            .map(|(id, driven)| (*id, driven.profile.clone()))
+            .collect();
+        std::fs::write(path, self.topology.to_config_with(&profiles, &self.groups))?;
+        self.logger
+            .lock()
+            .unwrap()
+            .log_info(&format!("Topology saved to {}", path));
+        Ok(())
+    }
+
+    // register a new observer of the topology changes
+    pub fn subscribe(&mut self) -> Receiver<ControllerUpdate> {
+        let (sender, receiver) = unbounded();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    fn notify(&mut self, update: ControllerUpdate) {
+        if let Some(ref recorder) = *self.recorder.lock().unwrap() {
+            recorder.record_update(&update, &self.topology);
+        }
+        self.subscribers.retain(|s| s.send(update.clone()).is_ok());
+    }
+
+    // send DroneCommand to a node
+    fn send_command(&self, id: NodeId, command: DroneCommand) {
+        if let Some(sender) = self.senders.get(&id) {
+            match sender.send(command.clone()) {
+                Ok(_) => {
+                    self.logger
+                        .lock()
+                        .unwrap()
+                        .log_debug(&format!("{:?} succesfully sent to Node{}", command, id));
+                }
+                Err(e) => {
+                    self.logger.lock().unwrap().log_debug(&format!(
+                        "Error sending DroneCommand to drone {}: {:?}",
+                        id, e
+                    ));
+                }
+            }
+        } else {
+            self.logger
+                .lock()
+                .unwrap()
+                .log_debug(&format!("No sender for drone {}", id));
+        }
+    }
+
+    // load a new configuration: the current simulation is stopped and the new one is started
+    pub fn load_config(&mut self, path: &str) -> Result<(), ConfigError> {
+        let mut net_init = NetworkInitializer::new(Some(path))?;
+        // the profiles are checked by the linter, here an invalid one is only reported
+        let profiles = read_pdr_profiles(path).unwrap_or_else(|e| {
+            self.logger.lock().unwrap().log_warn(&e);
+            HashMap::new()
+        });
+        let groups = read_groups(path).unwrap_or_else(|e| {
+            self.logger.lock().unwrap().log_warn(&e);
+            BTreeMap::new()
+        });
+
+        // stop the current simulation and wait for it to terminate
+        let reload = self.simulation.is_some();
+        self.shutdown();
+        if let Some(handle) = self.simulation.take() {
+            let _ = handle.join();
+        }
+
+        // the events of the simulation are forwarded to a channel owned by the controller,
+        // so that the drones spawned later can report to the same receiver
+        let (event_sender, spawned_receiver) = unbounded();
+        let (forward, event_receiver) = unbounded();
+        let sc_receiver = net_init.get_controller_recv();
+        let recorder = self.recorder.clone();
+        thread::spawn(move || forward_events(sc_receiver, spawned_receiver, forward, recorder));
+        self.event_loops
+            .retain(|l| l.send(LoopControl::Events(event_receiver.clone())).is_ok());
+        self.receiver = Some(event_receiver);
+        self.event_sender = Some(event_sender);
+        self.senders = net_init.get_controller_senders();
+        self.channels = net_init.get_channels();
+        let nodes = net_init.get_nodes();
+        self.topology = Topology::from_parsed(nodes.0, nodes.1, nodes.2);
+        self.crashed_neighbours.clear();
+        self.link_profiles.clear();
+        self.relays.clear();
+        self.captures.clear();
+        self.tapped.clear();
+        self.pdr_profiles = profiles
+            .into_iter()
+            .filter(|(id, _)| self.topology.drones().any(|d| d.id == *id))
+            .map(|(id, profile)| (id, DrivenPdr::new(id, profile)))
+            .collect();
+        self.groups = groups;
+        *self.network_initializer.lock().unwrap() = Some(net_init);
+
+        if reload {
+            let _ = std::fs::remove_dir_all("db/");
+        }
+        self.simulation = Some(run_simulation_thread(
+            self.logger.clone(),
+            self.network_initializer.clone(),
+        ));
+
+        self.notify(ControllerUpdate::TopologyLoaded);
+        Ok(())
+    }
+
+    // handle of the thread running the simulation, to be joined before exiting
+    pub fn take_simulation_thread(&mut self) -> Option<JoinHandle<()>> {
+        self.simulation.take()
+    }
+
+    // refuse or warn about a change that disconnects some clients from some servers
+    fn check_partition(&mut self, pairs: Vec<(NodeId, NodeId)>) -> Result<(), ControllerError> {
+        if pairs.is_empty() {
+            return Ok(());
+        }
+        match self.connectivity_check {
+            ConnectivityCheck::Refuse => Err(ControllerError::WouldPartition(pairs)),
+            ConnectivityCheck::Warn => {
+                let reason = partition_reason(&pairs);
+                self.logger.lock().unwrap().log_warn(&reason);
+                self.notify(ControllerUpdate::Warning(reason));
+                Ok(())
+            }
+            ConnectivityCheck::Disabled => Ok(()),
+        }
+    }
+
+    fn check_drone(&self, id: NodeId) -> Result<(), ControllerError> {
+        match self.topology.get(id) {
+            None => Err(ControllerError::UnknownNode(id)),
+            Some(n) if n.node_type != NodeType::Drone => Err(ControllerError::NotADrone(id)),
+            Some(n) if n.crashed => Err(ControllerError::Crashed(id)),
+            Some(_) => Ok(()),
+        }
+    }
+
+    pub fn crash(&mut self, id: NodeId) -> Result<(), ControllerError> {
+        self.check_drone(id)?;
+        let pairs = self.topology.partitioned_by(|t| {
+            t.crash(id);
+        });
+        self.check_partition(pairs)?;
+
+        // send crash command to drone and remove the links communicating with it
+        self.send_command(id, DroneCommand::Crash);
+        let neighbours = self.topology.get(id).unwrap().neighbours.clone();
+        for adj in neighbours {
+            self.send_command(adj, DroneCommand::RemoveSender(id));
+        }
+        let neighbours = self.topology.crash(id);
+        self.crashed_neighbours.insert(id, neighbours);
+
+        // drop its channels, senders and relays
+        self.channels.remove(&id);
+        self.senders.remove(&id);
+        self.relays.retain(|(from, to), _| *from != id && *to != id);
+
+        self.notify(ControllerUpdate::Crashed(id));
+        Ok(())
+    }
+
+    // respawn a crashed drone with the same id, pdr and original neighbours
+    pub fn reactivate(&mut self, id: NodeId) -> Result<(), ControllerError> {
+        match self.topology.get(id) {
+            None => return Err(ControllerError::UnknownNode(id)),
+            Some(n) if n.node_type != NodeType::Drone => {
+                return Err(ControllerError::NotADrone(id))
+            }
+            Some(n) if !n.crashed => return Err(ControllerError::NotCrashed(id)),
+            Some(_) => {}
+        }
+        if self.event_sender.is_none() {
+            return Err(ControllerError::NoChannel(id));
+        }
+
+        let pdr = self.topology.get(id).unwrap().pdr;
+        let neighbours: Vec<NodeId> = self
+            .crashed_neighbours
+            .remove(&id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|adj| self.topology.is_active(*adj) && self.channels.contains_key(adj))
+            .collect();
+        self.spawn_node(NodeType::Drone, id, pdr, &neighbours)?;
+
+        self.topology.reactivate(id, &neighbours);
+        self.notify(ControllerUpdate::Reactivated(id));
+        Ok(())
+    }
+
+    // create the channels of a new node, run it in its own thread and link it to its neighbours
+    fn spawn_node(
+        &mut self,
+        node_type: NodeType,
+        id: NodeId,
+        pdr: f32,
+        neighbours: &[NodeId],
+    ) -> Result<(), ControllerError> {
+        let Some(controller_send) = self.event_sender.clone() else {
+            return Err(ControllerError::NoChannel(id));
+        };
+        if let Some(adj) = neighbours
+            .iter()
+            .find(|adj| !self.channels.contains_key(*adj))
+        {
+            return Err(ControllerError::NoChannel(*adj));
+        }
+
+        let (packet_send, packet_recv) = unbounded::<Packet>();
+        let (command_send, command_recv) = unbounded::<DroneCommand>();
+        self.channels.insert(
+            id,
+            Channel {
+                sender: packet_send,
+                receiver: packet_recv,
+            },
+        );
+        self.senders.insert(id, command_send);
+        let mut packet_senders = HashMap::new();
+        for adj in neighbours {
+            packet_senders.insert(*adj, self.link_sender(id, *adj)?);
+        }
+        let node_packet_recv = self.channels[&id].receiver.clone();
+        thread::spawn(move || match node_type {
+            NodeType::Drone => {
+                let mut drone = create_drone(
+                    id,
+                    controller_send,
+                    command_recv,
+                    node_packet_recv,
+                    packet_senders,
+                    pdr,
+                );
+                drone.run();
+            }
+            NodeType::Client => {
+                let mut client = create_client(
+                    id,
+                    controller_send,
+                    command_recv,
+                    node_packet_recv,
+                    packet_senders,
+                );
+                client.run();
+            }
+            NodeType::Server => {
+                let mut server = create_server(
+                    id,
+                    controller_send,
+                    command_recv,
+                    node_packet_recv,
+                    packet_senders,
+                );
+                server.run();
+            }
+        });
+
+        // the neighbours can send packets to the new node
+        for adj in neighbours {
+            let sender = self.link_sender(*adj, id)?;
+            self.send_command(*adj, DroneCommand::AddSender(id, sender));
+        }
+        Ok(())
+    }
+
+    // spawn a new node with an unused id, clients and servers can only be linked to drones
+    pub fn add_node(
+        &mut self,
+        node_type: NodeType,
+        id: NodeId,
+        pdr: f32,
+        neighbours: &[NodeId],
+    ) -> Result<(), ControllerError> {
+        if self.topology.get(id).is_some() {
+            return Err(ControllerError::IdInUse(id));
+        }
+        if node_type == NodeType::Drone && !(0.0..=1.0).contains(&pdr) {
+            return Err(ControllerError::InvalidPdr(pdr));
+        }
+        let mut unique: Vec<NodeId> = vec![];
+        for adj in neighbours {
+            match self.topology.get(*adj) {
+                None => return Err(ControllerError::UnknownNode(*adj)),
+                Some(n) if n.crashed => return Err(ControllerError::Crashed(*adj)),
+                Some(n) if node_type != NodeType::Drone && n.node_type != NodeType::Drone => {
+                    return Err(ControllerError::InvalidLink(id, *adj))
+                }
+                Some(_) => {}
+            }
+            if *adj == id {
+                return Err(ControllerError::InvalidLink(id, *adj));
+            }
+            if !unique.contains(adj) {
+                unique.push(*adj);
+            }
+        }
+
+        self.spawn_node(node_type, id, pdr, &unique)?;
+
+        self.topology.add_node(Node {
+            id,
+            node_type,
+            pdr: if node_type == NodeType::Drone {
+                pdr
+            } else {
+                0.0
+            },
+            crashed: false,
+            neighbours: unique,
+        });
+        self.notify(ControllerUpdate::NodeAdded(id));
+        Ok(())
+    }
+
+    // stop a node (if still running) and remove it from the topology
+    pub fn remove_node(&mut self, id: NodeId) -> Result<(), ControllerError> {
+        let Some(node) = self.topology.get(id) else {
+            return Err(ControllerError::UnknownNode(id));
+        };
+        let crashed = node.crashed;
+        let neighbours = node.neighbours.clone();
+        let pairs = self.topology.partitioned_by(|t| t.remove_node(id));
+        self.check_partition(pairs)?;
+
+        if !crashed {
+            self.send_command(id, DroneCommand::Crash);
+            for adj in neighbours {
+                self.send_command(adj, DroneCommand::RemoveSender(id));
+            }
+        }
+        self.channels.remove(&id);
+        self.senders.remove(&id);
+        self.relays.retain(|(from, to), _| *from != id && *to != id);
+        self.link_profiles
+            .retain(|(from, to), _| *from != id && *to != id);
+        // a later node with the same id does not inherit the taps and the captures
+        self.tapped.retain(|(id1, id2)| *id1 != id && *id2 != id);
+        self.captures
+            .retain(|(id1, id2), _| *id1 != id && *id2 != id);
+        self.pdr_profiles.remove(&id);
+        self.crashed_neighbours.remove(&id);
+        for original in self.crashed_neighbours.values_mut() {
+            original.retain(|adj| *adj != id);
+        }
+
+        self.topology.remove_node(id);
+        self.notify(ControllerUpdate::NodeRemoved(id));
+        Ok(())
+    }
+
+    pub fn add_link(&mut self, id1: NodeId, id2: NodeId) -> Result<(), ControllerError> {
+        for id in [id1, id2] {
+            match self.topology.get(id) {
+                None => return Err(ControllerError::UnknownNode(id)),
+                Some(n) if n.crashed => return Err(ControllerError::Crashed(id)),
+                Some(_) => {}
+            }
+        }
+        // there cannot be a direct connection between clients and servers
+        if id1 == id2
+            || (self.topology.get(id1).unwrap().node_type != NodeType::Drone
+                && self.topology.get(id2).unwrap().node_type != NodeType::Drone)
+        {
+            return Err(ControllerError::InvalidLink(id1, id2));
+        }
+        if self.topology.are_linked(id1, id2) {
+            return Err(ControllerError::AlreadyLinked(id1, id2));
+        }
+
+        // the channels are not created because they already exist, they are only cloned
+        // (or relayed, if the direction is emulated)
+        let sender_id2 = self.link_sender(id1, id2)?;
+        let sender_id1 = self.link_sender(id2, id1)?;
+        self.send_command(id1, DroneCommand::AddSender(id2, sender_id2));
+        self.send_command(id2, DroneCommand::AddSender(id1, sender_id1));
+
+        self.topology.add_link(id1, id2);
+        self.notify(ControllerUpdate::LinkAdded(id1, id2));
+        Ok(())
+    }
+
+    // the channels are not removed because the nodes are still alive
+    pub fn remove_link(&mut self, id1: NodeId, id2: NodeId) -> Result<(), ControllerError> {
+        if !self.topology.are_linked(id1, id2) {
+            return Err(ControllerError::NotLinked(id1, id2));
+        }
+        let pairs = self.topology.partitioned_by(|t| t.remove_link(id1, id2));
+        self.check_partition(pairs)?;
+
+        self.send_command(id1, DroneCommand::RemoveSender(id2));
+        self.send_command(id2, DroneCommand::RemoveSender(id1));
+        // the relays stop once the nodes drop their senders, the profiles are kept for a new link
+        self.relays.remove(&(id1, id2));
+        self.relays.remove(&(id2, id1));
+
+        self.topology.remove_link(id1, id2);
+        self.notify(ControllerUpdate::LinkRemoved(id1, id2));
+        Ok(())
+    }
+
+    // a static pdr replaces the profile of the drone
+    pub fn set_pdr(&mut self, id: NodeId, pdr: f32) -> Result<(), ControllerError> {
+        self.check_drone(id)?;
+        if !(0.0..=1.0).contains(&pdr) {
+            return Err(ControllerError::InvalidPdr(pdr));
+        }
+        if self.pdr_profiles.remove(&id).is_some() {
+            self.notify(ControllerUpdate::PdrProfileChanged(id));
+        }
+        self.apply_pdr(id, pdr);
+        Ok(())
+    }
+
+    fn apply_pdr(&mut self, id: NodeId, pdr: f32) {
+        self.send_command(id, DroneCommand::SetPacketDropRate(pdr));
+
+        self.topology.set_pdr(id, pdr);
+        self.notify(ControllerUpdate::PdrChanged(id, pdr));
+    }
+
+    pub fn pdr_profile(&self, id: NodeId) -> Option<&PdrProfile> {
+        self.pdr_profiles.get(&id).map(|driven| &driven.profile)
+    }
+
+    // drones driven by a profile, sorted by id
+    pub fn pdr_profiles(&self) -> Vec<(NodeId, PdrProfile)> {
+        let mut profiles: Vec<(NodeId, PdrProfile)> = self
+            .pdr_profiles
+            .iter()
+            .map(|(id, driven)| (*id, driven.profile.clone()))
+            .collect();
+        profiles.sort_by_key(|(id, _)| *id);
+        profiles
+    }
+
+    // start a profile from now (None goes back to the current pdr, kept as a static one)
+    pub fn set_pdr_profile(
+        &mut self,
+        id: NodeId,
+        profile: Option<PdrProfile>,
+    ) -> Result<(), ControllerError> {
+        self.check_drone(id)?;
+        match profile {
+            Some(profile) => {
+                self.logger
+                    .lock()
+                    .unwrap()
+                    .log_info(&format!("Pdr profile of drone {}: {}", id, profile));
+                self.pdr_profiles.insert(id, DrivenPdr::new(id, profile));
+            }
+            None => {
+                if self.pdr_profiles.remove(&id).is_none() {
+                    return Ok(());
+                }
+            }
+        }
+        self.notify(ControllerUpdate::PdrProfileChanged(id));
+        self.update_pdr_profiles();
+        Ok(())
+    }
+
+    // send the current pdr of the profiles that changed since the last update, crashed drones
+    // keep their profile and get the current value when reactivated
+    pub fn update_pdr_profiles(&mut self) {
+        let now = Instant::now();
+        let mut changed = vec![];
+        for (id, driven) in self.pdr_profiles.iter_mut() {
+            let pdr = driven.pdr(now);
+            match self.topology.get(*id) {
+                Some(node) if !node.crashed && node.pdr != pdr => changed.push((*id, pdr)),
+                _ => {}
+            }
+        }
+        for (id, pdr) in changed {
+            self.apply_pdr(id, pdr);
+        }
+    }
+
+    // sender given to `from` to reach `to`: the channel of `to`, or the input of a new relay
+    // if the direction is emulated or the link is tapped
+    fn link_sender(&mut self, from: NodeId, to: NodeId) -> Result<Sender<Packet>, ControllerError> {
+        let direct = match self.channels.get(&to) {
+            Some(ch) => ch.sender.clone(),
+            None => return Err(ControllerError::NoChannel(to)),
+        };
+        let profile = self.link_profiles.get(&(from, to)).copied();
+        let tap = self.tap_of(from, to);
+        if profile.is_none() && tap.is_none() {
+            self.relays.remove(&(from, to));
+            return Ok(direct);
+        }
+        let (relay, input) = Relay::start(from, to, profile.unwrap_or_default(), tap, direct);
+        self.relays.insert((from, to), relay);
+        Ok(input)
+    }
+
+    // capture of the link, if it is tapped
+    fn tap_of(&self, id1: NodeId, id2: NodeId) -> Option<SharedCapture> {
+        let link = (id1.min(id2), id1.max(id2));
+        if !self.tapped.contains(&link) {
+            return None;
+        }
+        self.captures.get(&link).cloned()
+    }
+
+    pub fn link_profile(&self, from: NodeId, to: NodeId) -> LinkProfile {
+        self.link_profiles
+            .get(&(from, to))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    // emulated directions, sorted by (from, to)
+    pub fn link_profiles(&self) -> Vec<(NodeId, NodeId, LinkProfile)> {
+        let mut profiles: Vec<(NodeId, NodeId, LinkProfile)> = self
+            .link_profiles
+            .iter()
+            .map(|((from, to), profile)| (*from, *to, *profile))
+            .collect();
+        profiles.sort_by_key(|(from, to, _)| (*from, *to));
+        profiles
+    }
+
+    // emulate the delay, jitter and bandwidth of the direction from -> to of a link; the node
+    // `from` gets a relay in place of the sender of `to` (AddSender replaces the old sender),
+    // an instantaneous profile restores the direct sender
+    pub fn set_link_profile(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        profile: LinkProfile,
+    ) -> Result<(), ControllerError> {
+        for id in [from, to] {
+            match self.topology.get(id) {
+                None => return Err(ControllerError::UnknownNode(id)),
+                Some(n) if n.crashed => return Err(ControllerError::Crashed(id)),
+                Some(_) => {}
+            }
+        }
+        if !self.topology.are_linked(from, to) {
+            return Err(ControllerError::NotLinked(from, to));
+        }
+        if !profile.is_valid() {
+            return Err(ControllerError::InvalidProfile(profile));
+        }
+
+        if profile.is_instantaneous() {
+            self.link_profiles.remove(&(from, to));
+        } else {
+            self.link_profiles.insert((from, to), profile);
+        }
+        // a running relay only changes its profile (it is still needed if the link is tapped)
+        let needs_relay = !profile.is_instantaneous() || self.tap_of(from, to).is_some();
+        let updated = needs_relay
+            && self
+                .relays
+                .get(&(from, to))
+                .is_some_and(|r| r.set_profile(profile));
+        if !updated {
+            let sender = self.link_sender(from, to)?;
+            self.send_command(from, DroneCommand::AddSender(to, sender));
+        }
+
+        self.notify(ControllerUpdate::LinkProfileChanged(from, to));
+        Ok(())
+    }
+
+    // record every packet crossing the link in both directions, a previous capture of the same
+    // link is replaced
+    pub fn tap_link(&mut self, id1: NodeId, id2: NodeId) -> Result<(), ControllerError> {
+        for id in [id1, id2] {
+            match self.topology.get(id) {
+                None => return Err(ControllerError::UnknownNode(id)),
+                Some(n) if n.crashed => return Err(ControllerError::Crashed(id)),
+                Some(_) => {}
+            }
+        }
+        if !self.topology.are_linked(id1, id2) {
+            return Err(ControllerError::NotLinked(id1, id2));
+        }
+
+        let link = (id1.min(id2), id1.max(id2));
+        let capture = Capture::shared();
+        self.captures.insert(link, capture.clone());
+        self.tapped.insert(link);
+        for (from, to) in [(id1, id2), (id2, id1)] {
+            let updated = self
+                .relays
+                .get(&(from, to))
+                .is_some_and(|r| r.set_tap(Some(capture.clone())));
+            if !updated {
+                let sender = self.link_sender(from, to)?;
+                self.send_command(from, DroneCommand::AddSender(to, sender));
+            }
+        }
+
+        self.logger
+            .lock()
+            .unwrap()
+            .log_info(&format!("Tapping link {} - {}", id1, id2));
+        self.notify(ControllerUpdate::TapChanged(id1, id2));
+        Ok(())
+    }
+
+    // stop recording the packets of the link, the capture is kept until discarded
+    pub fn untap_link(&mut self, id1: NodeId, id2: NodeId) -> Result<(), ControllerError> {
+        let link = (id1.min(id2), id1.max(id2));
+        if !self.tapped.remove(&link) {
+            return Err(ControllerError::NotTapped(id1, id2));
+        }
+        for (from, to) in [(id1, id2), (id2, id1)] {
+            if self.link_profiles.contains_key(&(from, to)) {
+                if let Some(relay) = self.relays.get(&(from, to)) {
+                    relay.set_tap(None);
+                }
+            } else if self.topology.are_linked(from, to) && self.topology.is_active(from) {
+                // the relay is no longer needed
+                if let Ok(sender) = self.link_sender(from, to) {
+                    self.send_command(from, DroneCommand::AddSender(to, sender));
+                }
+            }
+        }
+
+        self.logger
+            .lock()
+            .unwrap()
+            .log_info(&format!("Link {} - {} no longer tapped", id1, id2));
+        self.notify(ControllerUpdate::TapChanged(id1, id2));
+        Ok(())
+    }
+
+    // captured links (smaller id first), with true if they are still tapped
+    pub fn captures(&self) -> Vec<(NodeId, NodeId, bool, SharedCapture)> {
+        let mut captures: Vec<(NodeId, NodeId, bool, SharedCapture)> = self
+            .captures
+            .iter()
+            .map(|(link, capture)| (link.0, link.1, self.tapped.contains(link), capture.clone()))
+            .collect();
+        captures.sort_by_key(|(id1, id2, _, _)| (*id1, *id2));
+        captures
+    }
+
+    // forget the capture of a link, the link is untapped first
+    pub fn discard_capture(&mut self, id1: NodeId, id2: NodeId) {
+        let link = (id1.min(id2), id1.max(id2));
+        if self.tapped.contains(&link) {
+            let _ = self.untap_link(id1, id2);
+        }
+        if self.captures.remove(&link).is_some() {
+            self.notify(ControllerUpdate::TapChanged(id1, id2));
+        }
+    }
+
+    // apply a textual command, Wait and Quit are left to the caller
+    pub fn execute(&mut self, command: &Command) -> Result<(), ControllerError> {
+        match *command {
+            Command::Crash(id) => self.crash(id),
+            Command::Reactivate(id) => self.reactivate(id),
+            Command::Pdr(id, pdr) => self.set_pdr(id, pdr),
+            Command::Profile(id, ref profile) => self.set_pdr_profile(id, profile.clone()),
+            Command::Link(id1, id2) => self.add_link(id1, id2),
+            Command::Unlink(id1, id2) => self.remove_link(id1, id2),
+            Command::Add(node_type, id, pdr, ref neighbours) => {
+                self.add_node(node_type, id, pdr, neighbours)
+            }
+            Command::Remove(id) => self.remove_node(id),
+            Command::Group(ref target, ref action) => self.apply_group(target, action),
+            Command::Inject(ref injection) => self.inject(injection),
+            Command::Wait(_) | Command::Quit => Ok(()),
+        }
+    }
+
+    pub fn groups(&self) -> &BTreeMap<String, Vec<NodeId>> {
+        &self.groups
+    }
+
+    // apply the action to every node of the target, one command at a time: a refused command
+    // (e.g. a crash that would partition the network) does not stop the others
+    pub fn apply_group(
+        &mut self,
+        target: &GroupTarget,
+        action: &GroupAction,
+    ) -> Result<(), ControllerError> {
+        let ids = match target {
+            GroupTarget::Named(name) => self
+                .groups
+                .get(name)
+                .cloned()
+                .ok_or_else(|| ControllerError::UnknownGroup(name.clone()))?,
+            GroupTarget::Nodes(ids) => ids.clone(),
+        };
+        let commands = expand(&self.topology, &ids, action);
+        let total = commands.len();
+        let mut failed = vec![];
+        for command in commands {
+            if let Err(e) = self.execute(&command) {
+                failed.push((command, e));
+            }
+        }
+
+        self.logger.lock().unwrap().log_info(&format!(
+            "Group {} {}: {} of {} actions applied",
+            target,
+            action,
+            total - failed.len(),
+            total
+        ));
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(ControllerError::Batch { failed, total })
+        }
+    }
+
+    // put a crafted packet in the channel of a node, as if a neighbour had sent it
+    pub fn inject(&self, injection: &Injection) -> Result<(), ControllerError> {
+        let id = injection.target;
+        let node = self
+            .topology
+            .get(id)
+            .ok_or(ControllerError::UnknownNode(id))?;
+        if node.crashed {
+            return Err(ControllerError::Crashed(id));
+        }
+        let channel = self
+            .channels
+            .get(&id)
+            .ok_or(ControllerError::NoChannel(id))?;
+        let packet = injection.to_packet();
+        channel
+            .sender
+            .send(packet.clone())
+            .map_err(|_| ControllerError::NoChannel(id))?;
+
+        self.logger
+            .lock()
+            .unwrap()
+            .log_info(&format!("[INJECT] {:?} sent to Node{}", packet, id));
+        if let Some(ref recorder) = *self.recorder.lock().unwrap() {
+            recorder.record_action(&Command::Inject(injection.clone()));
+        }
+        Ok(())
+    }
+
+    // forward a ControllerShortcut packet directly to its destination (last hop of the routing header)
+    pub fn forward_shortcut(&self, packet: &Packet) {
+        let dest = packet.routing_header.hops[packet.routing_header.hops.len() - 1];
+        if let Some(channel) = self.channels.get(&dest) {
+            match channel.sender.send(packet.clone()) {
+                Ok(_) => {
+                    self.logger
+                        .lock()
+                        .unwrap()
+                        .log_debug(&format!("{:?} succesfully sent to Node{}", packet, dest));
+                }
+                Err(e) => {
+                    self.logger
+                        .lock()
+                        .unwrap()
+                        .log_debug(&format!("Error sending packet to drone {}: {:?}", dest, e));
+                }
+            }
+        } else {
+            self.logger
+                .lock()
+                .unwrap()
+                .log_debug(&format!("No sender for drone {}", dest));
+        }
+    }
+
+    // send DroneCommand crash to all the nodes still alive and remove all connections
+    pub fn shutdown(&mut self) {
+        for node in self.topology.nodes().filter(|n| !n.crashed) {
+            self.send_command(node.id, DroneCommand::Crash);
+            if node.node_type == NodeType::Drone {
+                for adj in &node.neighbours {
+                    self.send_command(*adj, DroneCommand::RemoveSender(node.id));
+                }
+            }
+        }
+        self.channels.clear();
+        self.senders.clear();
+    }
+}
+
+// forward the DroneEvent of the simulation and of the spawned drones, recording them if needed
+fn forward_events(
+    sc_receiver: Receiver<DroneEvent>,
+    spawned_receiver: Receiver<DroneEvent>,
+    forward: Sender<DroneEvent>,
+    recorder: Arc<Mutex<Option<Recorder>>>,
+) {
+    let mut sc_receiver = Some(sc_receiver);
+    let mut spawned_receiver = Some(spawned_receiver);
+
+    while sc_receiver.is_some() || spawned_receiver.is_some() {
+        let never_sc = never();
+        let never_spawned = never();
+        // the index of the disconnected receiver is returned as error
+        let event = select! {
+            recv(sc_receiver.as_ref().unwrap_or(&never_sc)) -> event => event.map_err(|_| 0),
+            recv(spawned_receiver.as_ref().unwrap_or(&never_spawned)) -> event => event.map_err(|_| 1),
+        };
+        let event = match event {
+            Ok(event) => event,
+            Err(0) => {
+                sc_receiver = None;
+                continue;
+            }
+            Err(_) => {
+                spawned_receiver = None;
+                continue;
+            }
+        };
+
+        if let Some(ref recorder) = *recorder.lock().unwrap() {
+            recorder.record_event(&event);
+        }
+        if forward.send(event).is_err() {
+            break;
+        }
+    }
+}
+
+pub fn run_simulation_thread(
+    logger_: Arc<Mutex<Logger>>,
+    network_initializer_run_simulation: Arc<Mutex<Option<NetworkInitializer>>>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        logger_.lock().unwrap().log_info("Simulation started");
+        if let Some(ref mut c) = *network_initializer_run_simulation.lock().unwrap() {
+            match c.run_simulation(None, None) {
+                Ok(_) => {
+                    logger_
+                        .lock()
+                        .unwrap()
+                        .log_info("Simulation ended correctly");
+                }
+                Err(e) => {
+                    let error = format!("Simulation ended with error {}", e);
+                    logger_.lock().unwrap().log_error(&error);
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // topology of config_files/star.toml, without starting the simulation;
+    // client 20 is linked only to drone 6
+    fn star() -> SimulationController {
+        let logger = Arc::new(Mutex::new(Logger::new(
+            0,
+            true,
+            "SimulationController".to_string(),
+        )));
+        let mut controller = SimulationController::new(logger);
+        let net_init = NetworkInitializer::new(Some("config_files/star.toml")).unwrap();
+        let nodes = net_init.get_nodes();
+        controller.topology = Topology::from_parsed(nodes.0, nodes.1, nodes.2);
+        controller
+    }
+
+    #[test]
+    fn partitioning_changes_are_refused() {
+        let mut controller = star();
+        let updates = controller.subscribe();
+
+        assert_eq!(
+            controller.crash(6),
+            Err(ControllerError::WouldPartition(vec![(20, 30)]))
+        );
+        assert_eq!(
+            controller.execute(&Command::Unlink(6, 20)),
+            Err(ControllerError::WouldPartition(vec![(20, 30)]))
+        );
+        assert!(controller.topology.is_active(6));
+        assert!(controller.topology.are_linked(6, 20));
+        assert!(updates.try_recv().is_err());
+
+        // the network stays connected through the other drones
+        assert_eq!(controller.execute(&Command::Unlink(1, 4)), Ok(()));
+        assert_eq!(
+            controller.remove_link(1, 4),
+            Err(ControllerError::NotLinked(1, 4))
+        );
+        assert_eq!(
+            updates.try_iter().collect::<Vec<_>>(),
+            vec![ControllerUpdate::LinkRemoved(1, 4)]
+        );
+    }
+
+    #[test]
+    fn partitioning_changes_are_allowed_with_a_warning() {
+        let mut controller = star();
+        controller.set_connectivity_check(ConnectivityCheck::Warn);
+        let updates = controller.subscribe();
+
+        assert_eq!(controller.crash(6), Ok(()));
+        assert!(!controller.topology.is_active(6));
+        let updates: Vec<ControllerUpdate> = updates.try_iter().collect();
+        assert_eq!(updates.len(), 2);
+        assert!(
+            matches!(updates[0], ControllerUpdate::Warning(ref reason) if reason.contains("20"))
+        );
+        assert_eq!(updates[1], ControllerUpdate::Crashed(6));
+    }
+
+    #[test]
+    fn refused_group_actions_are_reported() {
+        let mut controller = star();
+
+        // crashing drone 1 keeps every client connected, crashing drone 6 does not
+        let res = controller.apply_group(&GroupTarget::Nodes(vec![6, 1]), &GroupAction::Crash);
+        let Err(ControllerError::Batch { failed, total }) = res else {
+            panic!("expected a batch error, got {:?}", res);
+        };
+        assert_eq!(total, 2);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, Command::Crash(6));
+        assert!(matches!(failed[0].1, ControllerError::WouldPartition(_)));
+        assert!(!controller.topology.is_active(1));
+        assert!(controller.topology.is_active(6));
+
+        assert_eq!(
+            controller.apply_group(&GroupTarget::Named("core".to_string()), &GroupAction::Crash),
+            Err(ControllerError::UnknownGroup("core".to_string()))
+        );
+        controller.groups.insert("core".to_string(), vec![2, 3]);
+        assert_eq!(
+            controller.apply_group(
+                &GroupTarget::Named("core".to_string()),
+                &GroupAction::Pdr(0.5)
+            ),
+            Ok(())
+        );
+        assert_eq!(controller.topology.get(3).unwrap().pdr, 0.5);
+    }
+}
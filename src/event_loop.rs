@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{never, Receiver, RecvError};
+use crossbeam::select;
+
+use wg_internal::controller::DroneEvent;
+
+use crate::controller::ControllerUpdate;
+
+// messages sent to an event loop on its control channel
+#[derive(Debug, Clone)]
+pub enum LoopControl {
+    Events(Receiver<DroneEvent>), // events of the simulation just loaded, the old ones are dropped
+    Shutdown,
+}
+
+// message received by the event loop
+enum Next {
+    Control(Result<LoopControl, RecvError>),
+    Update(Result<ControllerUpdate, RecvError>),
+    Event(Result<DroneEvent, RecvError>),
+    Tick,
+}
+
+// what an event loop does with the messages it receives
+pub trait EventHandler {
+    fn on_update(&mut self, update: ControllerUpdate);
+
+    fn on_event(&mut self, event: DroneEvent);
+
+    // periodic work, called every tick of the loop
+    fn on_tick(&mut self) {}
+}
+
+// block until an update of the controller, a DroneEvent of the current simulation or a control
+// message arrives; the loop ends on Shutdown or when the controller is dropped
+pub fn run_event_loop<H: EventHandler>(
+    updates: Receiver<ControllerUpdate>,
+    controls: Receiver<LoopControl>,
+    tick: Duration,
+    handler: &mut H,
+) {
+    let mut events: Option<Receiver<DroneEvent>> = None;
+    let mut last_tick = Instant::now();
+
+    loop {
+        let no_events = never();
+        let timeout = tick.saturating_sub(last_tick.elapsed());
+
+        let next = select! {
+            recv(controls) -> control => Next::Control(control),
+            recv(updates) -> update => Next::Update(update),
+            recv(events.as_ref().unwrap_or(&no_events)) -> event => Next::Event(event),
+            default(timeout) => Next::Tick,
+        };
+
+        match next {
+            Next::Control(Ok(LoopControl::Events(receiver))) => events = Some(receiver),
+            Next::Control(Ok(LoopControl::Shutdown)) | Next::Control(Err(_)) => break,
+            Next::Update(Ok(update)) => handler.on_update(update),
+            Next::Update(Err(_)) => break, // the controller has been dropped
+            Next::Event(Ok(event)) => handler.on_event(event),
+            Next::Event(Err(_)) => events = None, // the simulation has ended
+            Next::Tick => {}
+        }
+
+        if last_tick.elapsed() >= tick {
+            handler.on_tick();
+            last_tick = Instant::now();
+        }
+    }
+}
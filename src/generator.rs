@@ -0,0 +1,520 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use wg_internal::network::NodeId;
+
+use crate::lint::{CLIENT_LINKS, SERVER_MIN_LINKS};
+use crate::topology::{Node, NodeType, Topology};
+
+// shape of the network of drones
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Ring,
+    Grid,
+    KRegular(usize),       // degree of every drone
+    ErdosRenyi(f64),       // probability of each link
+    BarabasiAlbert(usize), // links of every new drone
+    Tree(usize),           // children of every drone
+    Subnets(usize),        // number of subnets
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PdrDistribution {
+    Constant(f32),
+    Uniform(f32, f32),
+}
+
+// parameters of a generated configuration, e.g. from the spec
+// "shape=barabasi-albert,m=2,drones=12,clients=3,servers=2,pdr=0.05-0.2,seed=7"
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratorOptions {
+    pub shape: Shape,
+    pub drones: usize,
+    pub clients: usize,
+    pub servers: usize,
+    pub pdr: PdrDistribution,
+    pub seed: u64,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        GeneratorOptions {
+            shape: Shape::Ring,
+            drones: 10,
+            clients: 2,
+            servers: 1,
+            pdr: PdrDistribution::Constant(0.1),
+            seed: 0,
+        }
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, key))
+}
+
+impl GeneratorOptions {
+    // list of key=value separated by commas, the missing keys keep the default value;
+    // the parameter of the shape is k (k-regular), p (erdos-renyi), m (barabasi-albert),
+    // branching (tree) or subnets (subnets), the ones of the other shapes are refused
+    pub fn from_spec(spec: &str) -> Result<GeneratorOptions, String> {
+        let mut options = GeneratorOptions::default();
+        let mut shape = "ring".to_string();
+        let mut parameters: Vec<(String, String)> = vec![];
+
+        for item in spec.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            let Some((key, value)) = item.split_once('=') else {
+                return Err(format!("expected key=value in '{}'", item));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "shape" => shape = value.to_lowercase(),
+                "drones" => options.drones = parse_value(key, value)?,
+                "clients" => options.clients = parse_value(key, value)?,
+                "servers" => options.servers = parse_value(key, value)?,
+                "seed" => options.seed = parse_value(key, value)?,
+                "pdr" => {
+                    options.pdr = match value.split_once('-') {
+                        Some((min, max)) => PdrDistribution::Uniform(
+                            parse_value(key, min.trim())?,
+                            parse_value(key, max.trim())?,
+                        ),
+                        None => PdrDistribution::Constant(parse_value(key, value)?),
+                    }
+                }
+                "k" | "p" | "m" | "branching" | "subnets" => {
+                    parameters.push((key.to_string(), value.to_string()))
+                }
+                _ => return Err(format!("unknown generator key '{}'", key)),
+            }
+        }
+
+        let name = match shape.as_str() {
+            "ring" | "grid" => None,
+            "k-regular" => Some("k"),
+            "erdos-renyi" => Some("p"),
+            "barabasi-albert" => Some("m"),
+            "tree" => Some("branching"),
+            "subnets" => Some("subnets"),
+            other => return Err(format!("unknown shape '{}'", other)),
+        };
+        if let Some((key, _)) = parameters
+            .iter()
+            .find(|(key, _)| Some(key.as_str()) != name)
+        {
+            return Err(format!("{} does not apply to the {} shape", key, shape));
+        }
+        // parameter of the shape (the last one given), with its default value
+        let param = |default: &str| -> String {
+            match parameters.last() {
+                Some((_, value)) => value.clone(),
+                None => default.to_string(),
+            }
+        };
+        options.shape = match shape.as_str() {
+            "ring" => Shape::Ring,
+            "grid" => Shape::Grid,
+            "k-regular" => Shape::KRegular(parse_value("k", &param("4"))?),
+            "erdos-renyi" => Shape::ErdosRenyi(parse_value("p", &param("0.3"))?),
+            "barabasi-albert" => Shape::BarabasiAlbert(parse_value("m", &param("2"))?),
+            "tree" => Shape::Tree(parse_value("branching", &param("2"))?),
+            _ => Shape::Subnets(parse_value("subnets", &param("2"))?),
+        };
+        options.validate()?;
+        Ok(options)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let n = self.drones;
+        // every server is linked to SERVER_MIN_LINKS drones, clients need at least one
+        let minimum = if self.servers > 0 {
+            SERVER_MIN_LINKS
+        } else {
+            1
+        };
+        if n < minimum {
+            return Err(format!("at least {} drones are needed", minimum));
+        }
+        match self.pdr {
+            PdrDistribution::Constant(p) if !(0.0..=1.0).contains(&p) => {
+                return Err(format!("pdr {} is not in [0,1]", p))
+            }
+            PdrDistribution::Uniform(min, max)
+                if !(0.0..=1.0).contains(&min) || !(0.0..=1.0).contains(&max) || min > max =>
+            {
+                return Err(format!("pdr range {}-{} is not in [0,1]", min, max))
+            }
+            _ => {}
+        }
+        match self.shape {
+            Shape::Ring if n < 3 => return Err("a ring needs at least 3 drones".to_string()),
+            Shape::KRegular(k) if k == 0 || k >= n || (k * n) % 2 != 0 => {
+                return Err(format!("no {}-regular graph with {} drones", k, n))
+            }
+            Shape::ErdosRenyi(p) if !(0.0..=1.0).contains(&p) => {
+                return Err(format!("probability {} is not in [0,1]", p))
+            }
+            Shape::BarabasiAlbert(m) if m == 0 || m >= n => {
+                return Err(format!("m must be in [1,{}]", n - 1))
+            }
+            Shape::Tree(0) => return Err("branching must be at least 1".to_string()),
+            Shape::Subnets(s) if s == 0 || s > n => {
+                return Err(format!("subnets must be in [1,{}]", n))
+            }
+            _ => {}
+        }
+
+        let (_, servers_start) = self.first_ids();
+        if servers_start + self.servers > NodeId::MAX as usize + 1 {
+            return Err("too many nodes for the available ids".to_string());
+        }
+        Ok(())
+    }
+
+    // first id of the clients and of the servers: drones are 1..=N, the clients and the
+    // servers start from the next multiple of 10 (as in config_files)
+    fn first_ids(&self) -> (usize, usize) {
+        let round_up = |id: usize| id.div_ceil(10) * 10;
+        let clients_start = round_up(self.drones + 1);
+        let servers_start = round_up(clients_start + self.clients);
+        (clients_start, servers_start)
+    }
+}
+
+// undirected links between the drones (0-based indexes)
+fn drone_links(shape: Shape, n: usize, rng: &mut StdRng) -> Vec<(usize, usize)> {
+    let mut links = vec![];
+    match shape {
+        Shape::Ring => {
+            for i in 0..n {
+                links.push((i, (i + 1) % n));
+            }
+        }
+        Shape::Grid => {
+            let columns = (n as f64).sqrt().ceil() as usize;
+            for i in 0..n {
+                if (i + 1) % columns != 0 && i + 1 < n {
+                    links.push((i, i + 1));
+                }
+                if i + columns < n {
+                    links.push((i, i + columns));
+                }
+            }
+        }
+        Shape::KRegular(k) => {
+            // circulant graph: neighbours at distance 1..k/2, plus the opposite one if k is odd
+            for i in 0..n {
+                for d in 1..=k / 2 {
+                    links.push((i, (i + d) % n));
+                }
+                if k % 2 == 1 && i < n / 2 {
+                    links.push((i, i + n / 2));
+                }
+            }
+        }
+        Shape::ErdosRenyi(p) => {
+            for i in 0..n {
+                for j in i + 1..n {
+                    if rng.gen_bool(p) {
+                        links.push((i, j));
+                    }
+                }
+            }
+        }
+        Shape::BarabasiAlbert(m) => {
+            // the first m + 1 drones are a clique, every other drone is linked to m drones
+            // chosen with probability proportional to their degree
+            let mut endpoints: Vec<usize> = vec![];
+            for i in 0..=m {
+                for j in i + 1..=m {
+                    links.push((i, j));
+                    endpoints.extend([i, j]);
+                }
+            }
+            for i in m + 1..n {
+                let mut targets: Vec<usize> = vec![];
+                while targets.len() < m {
+                    let target = *endpoints.choose(rng).unwrap_or(&0);
+                    if !targets.contains(&target) {
+                        targets.push(target);
+                    }
+                }
+                for target in targets {
+                    links.push((i, target));
+                    endpoints.extend([i, target]);
+                }
+            }
+        }
+        Shape::Tree(branching) => {
+            for i in 1..n {
+                links.push(((i - 1) / branching, i));
+            }
+        }
+        Shape::Subnets(count) => {
+            // each subnet is a ring (or a chain if too small), consecutive subnets are
+            // joined by two links
+            // the first n % count subnets get one more drone, so that there are count subnets
+            let (size, extra) = (n / count, n % count);
+            let mut subnets: Vec<Vec<usize>> = vec![];
+            let mut start = 0;
+            for i in 0..count {
+                let len = size + usize::from(i < extra);
+                subnets.push((start..start + len).collect());
+                start += len;
+            }
+            for subnet in &subnets {
+                for w in subnet.windows(2) {
+                    links.push((w[0], w[1]));
+                }
+                if subnet.len() > 2 {
+                    links.push((subnet[subnet.len() - 1], subnet[0]));
+                }
+            }
+            for pair in subnets.windows(2) {
+                links.push((*pair[0].last().unwrap(), pair[1][0]));
+                if pair[0].len() > 1 && pair[1].len() > 1 {
+                    links.push((pair[0][pair[0].len() - 2], pair[1][1]));
+                }
+            }
+        }
+    }
+
+    links.retain(|(a, b)| a != b);
+    for link in links.iter_mut() {
+        *link = (link.0.min(link.1), link.0.max(link.1));
+    }
+    links.sort();
+    links.dedup();
+    connect_components(n, &mut links, rng);
+    links
+}
+
+// link the connected components of the drones with random links
+fn connect_components(n: usize, links: &mut Vec<(usize, usize)>, rng: &mut StdRng) {
+    let mut component: Vec<usize> = (0..n).collect();
+    fn find(component: &mut [usize], i: usize) -> usize {
+        let mut root = i;
+        while component[root] != root {
+            root = component[root];
+        }
+        component[i] = root;
+        root
+    }
+    for (a, b) in links.iter() {
+        let (ra, rb) = (find(&mut component, *a), find(&mut component, *b));
+        component[ra] = rb;
+    }
+
+    for i in 1..n {
+        let (ri, r0) = (find(&mut component, i), find(&mut component, 0));
+        if ri != r0 {
+            // a random drone of the component of 0 is linked to drone i
+            let candidates: Vec<usize> =
+                (0..i).filter(|j| find(&mut component, *j) == r0).collect();
+            let j = *candidates.choose(rng).unwrap_or(&0);
+            links.push((j, i));
+            component[ri] = r0;
+        }
+    }
+}
+
+// generate a valid topology: every client is linked to 1 or 2 drones, every server to 2
+pub fn generate(options: &GeneratorOptions) -> Result<Topology, String> {
+    options.validate()?;
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let n = options.drones;
+
+    let mut nodes: Vec<Node> = (0..n)
+        .map(|i| Node {
+            id: (i + 1) as NodeId,
+            node_type: NodeType::Drone,
+            pdr: match options.pdr {
+                PdrDistribution::Constant(p) => p,
+                PdrDistribution::Uniform(min, max) if min < max => {
+                    (rng.gen_range(min..=max) * 100.0).round() / 100.0
+                }
+                PdrDistribution::Uniform(min, _) => min,
+            },
+            crashed: false,
+            neighbours: vec![],
+        })
+        .collect();
+    for (a, b) in drone_links(options.shape, n, &mut rng) {
+        nodes[a].neighbours.push((b + 1) as NodeId);
+        nodes[b].neighbours.push((a + 1) as NodeId);
+    }
+
+    let (clients_start, servers_start) = options.first_ids();
+    let drone_ids: Vec<NodeId> = (1..=n as NodeId).collect();
+    let mut attach = |id: usize, node_type: NodeType, links: usize, nodes: &mut Vec<Node>| {
+        let neighbours: Vec<NodeId> = drone_ids
+            .choose_multiple(&mut rng, links.min(n))
+            .copied()
+            .collect();
+        for adj in &neighbours {
+            nodes[*adj as usize - 1].neighbours.push(id as NodeId);
+        }
+        nodes.push(Node {
+            id: id as NodeId,
+            node_type,
+            pdr: 0.0,
+            crashed: false,
+            neighbours,
+        });
+    };
+    for c in 0..options.clients {
+        let links = if n >= CLIENT_LINKS.1 && c % 2 == 1 {
+            CLIENT_LINKS.1
+        } else {
+            CLIENT_LINKS.0
+        };
+        attach(clients_start + c, NodeType::Client, links, &mut nodes);
+    }
+    for s in 0..options.servers {
+        attach(
+            servers_start + s,
+            NodeType::Server,
+            SERVER_MIN_LINKS,
+            &mut nodes,
+        );
+    }
+
+    for node in nodes.iter_mut() {
+        node.neighbours.sort();
+    }
+    Ok(Topology::from_nodes(nodes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::lint::{has_errors, lint_str};
+
+    #[test]
+    fn parses_specs() {
+        let options = GeneratorOptions::from_spec(
+            "shape=barabasi-albert,m=3,drones=12,clients=3,servers=2,pdr=0.05-0.2,seed=7",
+        )
+        .unwrap();
+        assert_eq!(
+            options,
+            GeneratorOptions {
+                shape: Shape::BarabasiAlbert(3),
+                drones: 12,
+                clients: 3,
+                servers: 2,
+                pdr: PdrDistribution::Uniform(0.05, 0.2),
+                seed: 7,
+            }
+        );
+        assert_eq!(
+            GeneratorOptions::from_spec("").unwrap(),
+            GeneratorOptions::default()
+        );
+        assert_eq!(
+            GeneratorOptions::from_spec("shape=subnets,drones=10")
+                .unwrap()
+                .shape,
+            Shape::Subnets(2)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_specs() {
+        for spec in [
+            "shape=hypercube",
+            "drones",
+            "color=red",
+            "drones=-1",
+            "drones=1",
+            "pdr=1.5",
+            "pdr=nan",
+            "pdr=0.5-0.1",
+            "shape=ring,drones=2,servers=0",
+            "shape=k-regular,k=3,drones=9",
+            "shape=k-regular,k=10,drones=10",
+            "shape=erdos-renyi,p=nan",
+            "shape=erdos-renyi,p=2",
+            "shape=barabasi-albert,m=0",
+            "shape=tree,branching=0",
+            "shape=subnets,subnets=0",
+            "shape=subnets,subnets=11,drones=10",
+            "shape=ring,k=3",
+            "shape=grid,p=0.5",
+            "shape=k-regular,k=4,m=2",
+            "shape=tree,subnets=2,branching=3",
+            "drones=200,clients=50,servers=10",
+        ] {
+            assert!(GeneratorOptions::from_spec(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn servers_need_enough_drones_only_if_requested() {
+        assert!(GeneratorOptions::from_spec("shape=tree,drones=1,servers=1").is_err());
+        let options = GeneratorOptions::from_spec("shape=tree,drones=1,servers=0").unwrap();
+        let topology = generate(&options).unwrap();
+        assert_eq!(topology.drones().count(), 1);
+        assert_eq!(topology.servers().count(), 0);
+        assert!(GeneratorOptions::from_spec("shape=tree,drones=0,servers=0").is_err());
+    }
+
+    #[test]
+    fn subnets_are_honoured() {
+        // 10 drones in 4 subnets of 3, 3, 2 and 2 drones
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut expected = vec![
+            (0, 1),
+            (1, 2),
+            (0, 2),
+            (3, 4),
+            (4, 5),
+            (3, 5),
+            (6, 7),
+            (8, 9),
+            (2, 3),
+            (1, 4),
+            (5, 6),
+            (4, 7),
+            (7, 8),
+            (6, 9),
+        ];
+        expected.sort();
+        assert_eq!(drone_links(Shape::Subnets(4), 10, &mut rng), expected);
+    }
+
+    #[test]
+    fn generated_configs_are_valid() {
+        for spec in [
+            "shape=ring",
+            "shape=grid,drones=7",
+            "shape=k-regular,k=3,drones=10",
+            "shape=erdos-renyi,p=0.05,drones=15",
+            "shape=barabasi-albert,m=2,drones=12,clients=4,servers=3",
+            "shape=tree,branching=3,drones=13",
+            "shape=subnets,subnets=4,drones=10",
+            "shape=subnets,subnets=5,drones=5,pdr=0-1",
+        ] {
+            let options = GeneratorOptions::from_spec(spec).unwrap();
+            let topology = generate(&options).unwrap();
+            assert_eq!(topology.drones().count(), options.drones, "{}", spec);
+            assert_eq!(topology.clients().count(), options.clients, "{}", spec);
+            assert_eq!(topology.servers().count(), options.servers, "{}", spec);
+            let issues = lint_str(&topology.to_config());
+            assert!(!has_errors(&issues), "{}: {:?}", spec, issues);
+        }
+    }
+
+    #[test]
+    fn same_seed_same_topology() {
+        let options = GeneratorOptions::from_spec("shape=erdos-renyi,p=0.3,seed=3").unwrap();
+        assert_eq!(
+            generate(&options).unwrap().to_config(),
+            generate(&options).unwrap().to_config()
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Deserialize;
+
+use wg_internal::network::NodeId;
+
+use crate::command::Command;
+use crate::topology::{Node, NodeType, Topology};
+
+// action applied to every node of a selection or of a named group
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupAction {
+    Crash,
+    Reactivate,
+    Pdr(f32),
+    Clique,     // link every pair of nodes
+    Chain,      // link the nodes in the given order
+    Disconnect, // remove the links to the nodes outside of the group
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupTarget {
+    Named(String),
+    Nodes(Vec<NodeId>),
+}
+
+impl fmt::Display for GroupAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupAction::Crash => write!(f, "crash"),
+            GroupAction::Reactivate => write!(f, "reactivate"),
+            GroupAction::Pdr(pdr) => write!(f, "pdr {}", pdr),
+            GroupAction::Clique => write!(f, "clique"),
+            GroupAction::Chain => write!(f, "chain"),
+            GroupAction::Disconnect => write!(f, "disconnect"),
+        }
+    }
+}
+
+impl fmt::Display for GroupTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupTarget::Named(name) => write!(f, "{}", name),
+            GroupTarget::Nodes(ids) => {
+                let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+                write!(f, "{}", ids.join(","))
+            }
+        }
+    }
+}
+
+// a group name, or node ids separated by commas (e.g. "left_wing" or "1,2,3")
+pub fn parse_target(token: &str) -> Result<GroupTarget, String> {
+    if token.starts_with(|c: char| c.is_ascii_digit()) {
+        token
+            .split(',')
+            .filter(|id| !id.is_empty())
+            .map(|id| {
+                id.parse::<NodeId>()
+                    .map_err(|_| format!("invalid node id '{}'", id))
+            })
+            .collect::<Result<Vec<NodeId>, String>>()
+            .map(GroupTarget::Nodes)
+    } else {
+        Ok(GroupTarget::Named(token.to_string()))
+    }
+}
+
+// crash, reactivate, pdr <value>, clique, chain or disconnect
+pub fn parse_action<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<GroupAction, String> {
+    let action = match tokens.next() {
+        Some("crash") => GroupAction::Crash,
+        Some("reactivate") => GroupAction::Reactivate,
+        Some("pdr") => {
+            let value = tokens.next().ok_or("missing pdr")?;
+            let pdr: f32 = value
+                .parse()
+                .map_err(|_| format!("invalid number '{}'", value))?;
+            if !(0.0..=1.0).contains(&pdr) {
+                return Err("pdr must be in [0,1]".to_string());
+            }
+            GroupAction::Pdr(pdr)
+        }
+        Some("clique") => GroupAction::Clique,
+        Some("chain") => GroupAction::Chain,
+        Some("disconnect") => GroupAction::Disconnect,
+        Some(other) => return Err(format!("unknown group action '{}'", other)),
+        None => return Err("missing group action".to_string()),
+    };
+    if tokens.next().is_some() {
+        return Err("too many arguments".to_string());
+    }
+    Ok(action)
+}
+
+// single commands applying the action to the nodes, the ones not in the topology are skipped
+// and so are the nodes the action does not apply to (e.g. clients for a crash)
+pub fn expand(topology: &Topology, ids: &[NodeId], action: &GroupAction) -> Vec<Command> {
+    let mut nodes: Vec<&Node> = vec![];
+    for id in ids {
+        if nodes.iter().any(|n| n.id == *id) {
+            continue;
+        }
+        if let Some(node) = topology.get(*id) {
+            nodes.push(node);
+        }
+    }
+    let drones = nodes.iter().filter(|n| n.node_type == NodeType::Drone);
+    // a link needs two active nodes and at least a drone
+    let can_link = |a: NodeId, b: NodeId| {
+        let types = (
+            topology.get(a).map(|n| n.node_type),
+            topology.get(b).map(|n| n.node_type),
+        );
+        topology.is_active(a)
+            && topology.is_active(b)
+            && !topology.are_linked(a, b)
+            && (types.0 == Some(NodeType::Drone) || types.1 == Some(NodeType::Drone))
+    };
+
+    match *action {
+        GroupAction::Crash => drones
+            .filter(|n| !n.crashed)
+            .map(|n| Command::Crash(n.id))
+            .collect(),
+        GroupAction::Reactivate => drones
+            .filter(|n| n.crashed)
+            .map(|n| Command::Reactivate(n.id))
+            .collect(),
+        GroupAction::Pdr(pdr) => drones
+            .filter(|n| !n.crashed)
+            .map(|n| Command::Pdr(n.id, pdr))
+            .collect(),
+        GroupAction::Clique => {
+            let mut commands = vec![];
+            for (i, a) in nodes.iter().enumerate() {
+                for b in &nodes[i + 1..] {
+                    if can_link(a.id, b.id) {
+                        commands.push(Command::Link(a.id, b.id));
+                    }
+                }
+            }
+            commands
+        }
+        GroupAction::Chain => nodes
+            .windows(2)
+            .filter(|pair| can_link(pair[0].id, pair[1].id))
+            .map(|pair| Command::Link(pair[0].id, pair[1].id))
+            .collect(),
+        GroupAction::Disconnect => {
+            let mut commands = vec![];
+            for node in &nodes {
+                for adj in &node.neighbours {
+                    if !ids.contains(adj) {
+                        commands.push(Command::Unlink(node.id, *adj));
+                    }
+                }
+            }
+            commands
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GroupsConfig {
+    #[serde(default)]
+    groups: BTreeMap<String, Vec<NodeId>>,
+}
+
+// [groups] table of a configuration file, e.g.
+//   [groups]
+//   left_wing = [1, 2, 3]
+pub fn read_groups(path: &str) -> Result<BTreeMap<String, Vec<NodeId>>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let config: GroupsConfig =
+        toml::from_str(&content).map_err(|e| format!("{}: {}", path, e.message()))?;
+    Ok(config.groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: NodeId, node_type: NodeType, crashed: bool, neighbours: &[NodeId]) -> Node {
+        Node {
+            id,
+            node_type,
+            pdr: 0.1,
+            crashed,
+            neighbours: neighbours.to_vec(),
+        }
+    }
+
+    // drones 1 - 2 and 3, client 10 - drone 1, client 11 alone, drone 4 crashed
+    fn network() -> Topology {
+        Topology::from_nodes(vec![
+            node(1, NodeType::Drone, false, &[2, 10]),
+            node(2, NodeType::Drone, false, &[1]),
+            node(3, NodeType::Drone, false, &[]),
+            node(4, NodeType::Drone, true, &[]),
+            node(10, NodeType::Client, false, &[1]),
+            node(11, NodeType::Client, false, &[]),
+        ])
+    }
+
+    #[test]
+    fn parses_targets() {
+        assert_eq!(
+            parse_target("1,2,3").unwrap(),
+            GroupTarget::Nodes(vec![1, 2, 3])
+        );
+        assert_eq!(
+            parse_target("left_wing").unwrap(),
+            GroupTarget::Named("left_wing".to_string())
+        );
+        assert!(parse_target("1,x").is_err());
+        assert!(parse_target("1,300").is_err());
+    }
+
+    #[test]
+    fn parses_actions() {
+        let parse = |args: &str| parse_action(args.split_whitespace());
+        assert_eq!(parse("crash").unwrap(), GroupAction::Crash);
+        assert_eq!(parse("pdr 0.25").unwrap(), GroupAction::Pdr(0.25));
+        assert_eq!(parse("disconnect").unwrap(), GroupAction::Disconnect);
+        for args in [
+            "",
+            "fly",
+            "pdr",
+            "pdr x",
+            "pdr 1.5",
+            "pdr -0.1",
+            "pdr nan",
+            "crash now",
+        ] {
+            assert!(parse(args).is_err(), "{}", args);
+        }
+    }
+
+    #[test]
+    fn display_is_parsed_back() {
+        for action in [
+            GroupAction::Crash,
+            GroupAction::Reactivate,
+            GroupAction::Pdr(0.3),
+            GroupAction::Clique,
+            GroupAction::Chain,
+            GroupAction::Disconnect,
+        ] {
+            let text = action.to_string();
+            assert_eq!(parse_action(text.split_whitespace()).unwrap(), action);
+        }
+        for target in [
+            GroupTarget::Nodes(vec![4, 1, 2]),
+            GroupTarget::Named("core".to_string()),
+        ] {
+            assert_eq!(parse_target(&target.to_string()).unwrap(), target);
+        }
+    }
+
+    #[test]
+    fn expands_node_actions() {
+        let topology = network();
+        // clients, crashed drones, missing and repeated nodes are skipped
+        assert_eq!(
+            expand(&topology, &[1, 10, 4, 99, 1], &GroupAction::Crash),
+            vec![Command::Crash(1)]
+        );
+        assert_eq!(
+            expand(&topology, &[1, 4], &GroupAction::Reactivate),
+            vec![Command::Reactivate(4)]
+        );
+        assert_eq!(
+            expand(&topology, &[1, 2, 4, 10], &GroupAction::Pdr(0.5)),
+            vec![Command::Pdr(1, 0.5), Command::Pdr(2, 0.5)]
+        );
+    }
+
+    #[test]
+    fn expands_link_actions() {
+        let topology = network();
+        // existing links and links between two clients are skipped
+        assert_eq!(
+            expand(&topology, &[1, 2, 3, 10, 11], &GroupAction::Clique),
+            vec![
+                Command::Link(1, 3),
+                Command::Link(1, 11),
+                Command::Link(2, 3),
+                Command::Link(2, 10),
+                Command::Link(2, 11),
+                Command::Link(3, 10),
+                Command::Link(3, 11),
+            ]
+        );
+        // no link to a crashed drone
+        assert_eq!(
+            expand(&topology, &[3, 4, 2, 10], &GroupAction::Chain),
+            vec![Command::Link(2, 10)]
+        );
+        assert_eq!(
+            expand(&topology, &[1, 2], &GroupAction::Disconnect),
+            vec![Command::Unlink(1, 10)]
+        );
+        assert!(expand(&topology, &[1, 2, 10], &GroupAction::Disconnect).is_empty());
+    }
+}
@@ -0,0 +1,263 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use wg_internal::controller::DroneEvent;
+
+use logger::Logger;
+
+use crate::chaos::{ChaosEngine, ChaosOptions};
+use crate::command::{parse_command, Command};
+use crate::controller::{ConnectivityCheck, ControllerUpdate, SimulationController};
+use crate::event_loop::{run_event_loop, EventHandler};
+use crate::lint::{has_errors, lint_config};
+use crate::pdr_profile::PdrDriver;
+use crate::scenario::{read_scenario, Scheduler};
+
+struct HeadlessEvents {
+    logger: Arc<Mutex<Logger>>,
+    controller: Arc<Mutex<SimulationController>>,
+}
+
+impl EventHandler for HeadlessEvents {
+    fn on_update(&mut self, update: ControllerUpdate) {
+        self.logger
+            .lock()
+            .unwrap()
+            .log_debug(&format!("[HEADLESS] {:?}", update));
+    }
+
+    fn on_event(&mut self, event: DroneEvent) {
+        match event {
+            DroneEvent::PacketSent(packet) => {
+                self.logger
+                    .lock()
+                    .unwrap()
+                    .log_debug(&format!("PacketSent received {:?}", packet));
+            }
+            DroneEvent::PacketDropped(packet) => {
+                self.logger
+                    .lock()
+                    .unwrap()
+                    .log_debug(&format!("PacketDropped received {:?}", packet));
+            }
+            DroneEvent::ControllerShortcut(packet) => {
+                self.controller.lock().unwrap().forward_shortcut(&packet);
+            }
+        }
+    }
+}
+
+pub struct HeadlessOptions {
+    pub config: String,
+    pub script: Option<String>, // commands are read from stdin if not given
+    pub scenario: Option<String>,
+    pub chaos: Option<ChaosOptions>,
+    pub allow_partition: bool,
+    pub record: Option<String>,
+}
+
+// run the simulation without the GUI, commands are read from the script (if given) or from stdin
+pub fn run_headless(logger: Arc<Mutex<Logger>>, options: HeadlessOptions) {
+    let controller = Arc::new(Mutex::new(SimulationController::new(logger.clone())));
+    if options.allow_partition {
+        controller
+            .lock()
+            .unwrap()
+            .set_connectivity_check(ConnectivityCheck::Warn);
+    }
+
+    // the configuration is checked before being loaded
+    if let Ok(issues) = lint_config(&options.config) {
+        for issue in &issues {
+            logger
+                .lock()
+                .unwrap()
+                .log_warn(&format!("[HEADLESS] {}:{}", options.config, issue));
+        }
+        if has_errors(&issues) {
+            logger
+                .lock()
+                .unwrap()
+                .log_error("[HEADLESS] The configuration file has errors");
+            return;
+        }
+    }
+
+    let res = controller.lock().unwrap().load_config(&options.config);
+    if let Err(e) = res {
+        logger.lock().unwrap().log_error(&format!(
+            "[HEADLESS] Error in loading the configuration file: {}",
+            e
+        ));
+        return;
+    }
+
+    if let Some(ref trace) = options.record {
+        let res = controller.lock().unwrap().start_recording(trace);
+        if let Err(e) = res {
+            logger
+                .lock()
+                .unwrap()
+                .log_error(&format!("[HEADLESS] Cannot record to {}: {}", trace, e));
+        }
+    }
+
+    // Set up Ctrl+C handler, the trace is closed before exiting
+    let logger_ = logger.clone();
+    let controller_ = controller.clone();
+    let res = ctrlc::set_handler(move || {
+        logger_
+            .lock()
+            .unwrap()
+            .log_info("[HEADLESS] Received Ctrl+C, shutting down...");
+        controller_.lock().unwrap().shutdown();
+        controller_.lock().unwrap().stop_recording();
+        controller_.lock().unwrap().stop_event_loops();
+        std::process::exit(0);
+    });
+    if let Err(e) = res {
+        logger
+            .lock()
+            .unwrap()
+            .log_warn(&format!("[HEADLESS] Cannot set the Ctrl+C handler: {}", e));
+    }
+
+    // thread for receiving DroneEvent, only the ControllerShortcut needs to be handled
+    let mut handler = HeadlessEvents {
+        logger: logger.clone(),
+        controller: controller.clone(),
+    };
+    let updates = controller.lock().unwrap().subscribe();
+    let controls = controller.lock().unwrap().event_loop_control();
+    let event_loop = thread::spawn(move || {
+        run_event_loop(updates, controls, Duration::from_secs(1), &mut handler)
+    });
+
+    // timed actions run alongside the commands
+    let scheduler = match options.scenario {
+        Some(ref path) => match read_scenario(path) {
+            Ok(steps) => Some(Scheduler::start(
+                controller.clone(),
+                logger.clone(),
+                steps,
+                || {},
+            )),
+            Err(e) => {
+                logger
+                    .lock()
+                    .unwrap()
+                    .log_error(&format!("[HEADLESS] {}", e));
+                None
+            }
+        },
+        None => None,
+    };
+    // pdr of the drones with a profile, updated every tick
+    let pdr_driver = PdrDriver::start(controller.clone());
+    let chaos = options
+        .chaos
+        .clone()
+        .map(|chaos| ChaosEngine::start(controller.clone(), logger.clone(), chaos));
+    let mut quit = false;
+
+    // read commands from the script or from stdin
+    let input: Box<dyn BufRead> = match options.script {
+        Some(ref script_path) => match File::open(script_path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(e) => {
+                logger.lock().unwrap().log_error(&format!(
+                    "[HEADLESS] Cannot open script {}: {}",
+                    script_path, e
+                ));
+                Box::new(io::empty())
+            }
+        },
+        None => Box::new(BufReader::new(io::stdin())),
+    };
+
+    for (n, line) in input.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                logger
+                    .lock()
+                    .unwrap()
+                    .log_error(&format!("[HEADLESS] Error reading commands: {}", e));
+                break;
+            }
+        };
+
+        match parse_command(&line) {
+            Ok(Some(Command::Quit)) => {
+                quit = true;
+                break;
+            }
+            Ok(Some(Command::Wait(seconds))) => {
+                thread::sleep(Duration::from_secs_f32(seconds));
+            }
+            Ok(Some(command)) => {
+                let res = controller.lock().unwrap().execute(&command);
+                match res {
+                    Ok(_) => {
+                        logger
+                            .lock()
+                            .unwrap()
+                            .log_info(&format!("[HEADLESS] {:?} applied", command));
+                    }
+                    Err(e) => {
+                        logger.lock().unwrap().log_error(&format!(
+                            "[HEADLESS] line {}: {}",
+                            n + 1,
+                            e
+                        ));
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                logger
+                    .lock()
+                    .unwrap()
+                    .log_error(&format!("[HEADLESS] line {}: {}", n + 1, e));
+            }
+        }
+    }
+
+    // the remaining actions of the scenario are executed and the chaos engine keeps running
+    // until the end of its duration (or Ctrl+C), unless the user quits
+    if let Some(scheduler) = scheduler {
+        if quit {
+            scheduler.stop();
+        } else {
+            scheduler.join();
+        }
+    }
+
+    if let Some(chaos) = chaos {
+        if quit {
+            chaos.stop();
+        } else {
+            logger.lock().unwrap().log_info(
+                "[HEADLESS] Commands ended, chaos keeps running until its duration or Ctrl+C",
+            );
+            chaos.join();
+        }
+    }
+    pdr_driver.stop();
+
+    logger
+        .lock()
+        .unwrap()
+        .log_info("[HEADLESS] Shutting down...");
+    controller.lock().unwrap().shutdown();
+    controller.lock().unwrap().stop_recording();
+    controller.lock().unwrap().stop_event_loops();
+    let _ = event_loop.join();
+    let run_sim_thread_handler = controller.lock().unwrap().take_simulation_thread();
+    if let Some(handle) = run_sim_thread_handler {
+        let _ = handle.join();
+    }
+}
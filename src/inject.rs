@@ -0,0 +1,473 @@
+use std::fmt;
+
+use wg_internal::network::{NodeId, SourceRoutingHeader};
+use wg_internal::packet::{
+    Ack, FloodRequest, FloodResponse, Fragment, Nack, NackType, NodeType as WgNodeType, Packet,
+    PacketType,
+};
+
+use crate::topology::NodeType;
+
+// bytes of data carried by a fragment
+pub const FRAGMENT_SIZE: usize = 128;
+
+// payload given as bytes, e.g. "hex:0a1b2c" (to replay the data of a real fragment)
+const HEX_PREFIX: &str = "hex:";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NackKind {
+    ErrorInRouting(NodeId),
+    DestinationIsDrone,
+    Dropped,
+    UnexpectedRecipient(NodeId),
+}
+
+// body of a crafted packet
+#[derive(Debug, Clone, PartialEq)]
+pub enum PacketBody {
+    MsgFragment {
+        fragment_index: u64,
+        total_n_fragments: u64,
+        payload: Vec<u8>, // truncated to FRAGMENT_SIZE bytes
+    },
+    Ack {
+        fragment_index: u64,
+    },
+    Nack {
+        fragment_index: u64,
+        nack_type: NackKind,
+    },
+    FloodRequest {
+        flood_id: u64,
+        initiator_id: NodeId,
+        path_trace: Vec<(NodeId, NodeType)>,
+    },
+    FloodResponse {
+        flood_id: u64,
+        path_trace: Vec<(NodeId, NodeType)>,
+    },
+}
+
+// packet put directly in the channel of a node, with any routing header (even a wrong one)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Injection {
+    pub target: NodeId,
+    pub session_id: u64,
+    pub hop_index: usize,
+    pub hops: Vec<NodeId>,
+    pub body: PacketBody,
+}
+
+fn wg_node_type(node_type: NodeType) -> WgNodeType {
+    match node_type {
+        NodeType::Drone => WgNodeType::Drone,
+        NodeType::Client => WgNodeType::Client,
+        NodeType::Server => WgNodeType::Server,
+    }
+}
+
+impl Injection {
+    pub fn to_packet(&self) -> Packet {
+        let path_trace = |trace: &[(NodeId, NodeType)]| -> Vec<(NodeId, WgNodeType)> {
+            trace
+                .iter()
+                .map(|(id, t)| (*id, wg_node_type(*t)))
+                .collect()
+        };
+        let pack_type = match self.body {
+            PacketBody::MsgFragment {
+                fragment_index,
+                total_n_fragments,
+                ref payload,
+            } => {
+                let length = payload.len().min(FRAGMENT_SIZE);
+                let mut data = [0u8; FRAGMENT_SIZE];
+                data[..length].copy_from_slice(&payload[..length]);
+                PacketType::MsgFragment(Fragment {
+                    fragment_index,
+                    total_n_fragments,
+                    length: length as u8,
+                    data,
+                })
+            }
+            PacketBody::Ack { fragment_index } => PacketType::Ack(Ack { fragment_index }),
+            PacketBody::Nack {
+                fragment_index,
+                ref nack_type,
+            } => PacketType::Nack(Nack {
+                fragment_index,
+                nack_type: match *nack_type {
+                    NackKind::ErrorInRouting(id) => NackType::ErrorInRouting(id),
+                    NackKind::DestinationIsDrone => NackType::DestinationIsDrone,
+                    NackKind::Dropped => NackType::Dropped,
+                    NackKind::UnexpectedRecipient(id) => NackType::UnexpectedRecipient(id),
+                },
+            }),
+            PacketBody::FloodRequest {
+                flood_id,
+                initiator_id,
+                path_trace: ref trace,
+            } => PacketType::FloodRequest(FloodRequest {
+                flood_id,
+                initiator_id,
+                path_trace: path_trace(trace),
+            }),
+            PacketBody::FloodResponse {
+                flood_id,
+                path_trace: ref trace,
+            } => PacketType::FloodResponse(FloodResponse {
+                flood_id,
+                path_trace: path_trace(trace),
+            }),
+        };
+
+        Packet {
+            routing_header: SourceRoutingHeader {
+                hop_index: self.hop_index,
+                hops: self.hops.clone(),
+            },
+            session_id: self.session_id,
+            pack_type,
+        }
+    }
+}
+
+fn type_name(node_type: NodeType) -> &'static str {
+    match node_type {
+        NodeType::Drone => "drone",
+        NodeType::Client => "client",
+        NodeType::Server => "server",
+    }
+}
+
+fn write_ids(f: &mut fmt::Formatter<'_>, ids: &[NodeId]) -> fmt::Result {
+    let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    write!(f, "{}", ids.join(","))
+}
+
+fn write_trace(f: &mut fmt::Formatter<'_>, trace: &[(NodeId, NodeType)]) -> fmt::Result {
+    let trace: Vec<String> = trace
+        .iter()
+        .map(|(id, t)| format!("{}:{}", id, type_name(*t)))
+        .collect();
+    write!(f, "{}", trace.join(","))
+}
+
+// bytes that are read back the same as text, the others are written in hex
+fn write_payload(f: &mut fmt::Formatter<'_>, payload: &[u8]) -> fmt::Result {
+    match std::str::from_utf8(payload) {
+        Ok(text)
+            if !text.starts_with(HEX_PREFIX)
+                && text.split_whitespace().collect::<Vec<&str>>().join(" ") == text =>
+        {
+            write!(f, "{}", text)
+        }
+        _ => {
+            write!(f, "{}", HEX_PREFIX)?;
+            for byte in payload {
+                write!(f, "{:02x}", byte)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// same syntax accepted by parse_injection (without the "inject" keyword)
+impl fmt::Display for Injection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} ", self.target, self.session_id, self.hop_index)?;
+        write_ids(f, &self.hops)?;
+        match self.body {
+            PacketBody::MsgFragment {
+                fragment_index,
+                total_n_fragments,
+                ref payload,
+            } => {
+                write!(f, " fragment {} {} ", fragment_index, total_n_fragments)?;
+                write_payload(f, payload)
+            }
+            PacketBody::Ack { fragment_index } => write!(f, " ack {}", fragment_index),
+            PacketBody::Nack {
+                fragment_index,
+                ref nack_type,
+            } => match *nack_type {
+                NackKind::ErrorInRouting(id) => {
+                    write!(f, " nack {} error_in_routing {}", fragment_index, id)
+                }
+                NackKind::DestinationIsDrone => {
+                    write!(f, " nack {} destination_is_drone", fragment_index)
+                }
+                NackKind::Dropped => write!(f, " nack {} dropped", fragment_index),
+                NackKind::UnexpectedRecipient(id) => {
+                    write!(f, " nack {} unexpected_recipient {}", fragment_index, id)
+                }
+            },
+            PacketBody::FloodRequest {
+                flood_id,
+                initiator_id,
+                ref path_trace,
+            } => {
+                write!(f, " flood_request {} {} ", flood_id, initiator_id)?;
+                write_trace(f, path_trace)
+            }
+            PacketBody::FloodResponse {
+                flood_id,
+                ref path_trace,
+            } => {
+                write!(f, " flood_response {} ", flood_id)?;
+                write_trace(f, path_trace)
+            }
+        }
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(token: Option<&str>, what: &str) -> Result<T, String> {
+    let token = token.ok_or_else(|| format!("missing {}", what))?;
+    token
+        .parse()
+        .map_err(|_| format!("invalid {} '{}'", what, token))
+}
+
+// text, or bytes in hex after HEX_PREFIX (at most FRAGMENT_SIZE of them)
+fn parse_payload(text: &str) -> Result<Vec<u8>, String> {
+    let Some(hex) = text.strip_prefix(HEX_PREFIX) else {
+        return Ok(text.as_bytes().to_vec());
+    };
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return Err(format!("invalid hex payload '{}'", hex));
+    }
+    if hex.len() / 2 > FRAGMENT_SIZE {
+        return Err(format!(
+            "a fragment carries at most {} bytes",
+            FRAGMENT_SIZE
+        ));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| format!("invalid hex payload '{}'", hex))
+        })
+        .collect()
+}
+
+// ids separated by commas, e.g. "20,3,7,30"
+fn parse_ids(token: Option<&str>) -> Result<Vec<NodeId>, String> {
+    let token = token.ok_or("missing hops")?;
+    token
+        .split(',')
+        .filter(|id| !id.is_empty())
+        .map(|id| parse_number(Some(id), "node id"))
+        .collect()
+}
+
+// id:type separated by commas, e.g. "20:client,3:drone"
+fn parse_trace(token: Option<&str>) -> Result<Vec<(NodeId, NodeType)>, String> {
+    let Some(token) = token else {
+        return Ok(vec![]);
+    };
+    token
+        .split(',')
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (id, node_type) = entry
+                .split_once(':')
+                .ok_or_else(|| format!("expected id:type in '{}'", entry))?;
+            let node_type = match node_type {
+                "drone" => NodeType::Drone,
+                "client" => NodeType::Client,
+                "server" => NodeType::Server,
+                _ => return Err(format!("unknown node type '{}'", node_type)),
+            };
+            Ok((parse_number(Some(id), "node id")?, node_type))
+        })
+        .collect()
+}
+
+// <target> <session_id> <hop_index> <hops> <type> <fields...>, the types are
+//   fragment <index> <total> <payload...> (text, or hex:<bytes>)
+//   ack <index>
+//   nack <index> error_in_routing <id> | destination_is_drone | dropped | unexpected_recipient <id>
+//   flood_request <flood_id> <initiator> [<path_trace>]
+//   flood_response <flood_id> <path_trace>
+pub fn parse_injection(args: &str) -> Result<Injection, String> {
+    let mut tokens = args.split_whitespace();
+    let target = parse_number(tokens.next(), "target")?;
+    let session_id = parse_number(tokens.next(), "session id")?;
+    let hop_index = parse_number(tokens.next(), "hop index")?;
+    let hops = parse_ids(tokens.next())?;
+
+    let body = match tokens.next() {
+        Some("fragment") => PacketBody::MsgFragment {
+            fragment_index: parse_number(tokens.next(), "fragment index")?,
+            total_n_fragments: parse_number(tokens.next(), "number of fragments")?,
+            payload: parse_payload(&tokens.by_ref().collect::<Vec<&str>>().join(" "))?,
+        },
+        Some("ack") => PacketBody::Ack {
+            fragment_index: parse_number(tokens.next(), "fragment index")?,
+        },
+        Some("nack") => {
+            let fragment_index = parse_number(tokens.next(), "fragment index")?;
+            let nack_type = match tokens.next() {
+                Some("error_in_routing") => {
+                    NackKind::ErrorInRouting(parse_number(tokens.next(), "node id")?)
+                }
+                Some("destination_is_drone") => NackKind::DestinationIsDrone,
+                Some("dropped") => NackKind::Dropped,
+                Some("unexpected_recipient") => {
+                    NackKind::UnexpectedRecipient(parse_number(tokens.next(), "node id")?)
+                }
+                other => return Err(format!("unknown nack type '{}'", other.unwrap_or(""))),
+            };
+            PacketBody::Nack {
+                fragment_index,
+                nack_type,
+            }
+        }
+        Some("flood_request") => PacketBody::FloodRequest {
+            flood_id: parse_number(tokens.next(), "flood id")?,
+            initiator_id: parse_number(tokens.next(), "initiator")?,
+            path_trace: parse_trace(tokens.next())?,
+        },
+        Some("flood_response") => PacketBody::FloodResponse {
+            flood_id: parse_number(tokens.next(), "flood id")?,
+            path_trace: parse_trace(tokens.next())?,
+        },
+        other => return Err(format!("unknown packet type '{}'", other.unwrap_or(""))),
+    };
+
+    if tokens.next().is_some() {
+        return Err("too many arguments".to_string());
+    }
+    Ok(Injection {
+        target,
+        session_id,
+        hop_index,
+        hops,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_injections() {
+        assert_eq!(
+            parse_injection("3 42 1 20,3,7,30 fragment 0 2 hello  world").unwrap(),
+            Injection {
+                target: 3,
+                session_id: 42,
+                hop_index: 1,
+                hops: vec![20, 3, 7, 30],
+                body: PacketBody::MsgFragment {
+                    fragment_index: 0,
+                    total_n_fragments: 2,
+                    payload: b"hello world".to_vec(),
+                },
+            }
+        );
+        assert_eq!(
+            parse_injection("3 0 0 3 flood_request 9 20").unwrap().body,
+            PacketBody::FloodRequest {
+                flood_id: 9,
+                initiator_id: 20,
+                path_trace: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_injections() {
+        for args in [
+            "",
+            "3",
+            "3 42 1",
+            "x 42 1 3 ack 0",
+            "3 -1 1 3 ack 0",
+            "3 42 1 3,x ack 0",
+            "3 42 1 3 ack",
+            "3 42 1 3 ack 0 1",
+            "3 42 1 3 ping 0",
+            "3 42 1 3",
+            "3 42 1 3 nack 0",
+            "3 42 1 3 nack 0 lost",
+            "3 42 1 3 nack 0 error_in_routing",
+            "3 42 1 3 flood_request 1",
+            "3 42 1 3 flood_request 1 20 20",
+            "3 42 1 3 flood_response 1 20:router",
+            "3 42 1 3 flood_response 1 300:drone",
+            "3 42 1 3 fragment 0 1 hex:0",
+            "3 42 1 3 fragment 0 1 hex:zz",
+            "3 42 1 3 fragment 0 1 hex:00 11",
+        ] {
+            assert!(parse_injection(args).is_err(), "{}", args);
+        }
+    }
+
+    #[test]
+    fn display_is_parsed_back() {
+        for args in [
+            "3 42 1 20,3,7,30 fragment 0 2 hello world",
+            "3 42 1 20,3 fragment 1 2 ",
+            "3 42 1 20,3 fragment 0 1 hex:00ff0a",
+            "3 42 1 20,3 fragment 0 1 hex:6865783a31",
+            "3 42 1 20,3 fragment 0 1 hex:61202062",
+            "3 42 1 20,3 ack 5",
+            "3 42 1 20,3 nack 5 error_in_routing 7",
+            "3 42 1 20,3 nack 5 destination_is_drone",
+            "3 42 1 20,3 nack 5 dropped",
+            "3 42 1 20,3 nack 5 unexpected_recipient 9",
+            "3 42 0 3 flood_request 1 20 20:client,3:drone",
+            "3 42 0 3 flood_request 1 20 ",
+            "20 42 1 3,20 flood_response 1 20:client,3:drone,30:server",
+        ] {
+            let injection = parse_injection(args).unwrap();
+            assert_eq!(injection.to_string(), args);
+            assert_eq!(parse_injection(&injection.to_string()).unwrap(), injection);
+        }
+    }
+
+    #[test]
+    fn builds_packets() {
+        let injection = parse_injection("3 42 1 20,3,7 fragment 0 1 hello").unwrap();
+        let packet = injection.to_packet();
+        assert_eq!(packet.session_id, 42);
+        assert_eq!(packet.routing_header.hop_index, 1);
+        assert_eq!(packet.routing_header.hops, vec![20, 3, 7]);
+        let PacketType::MsgFragment(fragment) = packet.pack_type else {
+            panic!("expected a fragment");
+        };
+        assert_eq!(fragment.length, 5);
+        assert_eq!(&fragment.data[..5], b"hello");
+        assert!(fragment.data[5..].iter().all(|b| *b == 0));
+
+        // bytes in hex, at most a fragment of them
+        let injection = parse_injection("3 42 1 3 fragment 0 1 hex:68656c6c6f").unwrap();
+        assert_eq!(
+            injection.body,
+            parse_injection("3 42 1 3 fragment 0 1 hello").unwrap().body
+        );
+        let bytes = "00".repeat(FRAGMENT_SIZE);
+        assert!(parse_injection(&format!("3 42 1 3 fragment 0 1 hex:{}", bytes)).is_ok());
+        assert!(parse_injection(&format!("3 42 1 3 fragment 0 1 hex:{}00", bytes)).is_err());
+
+        // the text payload is truncated to the size of a fragment
+        let payload = "x".repeat(FRAGMENT_SIZE + 10);
+        let injection = parse_injection(&format!("3 42 1 3 fragment 0 1 {}", payload)).unwrap();
+        let PacketType::MsgFragment(fragment) = injection.to_packet().pack_type else {
+            panic!("expected a fragment");
+        };
+        assert_eq!(fragment.length as usize, FRAGMENT_SIZE);
+
+        let injection = parse_injection("3 42 1 3 nack 2 error_in_routing 7").unwrap();
+        assert!(matches!(
+            injection.to_packet().pack_type,
+            PacketType::Nack(Nack {
+                fragment_index: 2,
+                nack_type: NackType::ErrorInRouting(7),
+            })
+        ));
+    }
+}
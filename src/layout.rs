@@ -0,0 +1,488 @@
+use std::collections::{HashMap, VecDeque};
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use wg_internal::network::NodeId;
+
+use crate::topology::{NodeType, Topology};
+
+// iterations of the force-directed layout, fewer when only some links changed
+const ITERATIONS: usize = 300;
+const INCREMENTAL_ITERATIONS: usize = 60;
+// maximum displacement of a node in the first iteration, it cools down to 0
+const TEMPERATURE: f32 = 0.1;
+const INCREMENTAL_TEMPERATURE: f32 = 0.02;
+// pull towards the center, so that isolated nodes do not stick to the border
+const GRAVITY: f32 = 0.05;
+// nodes are kept away from the border of the area
+const MARGIN: f32 = 0.04;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutMode {
+    ForceDirected,
+    Layered,  // clients on the left, drones by hops from the clients, servers on the right
+    Circular, // drones on a circle in the order of the configuration
+}
+
+impl LayoutMode {
+    // 0 force-directed, 1 layered, 2 circular (as in the window)
+    pub fn from_index(index: i32) -> Option<LayoutMode> {
+        match index {
+            0 => Some(LayoutMode::ForceDirected),
+            1 => Some(LayoutMode::Layered),
+            2 => Some(LayoutMode::Circular),
+            _ => None,
+        }
+    }
+
+    pub fn index(self) -> i32 {
+        match self {
+            LayoutMode::ForceDirected => 0,
+            LayoutMode::Layered => 1,
+            LayoutMode::Circular => 2,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PinnedNode {
+    id: NodeId,
+    x: f32,
+    y: f32,
+}
+
+// content of the sidecar file of a configuration, e.g. star.layout.toml for star.toml
+#[derive(Serialize, Deserialize)]
+struct LayoutFile {
+    mode: LayoutMode,
+    #[serde(default)]
+    nodes: Vec<PinnedNode>,
+}
+
+// sidecar file with the layout of a configuration file
+pub fn sidecar_path(config: &str) -> String {
+    match config.strip_suffix(".toml") {
+        Some(stem) => format!("{}.layout.toml", stem),
+        None => format!("{}.layout.toml", config),
+    }
+}
+
+// positions of the centers of the nodes in [0,1]x[0,1], the window maps them to its area
+#[derive(Debug, Clone)]
+pub struct Layout {
+    mode: LayoutMode,
+    positions: HashMap<NodeId, (f32, f32)>,
+    pinned: HashMap<NodeId, (f32, f32)>, // nodes dragged by the user, never moved by the layout
+    sidecar: Option<String>,             // where the layout of the loaded configuration is kept
+    // graph of the last update, the layout runs again only when it changes
+    nodes: Vec<(NodeId, NodeType)>,
+    links: Vec<(NodeId, NodeId)>,
+}
+
+// deterministic direction for a node, the golden angle spreads consecutive ids
+fn direction(id: NodeId) -> (f32, f32) {
+    let angle = id as f32 * 2.399_963;
+    (angle.cos(), angle.sin())
+}
+
+fn clamp(value: f32) -> f32 {
+    value.clamp(MARGIN, 1.0 - MARGIN)
+}
+
+// y of the i-th of n nodes spread on a column
+fn spread(i: usize, n: usize) -> f32 {
+    MARGIN + (1.0 - 2.0 * MARGIN) * (i + 1) as f32 / (n + 1) as f32
+}
+
+impl Layout {
+    pub fn new(mode: LayoutMode) -> Layout {
+        Layout {
+            mode,
+            positions: HashMap::new(),
+            pinned: HashMap::new(),
+            sidecar: None,
+            nodes: vec![],
+            links: vec![],
+        }
+    }
+
+    pub fn mode(&self) -> LayoutMode {
+        self.mode
+    }
+
+    pub fn position(&self, id: NodeId) -> (f32, f32) {
+        self.positions.get(&id).copied().unwrap_or((0.5, 0.5))
+    }
+
+    // compute again the positions if the nodes or the links of the topology changed,
+    // the force-directed layout starts from the previous positions
+    pub fn update(&mut self, topology: &Topology) -> bool {
+        let nodes: Vec<(NodeId, NodeType)> =
+            topology.nodes().map(|n| (n.id, n.node_type)).collect();
+        let mut links: Vec<(NodeId, NodeId)> = topology
+            .links()
+            .into_iter()
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .collect();
+        links.sort();
+        if nodes == self.nodes && links == self.links && !self.positions.is_empty() {
+            return false;
+        }
+
+        let incremental = self.nodes.iter().any(|n| nodes.contains(n));
+        self.nodes = nodes;
+        self.links = links;
+        self.positions
+            .retain(|id, _| self.nodes.iter().any(|(n, _)| n == id));
+        self.pinned
+            .retain(|id, _| self.nodes.iter().any(|(n, _)| n == id));
+        self.run(incremental);
+        true
+    }
+
+    // compute again the positions of the last topology with another layout, the dragged nodes
+    // are released
+    pub fn set_mode(&mut self, mode: LayoutMode) {
+        self.mode = mode;
+        self.pinned.clear();
+        self.run(false);
+    }
+
+    // node dragged to the given position, it stays there until the layout is changed
+    pub fn pin(&mut self, id: NodeId, x: f32, y: f32) {
+        let position = (x.clamp(0.0, 1.0), y.clamp(0.0, 1.0));
+        self.pinned.insert(id, position);
+        self.positions.insert(id, position);
+    }
+
+    // start from the sidecar of a configuration (if it exists) before its topology is shown
+    pub fn open(&mut self, config: &str) -> Result<(), String> {
+        let path = sidecar_path(config);
+        self.sidecar = Some(path.clone());
+        self.positions.clear();
+        self.pinned.clear();
+        self.nodes.clear();
+        self.links.clear();
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(format!("{}: {}", path, e)),
+        };
+        let file: LayoutFile =
+            toml::from_str(&content).map_err(|e| format!("{}: {}", path, e.message()))?;
+        self.mode = file.mode;
+        for node in file.nodes {
+            self.pin(node.id, node.x, node.y);
+        }
+        Ok(())
+    }
+
+    // write the mode and the dragged nodes to the sidecar of the loaded configuration
+    pub fn save(&self) -> Result<(), String> {
+        match self.sidecar {
+            Some(ref path) => self.save_as(path),
+            None => Ok(()),
+        }
+    }
+
+    pub fn save_as(&self, path: &str) -> Result<(), String> {
+        let mut nodes: Vec<PinnedNode> = self
+            .pinned
+            .iter()
+            .map(|(id, (x, y))| PinnedNode {
+                id: *id,
+                x: *x,
+                y: *y,
+            })
+            .collect();
+        nodes.sort_by_key(|n| n.id);
+        let file = LayoutFile {
+            mode: self.mode,
+            nodes,
+        };
+        let content = toml::to_string(&file).map_err(|e| e.to_string())?;
+        std::fs::write(path, content).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn run(&mut self, incremental: bool) {
+        match self.mode {
+            LayoutMode::ForceDirected => self.force_directed(incremental),
+            LayoutMode::Layered => self.layered(),
+            LayoutMode::Circular => self.circular(),
+        }
+        for (id, position) in &self.pinned {
+            self.positions.insert(*id, *position);
+        }
+    }
+
+    fn ids_of(&self, node_type: NodeType) -> Vec<NodeId> {
+        self.nodes
+            .iter()
+            .filter(|(_, t)| *t == node_type)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    fn neighbours(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.links.iter().filter_map(move |&(a, b)| {
+            if a == id {
+                Some(b)
+            } else if b == id {
+                Some(a)
+            } else {
+                None
+            }
+        })
+    }
+
+    // clients and servers on the two sides, spread on their column
+    fn place_sides(&mut self) {
+        for (node_type, x) in [(NodeType::Client, MARGIN), (NodeType::Server, 1.0 - MARGIN)] {
+            let ids = self.ids_of(node_type);
+            for (i, id) in ids.iter().enumerate() {
+                self.positions.insert(*id, (x, spread(i, ids.len())));
+            }
+        }
+    }
+
+    fn circular(&mut self) {
+        self.place_sides();
+        let drones = self.ids_of(NodeType::Drone);
+        for (i, id) in drones.iter().enumerate() {
+            let angle = 2.0 * PI * i as f32 / drones.len() as f32;
+            self.positions
+                .insert(*id, (0.5 + 0.35 * angle.cos(), 0.5 + 0.4 * angle.sin()));
+        }
+    }
+
+    fn layered(&mut self) {
+        self.place_sides();
+
+        // hops from the nearest client, through drones only
+        let mut depth: HashMap<NodeId, usize> = HashMap::new();
+        let mut queue: VecDeque<NodeId> = self.ids_of(NodeType::Client).into();
+        for id in &queue {
+            depth.insert(*id, 0);
+        }
+        while let Some(id) = queue.pop_front() {
+            let d = depth[&id];
+            let next: Vec<NodeId> = self.neighbours(id).collect();
+            for adj in next {
+                let is_drone = self
+                    .nodes
+                    .iter()
+                    .any(|&(n, t)| n == adj && t == NodeType::Drone);
+                if is_drone && !depth.contains_key(&adj) {
+                    depth.insert(adj, d + 1);
+                    queue.push_back(adj);
+                }
+            }
+        }
+
+        // drones not reachable from a client go in a column after the others
+        let drones = self.ids_of(NodeType::Drone);
+        let deepest = drones.iter().filter_map(|id| depth.get(id)).max().copied();
+        let unreachable = deepest.unwrap_or(0) + 1;
+        let columns = if drones.iter().all(|id| depth.contains_key(id)) {
+            unreachable - 1
+        } else {
+            unreachable
+        };
+        for column in 1..=columns {
+            let x = MARGIN + (1.0 - 2.0 * MARGIN) * column as f32 / (columns + 1) as f32;
+            let mut ids: Vec<NodeId> = drones
+                .iter()
+                .filter(|id| depth.get(id).copied().unwrap_or(unreachable) == column)
+                .copied()
+                .collect();
+            // ordered by the mean y of the neighbours already placed, to avoid crossings
+            let order: HashMap<NodeId, f32> = ids
+                .iter()
+                .map(|id| {
+                    let placed: Vec<f32> = self
+                        .neighbours(*id)
+                        .filter_map(|adj| {
+                            let previous = depth.get(&adj).is_some_and(|d| *d < column);
+                            previous.then(|| self.position(adj).1)
+                        })
+                        .collect();
+                    let y = if placed.is_empty() {
+                        0.5
+                    } else {
+                        placed.iter().sum::<f32>() / placed.len() as f32
+                    };
+                    (*id, y)
+                })
+                .collect();
+            ids.sort_by(|a, b| order[a].total_cmp(&order[b]).then(a.cmp(b)));
+            for (i, id) in ids.iter().enumerate() {
+                self.positions.insert(*id, (x, spread(i, ids.len())));
+            }
+        }
+    }
+
+    // fruchterman-reingold in the unit square
+    fn force_directed(&mut self, incremental: bool) {
+        let ids: Vec<NodeId> = self.nodes.iter().map(|(id, _)| *id).collect();
+        if ids.is_empty() {
+            return;
+        }
+
+        // new nodes start near their placed neighbours, or around the center
+        for id in &ids {
+            if self.positions.contains_key(id) {
+                continue;
+            }
+            let placed: Vec<(f32, f32)> = self
+                .neighbours(*id)
+                .filter_map(|adj| self.positions.get(&adj).copied())
+                .collect();
+            let (dx, dy) = direction(*id);
+            let position = if placed.is_empty() {
+                (0.5 + 0.3 * dx, 0.5 + 0.3 * dy)
+            } else {
+                let n = placed.len() as f32;
+                let x = placed.iter().map(|p| p.0).sum::<f32>() / n;
+                let y = placed.iter().map(|p| p.1).sum::<f32>() / n;
+                (x + 0.05 * dx, y + 0.05 * dy)
+            };
+            self.positions
+                .insert(*id, (clamp(position.0), clamp(position.1)));
+        }
+
+        let (iterations, temperature) = if incremental {
+            (INCREMENTAL_ITERATIONS, INCREMENTAL_TEMPERATURE)
+        } else {
+            (ITERATIONS, TEMPERATURE)
+        };
+        let k = (1.0 / ids.len() as f32).sqrt() * 0.6;
+        let index: HashMap<NodeId, usize> =
+            ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let mut positions: Vec<(f32, f32)> = ids.iter().map(|id| self.positions[id]).collect();
+
+        for iteration in 0..iterations {
+            let mut shift = vec![(0.0f32, 0.0f32); ids.len()];
+
+            // every pair of nodes repels
+            for i in 0..ids.len() {
+                for j in i + 1..ids.len() {
+                    let (mut dx, mut dy) = (
+                        positions[i].0 - positions[j].0,
+                        positions[i].1 - positions[j].1,
+                    );
+                    let mut d = (dx * dx + dy * dy).sqrt();
+                    if d < 1e-4 {
+                        (dx, dy) = direction(ids[i].wrapping_add(ids[j]));
+                        d = 1.0;
+                    }
+                    let force = k * k / d.max(0.01);
+                    shift[i].0 += dx / d * force;
+                    shift[i].1 += dy / d * force;
+                    shift[j].0 -= dx / d * force;
+                    shift[j].1 -= dy / d * force;
+                }
+            }
+
+            // linked nodes attract
+            for (a, b) in &self.links {
+                let (i, j) = (index[a], index[b]);
+                let (dx, dy) = (
+                    positions[i].0 - positions[j].0,
+                    positions[i].1 - positions[j].1,
+                );
+                let d = (dx * dx + dy * dy).sqrt();
+                if d < 1e-4 {
+                    continue;
+                }
+                let force = d * d / k;
+                shift[i].0 -= dx / d * force;
+                shift[i].1 -= dy / d * force;
+                shift[j].0 += dx / d * force;
+                shift[j].1 += dy / d * force;
+            }
+
+            // the displacement is limited by the temperature, that cools down linearly
+            let t = temperature * (1.0 - iteration as f32 / iterations as f32);
+            for (i, position) in positions.iter_mut().enumerate() {
+                if self.pinned.contains_key(&ids[i]) {
+                    continue;
+                }
+                let (mut dx, mut dy) = shift[i];
+                dx += (0.5 - position.0) * GRAVITY;
+                dy += (0.5 - position.1) * GRAVITY;
+                let d = (dx * dx + dy * dy).sqrt();
+                if d > 0.0 {
+                    let step = d.min(t);
+                    position.0 = clamp(position.0 + dx / d * step);
+                    position.1 = clamp(position.1 + dy / d * step);
+                }
+            }
+        }
+
+        for (id, position) in ids.iter().zip(positions) {
+            self.positions.insert(*id, position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::topology::Node;
+
+    fn node(id: NodeId, node_type: NodeType, neighbours: Vec<NodeId>) -> Node {
+        Node {
+            id,
+            node_type,
+            pdr: 0.0,
+            crashed: false,
+            neighbours,
+        }
+    }
+
+    #[test]
+    fn pinned_nodes_are_saved_in_the_sidecar() {
+        assert_eq!(
+            sidecar_path("config_files/star.toml"),
+            "config_files/star.layout.toml"
+        );
+        assert_eq!(sidecar_path("star"), "star.layout.toml");
+
+        let config = std::env::temp_dir().join("pinned_nodes_are_saved_in_the_sidecar.toml");
+        let config = config.to_string_lossy().to_string();
+        let mut topology = Topology::from_nodes(vec![
+            node(1, NodeType::Drone, vec![2, 10]),
+            node(2, NodeType::Drone, vec![1, 20]),
+            node(10, NodeType::Client, vec![1]),
+            node(20, NodeType::Server, vec![2]),
+        ]);
+
+        // no sidecar yet, the layout starts empty
+        let mut layout = Layout::new(LayoutMode::Circular);
+        layout.open(&config).unwrap();
+        layout.update(&topology);
+        layout.pin(1, 0.2, 0.3);
+        layout.pin(10, 1.5, -1.0);
+        layout.save().unwrap();
+
+        let mut reopened = Layout::new(LayoutMode::ForceDirected);
+        reopened.open(&config).unwrap();
+        let _ = std::fs::remove_file(sidecar_path(&config));
+        assert_eq!(reopened.mode(), LayoutMode::Circular);
+        assert!(reopened.update(&topology));
+        assert_eq!(reopened.position(1), (0.2, 0.3));
+        assert_eq!(reopened.position(10), (1.0, 0.0));
+        assert_eq!(reopened.position(2), layout.position(2));
+
+        // the pinned nodes stay when the links change, until the layout is changed
+        topology.remove_link(1, 2);
+        assert!(reopened.update(&topology));
+        assert_eq!(reopened.position(1), (0.2, 0.3));
+        reopened.set_mode(LayoutMode::Layered);
+        assert_ne!(reopened.position(1), (0.2, 0.3));
+    }
+}
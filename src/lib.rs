@@ -0,0 +1,22 @@
+pub mod chaos;
+pub mod command;
+pub mod controller;
+pub mod event_loop;
+pub mod generator;
+pub mod group;
+pub mod headless;
+pub mod inject;
+pub mod layout;
+pub mod link;
+pub mod lint;
+pub mod pdr_profile;
+pub mod replay;
+pub mod route;
+pub mod sampling;
+pub mod scenario;
+pub mod stats;
+pub mod tap;
+pub mod topology;
+pub mod trace;
+
+pub use controller::{ConnectivityCheck, ControllerError, ControllerUpdate, SimulationController};
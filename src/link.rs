@@ -0,0 +1,350 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{never, unbounded, Receiver, RecvError, Sender};
+use crossbeam::select;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use wg_internal::network::NodeId;
+use wg_internal::packet::{Packet, PacketType};
+
+use crate::tap::SharedCapture;
+
+// wait of an idle relay, the relay wakes up earlier when it receives a packet
+const IDLE_TIMEOUT: Duration = Duration::from_secs(3600);
+
+// longest delay (and jitter) of a link
+pub const MAX_DELAY: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jitter {
+    Uniform, // delay in [delay - jitter, delay + jitter]
+    Normal,  // jitter is the standard deviation
+}
+
+// emulated behaviour of one direction of a link, the default one is an instantaneous link
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkProfile {
+    pub delay: Duration,
+    pub jitter: Duration,
+    pub distribution: Jitter,
+    pub bandwidth: u32, // bytes per second, 0 = unlimited
+}
+
+impl Default for LinkProfile {
+    fn default() -> Self {
+        LinkProfile {
+            delay: Duration::ZERO,
+            jitter: Duration::ZERO,
+            distribution: Jitter::Uniform,
+            bandwidth: 0,
+        }
+    }
+}
+
+impl LinkProfile {
+    // profile given in the units of the UI: delays in ms, bandwidth in kB/s (0 = unlimited)
+    pub fn from_millis(
+        delay: f32,
+        jitter: f32,
+        distribution: Jitter,
+        bandwidth: f32,
+    ) -> Result<LinkProfile, String> {
+        let millis = |ms: f32, name: &str| {
+            Duration::try_from_secs_f32(ms / 1000.0)
+                .ok()
+                .filter(|d| *d <= MAX_DELAY)
+                .ok_or(format!(
+                    "{} {} ms is not in [0,{}]",
+                    name,
+                    ms,
+                    MAX_DELAY.as_millis()
+                ))
+        };
+        let max_bandwidth = u32::MAX as f32 / 1000.0;
+        if !(0.0..=max_bandwidth).contains(&bandwidth) {
+            return Err(format!(
+                "bandwidth {} kB/s is not in [0,{}]",
+                bandwidth, max_bandwidth
+            ));
+        }
+
+        Ok(LinkProfile {
+            delay: millis(delay, "delay")?,
+            jitter: millis(jitter, "jitter")?,
+            distribution,
+            bandwidth: (bandwidth * 1000.0) as u32,
+        })
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.delay <= MAX_DELAY && self.jitter <= MAX_DELAY
+    }
+
+    pub fn is_instantaneous(&self) -> bool {
+        self.delay.is_zero() && self.jitter.is_zero() && self.bandwidth == 0
+    }
+
+    // delay of a single packet, never negative
+    fn sample_delay(&self, rng: &mut StdRng) -> Duration {
+        let jitter = self.jitter.as_secs_f64();
+        let offset = match self.distribution {
+            _ if self.jitter.is_zero() => 0.0,
+            Jitter::Uniform => rng.gen_range(-jitter..=jitter),
+            Jitter::Normal => {
+                // box-muller transform
+                let (u1, u2): (f64, f64) = (1.0 - rng.gen::<f64>(), rng.gen());
+                jitter * (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+            }
+        };
+        Duration::from_secs_f64((self.delay.as_secs_f64() + offset).max(0.0))
+    }
+}
+
+// approximate size on the wire, used for the bandwidth limit
+pub fn packet_size(packet: &Packet) -> usize {
+    let header = 8 + packet.routing_header.hops.len(); // session id and hops
+    let body = match packet.pack_type {
+        PacketType::MsgFragment(ref fragment) => 16 + fragment.length as usize,
+        PacketType::FloodRequest(ref request) => 16 + 2 * request.path_trace.len(),
+        PacketType::FloodResponse(ref response) => 8 + 2 * response.path_trace.len(),
+        _ => 16,
+    };
+    header + body
+}
+
+// packet waiting to be delivered, ordered by delivery time (then by arrival)
+struct Scheduled {
+    at: Instant,
+    seq: u64,
+    packet: Packet,
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        (self.at, self.seq) == (other.at, other.seq)
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.at, self.seq).cmp(&(other.at, other.seq))
+    }
+}
+
+// changes of a running relay
+enum RelayControl {
+    Profile(LinkProfile),
+    Tap(Option<SharedCapture>),
+}
+
+enum Next {
+    Packet(Result<Packet, RecvError>),
+    Control(Result<RelayControl, RecvError>),
+    Deliver,
+}
+
+// thread placed between two nodes on one direction of a link: the sending node gets the input
+// of the relay instead of the sender of the receiving node; the relay stops when the sending
+// node drops its sender (link removed or node crashed) and every packet has been delivered;
+// if the link is tapped, every packet is recorded when it enters the relay
+pub struct Relay {
+    control: Sender<RelayControl>,
+}
+
+impl Relay {
+    pub fn start(
+        from: NodeId,
+        to: NodeId,
+        profile: LinkProfile,
+        tap: Option<SharedCapture>,
+        output: Sender<Packet>,
+    ) -> (Relay, Sender<Packet>) {
+        let (input_send, input) = unbounded::<Packet>();
+        let (control, controls) = unbounded::<RelayControl>();
+
+        thread::spawn(move || {
+            let mut input: Option<Receiver<Packet>> = Some(input);
+            let mut controls: Option<Receiver<RelayControl>> = Some(controls);
+            let mut profile = profile;
+            let mut tap = tap;
+            let mut rng = StdRng::seed_from_u64(((from as u64) << 8) | to as u64);
+            let mut queue: BinaryHeap<Reverse<Scheduled>> = BinaryHeap::new();
+            let mut link_free = Instant::now(); // end of the transmission of the last packet
+            let mut seq = 0;
+
+            while input.is_some() || !queue.is_empty() {
+                let no_packets = never();
+                let no_controls = never();
+                let timeout = queue
+                    .peek()
+                    .map(|Reverse(s)| s.at.saturating_duration_since(Instant::now()))
+                    .unwrap_or(IDLE_TIMEOUT);
+
+                let next = select! {
+                    recv(input.as_ref().unwrap_or(&no_packets)) -> packet => Next::Packet(packet),
+                    recv(controls.as_ref().unwrap_or(&no_controls)) -> c => Next::Control(c),
+                    default(timeout) => Next::Deliver,
+                };
+
+                match next {
+                    Next::Packet(Ok(packet)) => {
+                        if let Some(ref capture) = tap {
+                            capture.lock().unwrap().record(from, to, &packet);
+                        }
+                        let now = Instant::now();
+                        let start = link_free.max(now);
+                        link_free = match profile.bandwidth {
+                            0 => start,
+                            bandwidth => {
+                                start
+                                    + Duration::from_secs_f64(
+                                        packet_size(&packet) as f64 / bandwidth as f64,
+                                    )
+                            }
+                        };
+                        let at = link_free + profile.sample_delay(&mut rng);
+                        queue.push(Reverse(Scheduled { at, seq, packet }));
+                        seq += 1;
+                    }
+                    Next::Packet(Err(_)) => input = None,
+                    Next::Control(Ok(RelayControl::Profile(p))) => profile = p,
+                    Next::Control(Ok(RelayControl::Tap(t))) => tap = t,
+                    Next::Control(Err(_)) => controls = None,
+                    Next::Deliver => {}
+                }
+
+                let now = Instant::now();
+                while queue.peek().is_some_and(|Reverse(s)| s.at <= now) {
+                    let Reverse(scheduled) = queue.pop().unwrap();
+                    // the receiving node may have crashed, the packet is lost
+                    let _ = output.send(scheduled.packet);
+                }
+            }
+        });
+
+        (Relay { control }, input_send)
+    }
+
+    // false if the relay has already stopped
+    pub fn set_profile(&self, profile: LinkProfile) -> bool {
+        self.control.send(RelayControl::Profile(profile)).is_ok()
+    }
+
+    pub fn set_tap(&self, tap: Option<SharedCapture>) -> bool {
+        self.control.send(RelayControl::Tap(tap)).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wg_internal::network::SourceRoutingHeader;
+    use wg_internal::packet::Ack;
+
+    fn profile(delay: u64, jitter: u64, distribution: Jitter) -> LinkProfile {
+        LinkProfile {
+            delay: Duration::from_millis(delay),
+            jitter: Duration::from_millis(jitter),
+            distribution,
+            bandwidth: 0,
+        }
+    }
+
+    #[test]
+    fn delays_stay_in_bounds() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let fixed = profile(10, 0, Jitter::Normal);
+        assert_eq!(fixed.sample_delay(&mut rng), Duration::from_millis(10));
+
+        let uniform = profile(100, 20, Jitter::Uniform);
+        for _ in 0..1000 {
+            let delay = uniform.sample_delay(&mut rng);
+            assert!(delay >= Duration::from_millis(80) && delay <= Duration::from_millis(120));
+        }
+
+        // the negative offsets are cut at zero
+        let normal = profile(0, 10, Jitter::Normal);
+        let delays: Vec<Duration> = (0..1000).map(|_| normal.sample_delay(&mut rng)).collect();
+        assert!(delays.iter().any(|d| d.is_zero()));
+        assert!(delays.iter().any(|d| !d.is_zero()));
+
+        // same seed, same delays
+        let mut rng1 = StdRng::seed_from_u64(3);
+        let mut rng2 = StdRng::seed_from_u64(3);
+        for _ in 0..10 {
+            assert_eq!(
+                uniform.sample_delay(&mut rng1),
+                uniform.sample_delay(&mut rng2)
+            );
+        }
+    }
+
+    #[test]
+    fn profiles_from_millis() {
+        let profile = LinkProfile::from_millis(250.0, 3_600_000.0, Jitter::Normal, 1.5).unwrap();
+        assert_eq!(profile.delay, Duration::from_millis(250));
+        assert_eq!(profile.jitter, MAX_DELAY);
+        assert_eq!(profile.bandwidth, 1500);
+        assert!(profile.is_valid());
+
+        assert!(LinkProfile::from_millis(-1.0, 0.0, Jitter::Uniform, 0.0).is_err());
+        assert!(LinkProfile::from_millis(0.0, 3_600_001.0, Jitter::Uniform, 0.0).is_err());
+        assert!(LinkProfile::from_millis(f32::NAN, 0.0, Jitter::Uniform, 0.0).is_err());
+        assert!(LinkProfile::from_millis(0.0, 0.0, Jitter::Uniform, 5_000_000.0).is_err());
+        assert!(!LinkProfile {
+            delay: MAX_DELAY + Duration::from_millis(1),
+            ..LinkProfile::default()
+        }
+        .is_valid());
+    }
+
+    #[test]
+    fn bandwidth_spaces_the_departures() {
+        let (output, received) = unbounded();
+        let limited = LinkProfile {
+            bandwidth: 500,
+            ..LinkProfile::default()
+        };
+        let (_relay, input) = Relay::start(1, 2, limited, None, output);
+
+        let packet = |session_id| Packet {
+            routing_header: SourceRoutingHeader {
+                hop_index: 1,
+                hops: vec![1, 2],
+            },
+            session_id,
+            pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+        };
+        // 26 bytes at 500 bytes per second, one packet every 52 ms
+        let transmission = Duration::from_millis(52);
+        assert_eq!(packet_size(&packet(0)), 26);
+
+        let start = Instant::now();
+        for session_id in 0..3 {
+            input.send(packet(session_id)).unwrap();
+        }
+        drop(input);
+
+        for (n, expected) in (0..3).zip(1..) {
+            let packet = received.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(packet.session_id, n);
+            assert!(start.elapsed() >= transmission * expected);
+        }
+        assert!(start.elapsed() < Duration::from_secs(1));
+        // every packet has been delivered and the input is closed, the relay stops
+        assert!(received.recv_timeout(Duration::from_secs(5)).is_err());
+    }
+}
@@ -0,0 +1,514 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::Range;
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use wg_internal::network::NodeId;
+
+use crate::pdr_profile::PdrProfile;
+use crate::topology::{Node, NodeType, Topology};
+
+// bounds on the number of drones a client or a server is linked to
+pub const CLIENT_LINKS: (usize, usize) = (1, 2);
+pub const SERVER_MIN_LINKS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+// problem found in a configuration file, line and column start from 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.line, self.column, severity, self.message
+        )
+    }
+}
+
+// the ids are read as i64 so that out-of-range values can be reported
+#[derive(Deserialize)]
+struct RawNode {
+    id: Spanned<i64>,
+    connected_drone_ids: Spanned<Vec<Spanned<i64>>>,
+    pdr: Option<Spanned<f64>>,
+    pdr_profile: Option<Spanned<String>>,
+}
+
+#[derive(Deserialize)]
+struct RawConfig {
+    #[serde(default)]
+    drones: Vec<Spanned<RawNode>>,
+    #[serde(default)]
+    clients: Vec<Spanned<RawNode>>,
+    #[serde(default)]
+    servers: Vec<Spanned<RawNode>>,
+    #[serde(default)]
+    groups: BTreeMap<String, Spanned<Vec<Spanned<i64>>>>,
+}
+
+struct Linter<'a> {
+    content: &'a str,
+    issues: Vec<Issue>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, span: Range<usize>, severity: Severity, message: String) {
+        // line and column of the byte offset
+        let before = &self.content[..span.start.min(self.content.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |p| p + 1) + 1;
+        self.issues.push(Issue {
+            line,
+            column,
+            severity,
+            message,
+        });
+    }
+}
+
+fn type_name(node_type: NodeType) -> &'static str {
+    match node_type {
+        NodeType::Drone => "drone",
+        NodeType::Client => "client",
+        NodeType::Server => "server",
+    }
+}
+
+// check a whole configuration file, every problem is reported
+pub fn lint_config(path: &str) -> Result<Vec<Issue>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(lint_str(&content))
+}
+
+// same as lint_config, on the content of the file; the issues are sorted by line
+pub fn lint_str(content: &str) -> Vec<Issue> {
+    let mut linter = Linter {
+        content,
+        issues: vec![],
+    };
+
+    let config: RawConfig = match toml::from_str(content) {
+        Ok(config) => config,
+        Err(e) => {
+            let span = e.span().unwrap_or(0..0);
+            linter.report(span, Severity::Error, e.message().to_string());
+            return linter.issues;
+        }
+    };
+
+    let all: Vec<(NodeType, &Spanned<RawNode>)> = config
+        .drones
+        .iter()
+        .map(|n| (NodeType::Drone, n))
+        .chain(config.clients.iter().map(|n| (NodeType::Client, n)))
+        .chain(config.servers.iter().map(|n| (NodeType::Server, n)))
+        .collect();
+
+    // ids: range and duplicates
+    let mut types: HashMap<i64, NodeType> = HashMap::new();
+    for (node_type, node) in &all {
+        let id = *node.get_ref().id.get_ref();
+        let span = node.get_ref().id.span();
+        if !(0..=NodeId::MAX as i64).contains(&id) {
+            linter.report(
+                span,
+                Severity::Error,
+                format!("id {} is out of range [0,{}]", id, NodeId::MAX),
+            );
+        } else if let Some(other) = types.get(&id) {
+            linter.report(
+                span,
+                Severity::Error,
+                format!("id {} is already used by a {}", id, type_name(*other)),
+            );
+        } else {
+            types.insert(id, *node_type);
+        }
+    }
+
+    let neighbours_of = |id: i64| -> Option<Vec<i64>> {
+        all.iter()
+            .find(|(_, n)| *n.get_ref().id.get_ref() == id)
+            .map(|(_, n)| {
+                n.get_ref()
+                    .connected_drone_ids
+                    .get_ref()
+                    .iter()
+                    .map(|adj| *adj.get_ref())
+                    .collect()
+            })
+    };
+
+    // links that passed every check, used for the reachability of the servers
+    let mut valid_links: BTreeMap<NodeId, (NodeType, Vec<NodeId>)> = BTreeMap::new();
+
+    for (node_type, node) in &all {
+        let node = node.get_ref();
+        let id = *node.id.get_ref();
+        let name = format!("{} {}", type_name(*node_type), id);
+        let mut links = vec![];
+
+        // pdr
+        match (node_type, &node.pdr) {
+            (NodeType::Drone, None) => linter.report(
+                node.id.span(),
+                Severity::Error,
+                format!("{} has no pdr", name),
+            ),
+            (NodeType::Drone, Some(pdr)) if !(0.0..=1.0).contains(pdr.get_ref()) => linter.report(
+                pdr.span(),
+                Severity::Error,
+                format!("pdr {} of {} is not in [0,1]", pdr.get_ref(), name),
+            ),
+            _ => {}
+        }
+        if let Some(ref spec) = node.pdr_profile {
+            if *node_type != NodeType::Drone {
+                linter.report(
+                    spec.span(),
+                    Severity::Warning,
+                    format!("{} has a pdr profile, only drones drop packets", name),
+                );
+            } else if let Err(e) = PdrProfile::from_spec(spec.get_ref()) {
+                linter.report(
+                    spec.span(),
+                    Severity::Error,
+                    format!("pdr profile of {}: {}", name, e),
+                );
+            }
+        }
+
+        // links
+        let mut seen = vec![];
+        let mut drone_links = 0;
+        for adj in node.connected_drone_ids.get_ref() {
+            let adj_id = *adj.get_ref();
+            if seen.contains(&adj_id) {
+                linter.report(
+                    adj.span(),
+                    Severity::Warning,
+                    format!("{} is linked to {} twice", name, adj_id),
+                );
+                continue;
+            }
+            seen.push(adj_id);
+
+            if adj_id == id {
+                linter.report(
+                    adj.span(),
+                    Severity::Error,
+                    format!("{} is linked to itself", name),
+                );
+                continue;
+            }
+            let Some(adj_type) = types.get(&adj_id) else {
+                linter.report(
+                    adj.span(),
+                    Severity::Error,
+                    format!("{} is linked to unknown node {}", name, adj_id),
+                );
+                continue;
+            };
+            if *node_type != NodeType::Drone && *adj_type != NodeType::Drone {
+                linter.report(
+                    adj.span(),
+                    Severity::Error,
+                    format!(
+                        "{} is linked to {} {}, only drones can be linked to clients and servers",
+                        name,
+                        type_name(*adj_type),
+                        adj_id
+                    ),
+                );
+                continue;
+            }
+            if *adj_type == NodeType::Drone {
+                drone_links += 1;
+            }
+            if !neighbours_of(adj_id).is_some_and(|n| n.contains(&id)) {
+                linter.report(
+                    adj.span(),
+                    Severity::Error,
+                    format!(
+                        "{} is linked to {} {}, but not the other way round",
+                        name,
+                        type_name(*adj_type),
+                        adj_id
+                    ),
+                );
+                continue;
+            }
+            links.push(adj_id as NodeId);
+        }
+        // the first node with a valid id (the ones of the other nodes are already reported)
+        if let Ok(node_id) = NodeId::try_from(id) {
+            valid_links.entry(node_id).or_insert((*node_type, links));
+        }
+
+        let span = node.connected_drone_ids.span();
+        match node_type {
+            NodeType::Client if drone_links < CLIENT_LINKS.0 || drone_links > CLIENT_LINKS.1 => {
+                linter.report(
+                    span,
+                    Severity::Error,
+                    format!(
+                        "{} is linked to {} drones, it must be linked to {} or {}",
+                        name, drone_links, CLIENT_LINKS.0, CLIENT_LINKS.1
+                    ),
+                )
+            }
+            NodeType::Server if drone_links < SERVER_MIN_LINKS => linter.report(
+                span,
+                Severity::Error,
+                format!(
+                    "{} is linked to {} drones, it must be linked to at least {}",
+                    name, drone_links, SERVER_MIN_LINKS
+                ),
+            ),
+            _ => {}
+        }
+    }
+
+    // groups: names usable in the commands and known members
+    for (name, members) in &config.groups {
+        let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid_name {
+            linter.report(
+                members.span(),
+                Severity::Error,
+                format!(
+                    "group name '{}' must start with a letter and contain only letters, digits, '_' and '-'",
+                    name
+                ),
+            );
+        }
+        for id in members.get_ref() {
+            if !types.contains_key(id.get_ref()) {
+                linter.report(
+                    id.span(),
+                    Severity::Error,
+                    format!("group {} contains unknown node {}", name, id.get_ref()),
+                );
+            }
+        }
+    }
+
+    // clients that cannot reach some servers, only valid links are used; the network would be
+    // partitioned, so it is an error
+    let nodes: Vec<Node> = valid_links
+        .into_iter()
+        .map(|(id, (node_type, neighbours))| Node {
+            id,
+            node_type,
+            pdr: 0.0,
+            crashed: false,
+            neighbours,
+        })
+        .collect();
+    let topology = Topology::from_nodes(nodes);
+    for (client, server) in topology.unreachable_pairs() {
+        if let Some((_, node)) = all
+            .iter()
+            .find(|(t, n)| *t == NodeType::Client && *n.get_ref().id.get_ref() == client as i64)
+        {
+            linter.report(
+                node.span(),
+                Severity::Error,
+                format!("client {} cannot reach server {}", client, server),
+            );
+        }
+    }
+
+    let mut issues = linter.issues;
+    issues.sort_by_key(|i| (i.line, i.column));
+    issues
+}
+
+pub fn has_errors(issues: &[Issue]) -> bool {
+    issues.iter().any(|i| i.severity == Severity::Error)
+}
+
+// one issue per line, prefixed by the path of the file
+pub fn format_report(path: &str, issues: &[Issue]) -> String {
+    let lines: Vec<String> = issues
+        .iter()
+        .map(|issue| format!("{}:{}", path, issue))
+        .collect();
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // client 10 and server 20 linked through drones 1, 2 and 3
+    const VALID: &str = r#"
+[[drones]]
+id = 1
+connected_drone_ids = [2, 3, 10]
+pdr = 0.1
+
+[[drones]]
+id = 2
+connected_drone_ids = [1, 20]
+pdr = 0.1
+
+[[drones]]
+id = 3
+connected_drone_ids = [1, 20]
+pdr = 0.1
+
+[[clients]]
+id = 10
+connected_drone_ids = [1]
+
+[[servers]]
+id = 20
+connected_drone_ids = [2, 3]
+"#;
+
+    fn messages(content: &str) -> Vec<String> {
+        lint_str(content).into_iter().map(|i| i.message).collect()
+    }
+
+    #[test]
+    fn valid_config_has_no_issues() {
+        assert_eq!(lint_str(VALID), vec![]);
+    }
+
+    #[test]
+    fn issues_have_the_location_of_the_value() {
+        // pdr of drone 2
+        let content = VALID.replace(
+            "pdr = 0.1\n\n[[drones]]\nid = 3",
+            "pdr = 1.5\n\n[[drones]]\nid = 3",
+        );
+        let issues = lint_str(&content);
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].line, issues[0].column), (10, 7));
+        assert_eq!(issues[0].severity, Severity::Error);
+        assert!(has_errors(&issues));
+    }
+
+    #[test]
+    fn invalid_pdr_is_reported() {
+        for pdr in ["-0.1", "1.5", "nan", "inf"] {
+            let content = VALID.replacen("pdr = 0.1", &format!("pdr = {}", pdr), 1);
+            assert!(
+                messages(&content)
+                    .iter()
+                    .any(|m| m.contains("of drone 1 is not in [0,1]")),
+                "{}",
+                pdr
+            );
+        }
+        let content = VALID.replacen("pdr = 0.1\n", "", 1);
+        assert_eq!(messages(&content), vec!["drone 1 has no pdr"]);
+    }
+
+    #[test]
+    fn invalid_ids_are_reported() {
+        let content = VALID.replace("id = 3\n", "id = 2\n");
+        assert!(messages(&content).contains(&"id 2 is already used by a drone".to_string()));
+
+        let content = VALID.replace("id = 20\n", "id = 300\n");
+        assert!(messages(&content).contains(&"id 300 is out of range [0,255]".to_string()));
+
+        let content = VALID.replace("connected_drone_ids = [1]", "connected_drone_ids = [1, 7]");
+        assert!(messages(&content).contains(&"client 10 is linked to unknown node 7".to_string()));
+    }
+
+    #[test]
+    fn invalid_links_are_reported() {
+        let content = VALID.replace(
+            "connected_drone_ids = [1, 20]\npdr = 0.1\n\n[[drones]]\nid = 3",
+            "connected_drone_ids = [20]\npdr = 0.1\n\n[[drones]]\nid = 3",
+        );
+        assert!(messages(&content)
+            .contains(&"drone 1 is linked to drone 2, but not the other way round".to_string()));
+
+        let content = format!(
+            "{}\n[[clients]]\nid = 11\nconnected_drone_ids = [10]\n",
+            VALID
+        )
+        .replace("connected_drone_ids = [1]", "connected_drone_ids = [1, 11]");
+        let found = messages(&content);
+        assert!(found.contains(
+            &"client 10 is linked to client 11, only drones can be linked to clients and servers"
+                .to_string()
+        ));
+        assert!(found
+            .contains(&"client 11 is linked to 0 drones, it must be linked to 1 or 2".to_string()));
+    }
+
+    #[test]
+    fn servers_with_few_links_are_reported() {
+        let content = VALID
+            .replace("connected_drone_ids = [2, 3]", "connected_drone_ids = [2]")
+            .replace(
+                "connected_drone_ids = [1, 20]\npdr = 0.1\n\n[[clients]]",
+                "connected_drone_ids = [1]\npdr = 0.1\n\n[[clients]]",
+            );
+        assert_eq!(
+            messages(&content),
+            vec!["server 20 is linked to 1 drones, it must be linked to at least 2"]
+        );
+    }
+
+    #[test]
+    fn partitioned_network_is_an_error() {
+        // without drone 1 the client cannot reach the server
+        let content = VALID
+            .replace(
+                "connected_drone_ids = [2, 3, 10]",
+                "connected_drone_ids = [10]",
+            )
+            .replace(
+                "connected_drone_ids = [1, 20]",
+                "connected_drone_ids = [20]",
+            );
+        let issues = lint_str(&content);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].message, "client 10 cannot reach server 20");
+        assert!(has_errors(&issues));
+    }
+
+    #[test]
+    fn only_valid_links_are_used_for_the_reachability() {
+        // drone 1 lists drones 2 and 3, but they do not list it
+        let content = VALID.replace(
+            "connected_drone_ids = [1, 20]",
+            "connected_drone_ids = [20]",
+        );
+        assert!(messages(&content).contains(&"client 10 cannot reach server 20".to_string()));
+    }
+
+    #[test]
+    fn syntax_errors_are_reported() {
+        let issues = lint_str("[[drones]]\nid = \n");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, 2);
+        assert!(has_errors(&issues));
+    }
+}
@@ -1,49 +1,61 @@
 slint::include_modules!();
 use slint::{Model, ModelRc, VecModel, Weak};
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::OsString;
 use std::sync::{Arc, Mutex};
-use std::thread::{self, JoinHandle};
+use std::thread;
+use std::time::Duration;
 
-use crossbeam::channel::{Receiver, Sender, TryRecvError};
-use rfd::FileDialog;
+use crossbeam::channel::unbounded;
+use rfd::{FileDialog, MessageButtons, MessageDialog, MessageLevel};
 
-use network_initializer::channel::Channel;
-use network_initializer::{errors::ConfigError, NetworkInitializer};
-
-use wg_internal::controller::{DroneCommand, DroneEvent};
-use wg_internal::network::NodeId;
-use wg_internal::packet::{NackType, Packet, PacketType};
+use wg_internal::controller::DroneEvent;
+use wg_internal::packet::PacketType;
 
 use logger::{LogLevel, Logger};
 
+use simulation_controller::chaos::{ChaosEngine, ChaosOptions};
+use simulation_controller::command::{parse_command, Command};
+use simulation_controller::event_loop::{run_event_loop, EventHandler};
+use simulation_controller::generator::{generate, GeneratorOptions};
+use simulation_controller::group::{GroupAction, GroupTarget};
+use simulation_controller::headless::{self, HeadlessOptions};
+use simulation_controller::layout::{sidecar_path, Layout, LayoutMode};
+use simulation_controller::link::{Jitter, LinkProfile};
+use simulation_controller::lint::{format_report, has_errors, lint_config};
+use simulation_controller::pdr_profile::{PdrDriver, PdrProfile};
+use simulation_controller::replay::{ReplayControl, ReplayUpdate, Replayer, MAX_SPEED, MIN_SPEED};
+use simulation_controller::route::{record_route_key, route_key, Route, Routes};
+use simulation_controller::sampling::{replayed_message_kind, Sampler, SamplingPolicy};
+use simulation_controller::scenario::{read_scenario, Scheduler};
+use simulation_controller::stats::{
+    packet_endpoints, packet_kind, Counters, Statistics, PACKET_KINDS,
+};
+use simulation_controller::tap::SharedCapture;
+use simulation_controller::topology::{NodeType, Topology};
+use simulation_controller::trace::read_trace;
+use simulation_controller::{ConnectivityCheck, ControllerUpdate, SimulationController};
+
+mod cli;
 mod utils;
-use utils::{get_node_type, initiate_logger, run_simulation_thread, send_drone_command, NodeType};
-
-use network_initializer::parsed_nodes::{ParsedClient, ParsedDrone, ParsedServer};
+use cli::Args;
+use utils::{get_node_type, initiate_logger};
 
 const PATH: &str = "./config_files/star.toml";
+// how often the sampled messages are sent to the window
+const MESSAGES_INTERVAL: Duration = Duration::from_millis(50);
+// packets of a capture shown in the packet list, the export has all of them
+const CAPTURED_SHOWN: usize = 200;
 
 // NOTE: functions related to slint struct cannot be moved to other files
 
-// it checks it the edge is already present in the vector
-fn check_edges(edges: &Vec<EdgeStruct>, id1: i32, id2: i32) -> bool {
-    for edge in edges {
-        if (edge.id1 == id1 && edge.id2 == id2) || (edge.id1 == id2 && edge.id2 == id1) {
-            return true;
-        }
-    }
-    return false;
-}
-
-// it populates drones, clients and servers based on the parsed nodes from the network initializer
+// it populates drones, clients and servers based on the topology of the controller
 fn populate_all(
-    parsed_drones: &Vec<ParsedDrone>,
-    parsed_servers: &Vec<ParsedServer>,
-    parsed_clients: &Vec<ParsedClient>,
+    topology: &Topology,
     edges: &mut Vec<EdgeStruct>,
     id_to_type: &Arc<Mutex<HashMap<i32, (NodeType, i32)>>>,
+    layout: &Layout,
 ) -> (
     Vec<DroneStruct>,
     Vec<ClientServerStruct>,
@@ -54,133 +66,100 @@ fn populate_all(
     let mut servers: Vec<ClientServerStruct> = vec![];
 
     // populate id_to_type_pos with {id, (NodeType, position_in_vector)}
-    let mut i = 0;
-    for drone in parsed_drones {
-        id_to_type
-            .lock()
-            .unwrap()
-            .insert(drone.id as i32, (NodeType::Drone, i));
-        i = i + 1;
-    }
-    i = 0;
-    for client in parsed_clients {
-        id_to_type
-            .lock()
-            .unwrap()
-            .insert(client.id as i32, (NodeType::Client, i));
-        i = i + 1;
-    }
-    i = 0;
-    for server in parsed_servers {
-        id_to_type
-            .lock()
-            .unwrap()
-            .insert(server.id as i32, (NodeType::Server, i));
-        i = i + 1;
+    id_to_type.lock().unwrap().clear();
+    for node in topology.nodes() {
+        if let Some((node_type, position)) = topology.position(node.id) {
+            id_to_type
+                .lock()
+                .unwrap()
+                .insert(node.id as i32, (node_type, position as i32));
+        }
     }
 
-    // populate drones
-    i = 0;
-    for drone in parsed_drones {
-        let mut adjent = vec![];
-        for adj in &drone.connected_drone_ids {
-            adjent.push(*adj as i32);
-            if !check_edges(&edges, drone.id as i32, *adj as i32) {
-                let (node_type2_, index) = get_node_type(*adj as i32, id_to_type);
-                edges.push(EdgeStruct {
-                    id1: drone.id as i32,
-                    id2: *adj as i32,
-                    node_type1: 0,
-                    node_type2: node_type2_,
-                    index1: i,
-                    index2: index,
-                });
-            }
-        }
+    // populate edges
+    for (id1, id2) in topology.links() {
+        let (node_type1, index1) = get_node_type(id1 as i32, id_to_type);
+        let (node_type2, index2) = get_node_type(id2 as i32, id_to_type);
+        edges.push(EdgeStruct {
+            id1: id1 as i32,
+            id2: id2 as i32,
+            node_type1: node_type1,
+            node_type2: node_type2,
+            index1: index1,
+            index2: index2,
+            tapped: false,
+            heat: 0.0,
+        });
+    }
 
+    // drones that can still be linked to a node
+    let not_adjacent = |id: u8, adjent: &Vec<i32>| -> Vec<i32> {
         let mut not_adj = vec![];
-        for d in parsed_drones {
-            if (!adjent.contains(&(d.id as i32))) && d.id != drone.id {
+        for d in topology.drones() {
+            if !d.crashed && !adjent.contains(&(d.id as i32)) && d.id != id {
                 not_adj.push(d.id as i32);
             }
         }
+        not_adj
+    };
+
+    // populate drones
+    let mut i = 0;
+    for drone in topology.drones() {
+        let adjent: Vec<i32> = drone.neighbours.iter().map(|adj| *adj as i32).collect();
+        let not_adj = if drone.crashed {
+            vec![]
+        } else {
+            not_adjacent(drone.id, &adjent)
+        };
+        let (x, y) = layout.position(drone.id);
         drones.push(DroneStruct {
             adjent: slint::ModelRc::new(slint::VecModel::from(adjent)),
             not_adjacent: slint::ModelRc::new(slint::VecModel::from(not_adj)),
             id: drone.id as i32,
             pdr: drone.pdr,
-            crashed: false,
+            profile: "".into(),
+            crashed: drone.crashed,
             position_in_vector: i,
+            selected: false,
+            x,
+            y,
         });
         i = i + 1;
     }
 
     // populate clients
     i = 0;
-    for client in parsed_clients {
-        let mut adjent = vec![];
-        for adj in &client.connected_drone_ids {
-            adjent.push(*adj as i32);
-            if !check_edges(&edges, client.id as i32, *adj as i32) {
-                let (node_type2_, index) = get_node_type(*adj as i32, id_to_type);
-                edges.push(EdgeStruct {
-                    id1: client.id as i32,
-                    id2: *adj as i32,
-                    node_type1: 1,
-                    node_type2: node_type2_,
-                    index1: i,
-                    index2: index,
-                });
-            }
-        }
-
-        let mut not_adj = vec![];
-        for d in parsed_drones {
-            if !adjent.contains(&(d.id as i32)) && d.id != client.id {
-                not_adj.push(d.id as i32);
-            }
-        }
-
+    for client in topology.clients() {
+        let adjent: Vec<i32> = client.neighbours.iter().map(|adj| *adj as i32).collect();
+        let not_adj = not_adjacent(client.id, &adjent);
+        let (x, y) = layout.position(client.id);
         clients.push(ClientServerStruct {
             drones_adjacent: slint::ModelRc::new(slint::VecModel::from(adjent)),
             drones_not_adjacent: slint::ModelRc::new(slint::VecModel::from(not_adj)),
             id: client.id as i32,
             position_in_vector: i,
+            selected: false,
+            x,
+            y,
         });
         i = i + 1;
     }
 
     // populate servers
     i = 0;
-    for server in parsed_servers {
-        let mut adjent = vec![];
-        for adj in &server.connected_drone_ids {
-            adjent.push(*adj as i32);
-            if !check_edges(&edges, server.id as i32, *adj as i32) {
-                let (node_type2_, index) = get_node_type(*adj as i32, id_to_type);
-                edges.push(EdgeStruct {
-                    id1: server.id as i32,
-                    id2: *adj as i32,
-                    node_type1: 2,
-                    node_type2: node_type2_,
-                    index1: i,
-                    index2: index,
-                });
-            }
-        }
-
-        let mut not_adj = vec![];
-        for d in parsed_drones {
-            if !adjent.contains(&(d.id as i32)) && d.id != server.id {
-                not_adj.push(d.id as i32);
-            }
-        }
-
+    for server in topology.servers() {
+        let adjent: Vec<i32> = server.neighbours.iter().map(|adj| *adj as i32).collect();
+        let not_adj = not_adjacent(server.id, &adjent);
+        let (x, y) = layout.position(server.id);
         servers.push(ClientServerStruct {
             drones_adjacent: slint::ModelRc::new(slint::VecModel::from(adjent)),
             drones_not_adjacent: slint::ModelRc::new(slint::VecModel::from(not_adj)),
             id: server.id as i32,
             position_in_vector: i,
+            selected: false,
+            x,
+            y,
         });
         i = i + 1;
     }
@@ -188,52 +167,704 @@ fn populate_all(
     return (drones, clients, servers);
 }
 
-// handle the message to be sent to the slint window
-fn send_message(
-    weak: &Weak<Window>,
-    logger_: &Arc<Mutex<Logger>>,
-    packet: Packet,
-    id_to_type_pos1: Arc<Mutex<HashMap<i32, (NodeType, i32)>>>,
-    packet_dropped: bool,
-    type_msg: i32,
+// replace the models of the window with the given topology
+fn show_topology(
+    window: &Window,
+    topology: &Topology,
+    id_to_type_pos: &Arc<Mutex<HashMap<i32, (NodeType, i32)>>>,
+    layout: &Arc<Mutex<Layout>>,
+) {
+    // the layout runs again only if nodes or links changed
+    let mut layout = layout.lock().unwrap();
+    layout.update(topology);
+
+    let mut edges: Vec<EdgeStruct> = vec![];
+    let (mut drones, clients, servers) =
+        populate_all(topology, &mut edges, id_to_type_pos, &layout);
+
+    // drones driven by a pdr profile show their current pdr
+    let profiles = window.get_pdr_profiles();
+    for drone in drones.iter_mut() {
+        drone.profile = profiles
+            .iter()
+            .find(|p| p.id == drone.id)
+            .map(|p| p.spec)
+            .unwrap_or_default();
+    }
+
+    // tapped links are highlighted, the heatmap keeps its values until the next refresh
+    let taps = window.get_taps();
+    let previous = window.get_edges();
+    for edge in edges.iter_mut() {
+        edge.tapped = taps.iter().any(|t| {
+            t.active && (t.id1, t.id2) == (edge.id1.min(edge.id2), edge.id1.max(edge.id2))
+        });
+        edge.heat = previous
+            .iter()
+            .find(|e| (e.id1, e.id2) == (edge.id1, edge.id2))
+            .map_or(0.0, |e| e.heat);
+    }
+
+    window.set_edges(slint::ModelRc::new(slint::VecModel::from(edges)));
+    window.set_clients(slint::ModelRc::new(slint::VecModel::from(clients)));
+    window.set_drones(slint::ModelRc::new(slint::VecModel::from(drones)));
+    window.set_servers(slint::ModelRc::new(slint::VecModel::from(servers)));
+
+    // removed nodes leave the selection
+    let selection: Vec<i32> = window
+        .get_selection()
+        .iter()
+        .filter(|id| topology.get(*id as u8).is_some())
+        .collect();
+    set_selection(window, selection);
+    show_route(window, id_to_type_pos);
+}
+
+// highlight a route (None removes the highlight), it is kept until dismissed
+fn set_route(
+    window: &Window,
+    route: Option<&Route>,
+    id_to_type_pos: &Arc<Mutex<HashMap<i32, (NodeType, i32)>>>,
+) {
+    match route {
+        Some(route) => {
+            let hops: Vec<i32> = route.hops.iter().map(|id| *id as i32).collect();
+            window.set_route_key(route.key().into());
+            window.set_route_text(route.to_string().into());
+            window.set_route_hops(ModelRc::new(VecModel::from(hops)));
+            window.set_route_hop(route.hop_index as i32);
+            window.set_route_dropped(route.dropped);
+        }
+        None => {
+            window.set_route_key("".into());
+            window.set_route_text("".into());
+            window.set_route_hops(ModelRc::new(VecModel::from(vec![])));
+        }
+    }
+    show_route(window, id_to_type_pos);
+}
+
+// segments and marker of the highlighted route, the hops not in the topology are skipped
+fn show_route(window: &Window, id_to_type_pos: &Arc<Mutex<HashMap<i32, (NodeType, i32)>>>) {
+    let current = window.get_route_hop() as usize;
+    let nodes: Vec<(i32, i32)> = window
+        .get_route_hops()
+        .iter()
+        .map(|id| get_node_type(id, id_to_type_pos))
+        .collect();
+
+    let mut segments = vec![];
+    for (i, pair) in nodes.windows(2).enumerate() {
+        let ((node_type1, index1), (node_type2, index2)) = (pair[0], pair[1]);
+        if node_type1 == -1 || node_type2 == -1 {
+            continue;
+        }
+        segments.push(RouteSegmentStruct {
+            node_type1,
+            index1,
+            node_type2,
+            index2,
+            traversed: i < current,
+        });
+    }
+    let (marker_type, marker_index) = nodes.get(current).copied().unwrap_or((-1, -1));
+
+    window.set_route_segments(ModelRc::new(VecModel::from(segments)));
+    window.set_route_marker_type(marker_type);
+    window.set_route_marker_index(marker_index);
+}
+
+// list of the recent routes, the highlighted one follows the last packet of its session
+fn refresh_routes(
+    window: &Window,
+    routes: &Routes,
+    id_to_type_pos: &Arc<Mutex<HashMap<i32, (NodeType, i32)>>>,
+) {
+    let list: Vec<RouteStruct> = routes
+        .recent()
+        .map(|r| RouteStruct {
+            key: r.key().into(),
+            label: r.to_string().into(),
+        })
+        .collect();
+    window.set_routes(ModelRc::new(VecModel::from(list)));
+
+    let key = window.get_route_key();
+    if let Some(route) = routes.get(&key) {
+        set_route(window, Some(route), id_to_type_pos);
+    }
+}
+
+// mark the selected nodes, the order of the selection is kept (e.g. for a chain)
+fn set_selection(window: &Window, selection: Vec<i32>) {
+    let text: Vec<String> = selection.iter().map(|id| id.to_string()).collect();
+    window.set_selection_text(text.join(", ").into());
+
+    let drones = window.get_drones();
+    for i in 0..drones.row_count() {
+        let mut drone = drones.row_data(i).unwrap();
+        if drone.selected != selection.contains(&drone.id) {
+            drone.selected = !drone.selected;
+            drones.set_row_data(i, drone);
+        }
+    }
+    for nodes in [window.get_clients(), window.get_servers()] {
+        for i in 0..nodes.row_count() {
+            let mut node = nodes.row_data(i).unwrap();
+            if node.selected != selection.contains(&node.id) {
+                node.selected = !node.selected;
+                nodes.set_row_data(i, node);
+            }
+        }
+    }
+    window.set_selection(ModelRc::new(VecModel::from(selection)));
+}
+
+// move the nodes of the window to the positions of the layout
+fn place_nodes(window: &Window, layout: &Layout) {
+    let drones = window.get_drones();
+    for i in 0..drones.row_count() {
+        let mut drone = drones.row_data(i).unwrap();
+        (drone.x, drone.y) = layout.position(drone.id as u8);
+        drones.set_row_data(i, drone);
+    }
+    for nodes in [window.get_clients(), window.get_servers()] {
+        for i in 0..nodes.row_count() {
+            let mut node = nodes.row_data(i).unwrap();
+            (node.x, node.y) = layout.position(node.id as u8);
+            nodes.set_row_data(i, node);
+        }
+    }
+}
+
+// zoom and center of the viewport that show every node
+fn fit_view(window: &Window) {
+    let mut points: Vec<(f32, f32)> = window.get_drones().iter().map(|d| (d.x, d.y)).collect();
+    for nodes in [window.get_clients(), window.get_servers()] {
+        points.extend(nodes.iter().map(|n| (n.x, n.y)));
+    }
+    if points.is_empty() {
+        window.set_view_zoom(1.0);
+        window.set_view_x(0.5);
+        window.set_view_y(0.5);
+        return;
+    }
+
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for (x, y) in points {
+        (min_x, max_x) = (min_x.min(x), max_x.max(x));
+        (min_y, max_y) = (min_y.min(y), max_y.max(y));
+    }
+    // a margin for the size of the nodes
+    let width = max_x - min_x + 0.1;
+    let height = max_y - min_y + 0.1;
+    window.set_view_zoom((1.0 / width).min(1.0 / height).clamp(0.25, 8.0));
+    window.set_view_x((min_x + max_x) / 2.0);
+    window.set_view_y((min_y + max_y) / 2.0);
+}
+
+// restore the layout kept next to a configuration file
+fn open_layout(
+    window: &Window,
+    logger: &Arc<Mutex<Logger>>,
+    layout: &Arc<Mutex<Layout>>,
+    path: &str,
 ) {
-    // select sender and receiver
-    let id1;
-    let id2;
-
-    if packet_dropped {
-        // if packet dropped, the sender and receiver are swapped
-        id1 = packet.routing_header.hops[packet.routing_header.hop_index] as i32;
-        id2 = packet.routing_header.hops[packet.routing_header.hop_index - 1] as i32;
+    let mut layout = layout.lock().unwrap();
+    if let Err(e) = layout.open(path) {
+        logger
+            .lock()
+            .unwrap()
+            .log_warn(&format!("Cannot restore the layout: {}", e));
+    }
+    window.set_layout_mode(layout.mode().index());
+}
+
+// nodes whose center is inside the rectangle, in the coordinates of the layout
+fn nodes_in_rect(window: &Window, x1: f32, y1: f32, x2: f32, y2: f32) -> Vec<i32> {
+    let inside = |x: f32, y: f32| (x1..=x2).contains(&x) && (y1..=y2).contains(&y);
+    let mut ids: Vec<i32> = window
+        .get_drones()
+        .iter()
+        .filter(|d| inside(d.x, d.y))
+        .map(|d| d.id)
+        .collect();
+    for nodes in [window.get_clients(), window.get_servers()] {
+        ids.extend(nodes.iter().filter(|n| inside(n.x, n.y)).map(|n| n.id));
+    }
+    ids
+}
+
+// named groups of the configuration shown in the group panel
+fn groups_model(groups: &BTreeMap<String, Vec<u8>>) -> ModelRc<GroupStruct> {
+    let groups: Vec<GroupStruct> = groups
+        .iter()
+        .map(|(name, ids)| {
+            let ids: Vec<i32> = ids.iter().map(|id| *id as i32).collect();
+            GroupStruct {
+                name: name.into(),
+                members: ModelRc::new(VecModel::from(ids)),
+            }
+        })
+        .collect();
+    ModelRc::new(VecModel::from(groups))
+}
+
+// statistics of a node and of its outgoing edges
+fn node_stats(statistics: &Statistics, id: u8) -> (StatsStruct, Vec<EdgeStatsStruct>) {
+    let mut stats = StatsStruct {
+        sent: slint::ModelRc::new(slint::VecModel::from(vec![0; PACKET_KINDS])),
+        dropped: slint::ModelRc::new(slint::VecModel::from(vec![0; PACKET_KINDS])),
+        shortcut: slint::ModelRc::new(slint::VecModel::from(vec![0; PACKET_KINDS])),
+        total_sent: 0,
+        total_dropped: 0,
+        total_shortcut: 0,
+        sent_rate: 0.0,
+        dropped_rate: 0.0,
+    };
+    if let Some(counters) = statistics.node(id) {
+        let to_model = |c: &[u64; PACKET_KINDS]| {
+            let values: Vec<i32> = c.iter().map(|v| *v as i32).collect();
+            slint::ModelRc::new(slint::VecModel::from(values))
+        };
+        stats = StatsStruct {
+            sent: to_model(&counters.sent),
+            dropped: to_model(&counters.dropped),
+            shortcut: to_model(&counters.shortcut),
+            total_sent: counters.total_sent() as i32,
+            total_dropped: counters.total_dropped() as i32,
+            total_shortcut: counters.total_shortcut() as i32,
+            sent_rate: statistics.sent_rate(counters),
+            dropped_rate: statistics.dropped_rate(counters),
+        };
+    }
+
+    let mut edge_stats = vec![];
+    for (to, counters) in statistics.outgoing(id) {
+        edge_stats.push(EdgeStatsStruct {
+            to: to as i32,
+            sent: counters.total_sent() as i32,
+            dropped: counters.total_dropped() as i32,
+            sent_rate: statistics.sent_rate(counters),
+            dropped_rate: statistics.dropped_rate(counters),
+        });
+    }
+    (stats, edge_stats)
+}
+
+// policy of the animated events: [sampling] table of the configuration file, then command line
+fn sampling_policy(
+    logger: &Arc<Mutex<Logger>>,
+    config: Option<&str>,
+    spec: &Option<String>,
+) -> SamplingPolicy {
+    let mut policy = SamplingPolicy::default();
+    if let Some(path) = config {
+        match SamplingPolicy::from_config_file(path) {
+            Ok(Some(p)) => policy = p,
+            Ok(None) => {}
+            Err(e) => logger
+                .lock()
+                .unwrap()
+                .log_warn(&format!("Sampling policy not read: {}", e)),
+        }
+    }
+    if let Some(spec) = spec {
+        // already validated while parsing the arguments
+        let _ = policy.apply_spec(spec);
+    }
+    policy
+}
+
+fn sampling_struct(policy: &SamplingPolicy) -> SamplingStruct {
+    let join = |values: Vec<String>| values.join(" ");
+    SamplingStruct {
+        msg_fragment: policy.msg_fragment as i32,
+        ack: policy.ack as i32,
+        nack: policy.nack as i32,
+        flood_request: policy.flood_request as i32,
+        flood_response: policy.flood_response as i32,
+        dropped: policy.dropped as i32,
+        shortcut: policy.shortcut as i32,
+        edge_rate: policy.edge_rate as i32,
+        always_nodes: join(
+            policy
+                .always_nodes
+                .iter()
+                .map(|id| id.to_string())
+                .collect(),
+        )
+        .into(),
+        always_sessions: join(
+            policy
+                .always_sessions
+                .iter()
+                .map(|id| id.to_string())
+                .collect(),
+        )
+        .into(),
+    }
+}
+
+// change the sampling policy with the spec built by the sampling panel
+fn apply_sampling(window: &Window, sampler: &Arc<Mutex<Sampler>>, spec: &str) {
+    let mut sampler = sampler.lock().unwrap();
+    let mut policy = sampler.policy().clone();
+    match policy.apply_spec(spec) {
+        Ok(_) => {
+            sampler.set_policy(policy);
+            window.set_status_message("".into());
+        }
+        Err(e) => window.set_status_message(format!("Sampling: {}", e).into()),
+    }
+    window.set_sampling(sampling_struct(sampler.policy()));
+}
+
+// check the configuration file before loading it, the problems found are shown in a dialog;
+// false if the file has errors and must not be loaded
+fn check_config(logger: &Arc<Mutex<Logger>>, path: &str) -> bool {
+    let issues = match lint_config(path) {
+        Ok(issues) => issues,
+        Err(_) => return true, // the error is reported by the network initializer
+    };
+    if issues.is_empty() {
+        return true;
+    }
+
+    let report = format_report(path, &issues);
+    let errors = has_errors(&issues);
+    if errors {
+        logger.lock().unwrap().log_error(&report);
     } else {
-        match packet.pack_type {
-            PacketType::FloodRequest(flood) => {
-                // in case of FloodRequest, sender and receiver are taken from the path_trace
-                id1 = flood.path_trace.get(flood.path_trace.len() - 2).unwrap().0 as i32;
-                id2 = flood.path_trace.get(flood.path_trace.len() - 1).unwrap().0 as i32;
+        logger.lock().unwrap().log_warn(&report);
+    }
+    MessageDialog::new()
+        .set_level(if errors {
+            MessageLevel::Error
+        } else {
+            MessageLevel::Warning
+        })
+        .set_title(if errors {
+            "The configuration cannot be loaded"
+        } else {
+            "Configuration warnings"
+        })
+        .set_description(report)
+        .set_buttons(MessageButtons::Ok)
+        .show();
+    !errors
+}
+
+// start the timed actions of the scenario file, the button is reset when the scenario ends
+fn start_scenario(
+    controller: &Arc<Mutex<SimulationController>>,
+    logger: &Arc<Mutex<Logger>>,
+    weak: Weak<Window>,
+    path: &str,
+) -> Result<Scheduler, String> {
+    let steps = read_scenario(path)?;
+    logger.lock().unwrap().log_info(&format!(
+        "[SCENARIO] Starting {} ({} actions)",
+        path,
+        steps.len()
+    ));
+    if let Some(window) = weak.upgrade() {
+        window.set_scenario_running(true);
+    }
+    Ok(Scheduler::start(
+        controller.clone(),
+        logger.clone(),
+        steps,
+        move || {
+            let _ = weak.upgrade_in_event_loop(|window| window.set_scenario_running(false));
+        },
+    ))
+}
+
+// stop the running scenario (if any), its steps refer to the nodes of the current configuration
+fn stop_scenario(scheduler: &Arc<Mutex<Option<Scheduler>>>) {
+    if let Some(running) = scheduler.lock().unwrap().take() {
+        running.stop();
+    }
+}
+
+// lint mode: print the problems of the configuration file, the exit code is 1 if there are errors
+fn run_lint(path: &str) -> i32 {
+    match lint_config(path) {
+        Ok(issues) if issues.is_empty() => {
+            println!("{}: no problems found", path);
+            0
+        }
+        Ok(issues) => {
+            println!("{}", format_report(path, &issues));
+            if has_errors(&issues) {
+                1
+            } else {
+                0
             }
-            _ => {
-                id1 = packet.routing_header.hops[packet.routing_header.hop_index - 1] as i32;
-                id2 = packet.routing_header.hops[packet.routing_header.hop_index] as i32;
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+// value of each edge for the heatmap mode of the window, scaled to [0,1]
+fn refresh_heatmap(window: &Window, statistics: &Statistics) {
+    let mode = window.get_heatmap_mode();
+    if mode == 0 {
+        return;
+    }
+    let edges = window.get_edges();
+    let drones = window.get_drones();
+    let pdr = |node_type: i32, index: i32| -> f32 {
+        match node_type {
+            0 => drones.row_data(index as usize).map_or(0.0, |d| d.pdr),
+            _ => 0.0,
+        }
+    };
+    // both directions of the link
+    let sum = |edge: &EdgeStruct, rate: &dyn Fn(&Counters) -> f32| {
+        [(edge.id1, edge.id2), (edge.id2, edge.id1)]
+            .iter()
+            .filter_map(|(from, to)| statistics.edge(*from as u8, *to as u8))
+            .map(rate)
+            .sum::<f32>()
+    };
+
+    let values: Vec<f32> = edges
+        .iter()
+        .map(|edge| match mode {
+            1 => pdr(edge.node_type1, edge.index1).max(pdr(edge.node_type2, edge.index2)),
+            2 => {
+                let sent = sum(&edge, &|c| statistics.sent_rate(c));
+                let dropped = sum(&edge, &|c| statistics.dropped_rate(c));
+                if sent > 0.0 {
+                    (dropped / sent).min(1.0)
+                } else {
+                    0.0
+                }
             }
+            3 => sum(&edge, &|c| statistics.sent_rate(c)),
+            _ => sum(&edge, &|c| statistics.fragment_rate(c)),
+        })
+        .collect();
+
+    // ratios are absolute, rates are relative to the busiest link
+    let (scale, label) = match mode {
+        1 | 2 => (1.0, "100%".to_string()),
+        _ => {
+            let max = values.iter().copied().fold(0.0, f32::max);
+            (max, format!("{:.1}/s", max))
+        }
+    };
+    for (i, value) in values.iter().enumerate() {
+        let heat = if scale > 0.0 { value / scale } else { 0.0 };
+        let mut edge = edges.row_data(i).unwrap();
+        if edge.heat != heat {
+            edge.heat = heat;
+            edges.set_row_data(i, edge);
         }
     }
+    window.set_heatmap_max(label.into());
+}
 
-    let logger_int = logger_.clone();
-    match weak.upgrade_in_event_loop(move |window| {
-        let messages: ModelRc<MessageStruct> = window.get_messages();
-        let drones = window.get_drones();
+// show the statistics of the selected drone and of the selected client or server
+fn refresh_stats(window: &Window, statistics: &Statistics) {
+    refresh_heatmap(window, statistics);
+
+    let (stats, edge_stats) = node_stats(statistics, window.get_id_selected_drone() as u8);
+    window.set_drone_stats(stats);
+    window.set_drone_edge_stats(slint::ModelRc::new(slint::VecModel::from(edge_stats)));
+
+    let (stats, edge_stats) = node_stats(statistics, window.get_id_selected_client_server() as u8);
+    window.set_client_server_stats(stats);
+    window.set_client_server_edge_stats(slint::ModelRc::new(slint::VecModel::from(edge_stats)));
+}
 
-        let (ns1, index1) = get_node_type(id1, &id_to_type_pos1);
-        let (ns2, index2) = get_node_type(id2, &id_to_type_pos1);
+// emulated directions of the links shown in the link panel
+fn link_profiles_model(profiles: &[(u8, u8, LinkProfile)]) -> ModelRc<LinkProfileStruct> {
+    let profiles: Vec<LinkProfileStruct> = profiles
+        .iter()
+        .map(|(from, to, profile)| LinkProfileStruct {
+            id1: *from as i32,
+            id2: *to as i32,
+            delay: profile.delay.as_secs_f32() * 1000.0,
+            jitter: profile.jitter.as_secs_f32() * 1000.0,
+            distribution: match profile.distribution {
+                Jitter::Uniform => 0,
+                Jitter::Normal => 1,
+            },
+            bandwidth: profile.bandwidth as f32 / 1000.0,
+        })
+        .collect();
+    ModelRc::new(VecModel::from(profiles))
+}
+
+// captured links and the most recent packets of the selected capture
+fn show_captures(window: &Window, captures: &[(u8, u8, bool, SharedCapture)]) {
+    let selected = (window.get_capture_id1(), window.get_capture_id2());
+    let mut taps: Vec<TapStruct> = vec![];
+    let mut packets: Vec<CapturedPacketStruct> = vec![];
+
+    for (id1, id2, active, capture) in captures {
+        let capture = capture.lock().unwrap();
+        taps.push(TapStruct {
+            id1: *id1 as i32,
+            id2: *id2 as i32,
+            active: *active,
+            total: capture.total() as i32,
+        });
+        if (*id1 as i32, *id2 as i32) != selected {
+            continue;
+        }
+        let kept = capture.packets().count();
+        for p in capture.packets().skip(kept.saturating_sub(CAPTURED_SHOWN)) {
+            let hops: Vec<String> = p
+                .hops
+                .iter()
+                .enumerate()
+                .map(|(i, id)| {
+                    if i == p.hop_index {
+                        format!("[{}]", id)
+                    } else {
+                        id.to_string()
+                    }
+                })
+                .collect();
+            packets.push(CapturedPacketStruct {
+                t: p.t as f32 / 1000.0,
+                from: p.from as i32,
+                to: p.to as i32,
+                session_id: p.session_id.to_string().into(),
+                packet_type: match p.nack_type {
+                    Some(ref nack_type) => format!("{} {}", p.packet_type, nack_type),
+                    None => p.packet_type.clone(),
+                }
+                .into(),
+                fragment: match (p.fragment_index, p.total_n_fragments) {
+                    (Some(index), Some(total)) => format!("{}/{}", index, total),
+                    (Some(index), None) => index.to_string(),
+                    _ => String::new(),
+                }
+                .into(),
+                hops: hops.join(" ").into(),
+                size: p.size as i32,
+            });
+        }
+    }
+    window.set_taps(ModelRc::new(VecModel::from(taps)));
+    window.set_captured_packets(ModelRc::new(VecModel::from(packets)));
+}
+
+// drones driven by a pdr profile, with the spec shown in the right menu
+fn pdr_profiles_model(profiles: &[(u8, PdrProfile)]) -> ModelRc<PdrProfileStruct> {
+    let profiles: Vec<PdrProfileStruct> = profiles
+        .iter()
+        .map(|(id, profile)| PdrProfileStruct {
+            id: *id as i32,
+            spec: profile.to_string().into(),
+        })
+        .collect();
+    ModelRc::new(VecModel::from(profiles))
+}
+
+// state of the controller shown by the window, taken under a single lock
+struct ControllerView {
+    topology: Topology,
+    link_profiles: Vec<(u8, u8, LinkProfile)>,
+    captures: Vec<(u8, u8, bool, SharedCapture)>,
+    pdr_profiles: Vec<(u8, PdrProfile)>,
+    groups: BTreeMap<String, Vec<u8>>,
+}
 
-        if ns1 == -1 || ns2 == -1 {
-            logger_int
+impl ControllerView {
+    fn of(controller: &SimulationController) -> ControllerView {
+        ControllerView {
+            topology: controller.topology().clone(),
+            link_profiles: controller.link_profiles(),
+            captures: controller.captures(),
+            pdr_profiles: controller.pdr_profiles(),
+            groups: controller.groups().clone(),
+        }
+    }
+}
+
+// handle the update of the topology sent by the controller
+fn update_view(
+    weak: &Weak<Window>,
+    logger_: &Arc<Mutex<Logger>>,
+    update: ControllerUpdate,
+    view: ControllerView,
+    id_to_type_pos: Arc<Mutex<HashMap<i32, (NodeType, i32)>>>,
+    layout: Arc<Mutex<Layout>>,
+) {
+    match weak.upgrade_in_event_loop(move |window| {
+        show_captures(&window, &view.captures);
+        window.set_pdr_profiles(pdr_profiles_model(&view.pdr_profiles));
+        show_topology(&window, &view.topology, &id_to_type_pos, &layout);
+        window.set_link_profiles(link_profiles_model(&view.link_profiles));
+        window.set_groups(groups_model(&view.groups));
+        match update {
+            ControllerUpdate::TopologyLoaded => {
+                window.set_messages(slint::ModelRc::new(slint::VecModel::from(vec![])));
+                set_route(&window, None, &id_to_type_pos);
+                window.set_status_message("".into());
+            }
+            ControllerUpdate::Warning(reason) => {
+                window.set_status_message(reason.into());
+            }
+            _ => {}
+        }
+    }) {
+        Ok(_) => {
+            logger_.lock().unwrap().log_debug("Topology sent to window");
+        }
+        Err(e) => {
+            logger_
                 .lock()
                 .unwrap()
-                .log_error(&format!("Error in getting node type"));
-        } else {
-            let message = MessageStruct {
+                .log_error(&format!("Error sending topology to window: {}", e));
+        }
+    }
+}
+
+// add the animations of the messages (id1, id2, msg_type) to the slint window
+fn show_messages(
+    weak: &Weak<Window>,
+    logger_: &Arc<Mutex<Logger>>,
+    new_messages: Vec<(i32, i32, i32, String)>,
+    id_to_type_pos1: Arc<Mutex<HashMap<i32, (NodeType, i32)>>>,
+) {
+    let logger_int = logger_.clone();
+    match weak.upgrade_in_event_loop(move |window| {
+        let messages: ModelRc<MessageStruct> = window.get_messages();
+        let vec_model = match messages.as_any().downcast_ref::<VecModel<MessageStruct>>() {
+            Some(_) => messages.clone(),
+            None => {
+                let model = slint::ModelRc::new(slint::VecModel::<MessageStruct>::default());
+                window.set_messages(model.clone());
+                model
+            }
+        };
+        let vec_model = vec_model
+            .as_any()
+            .downcast_ref::<VecModel<MessageStruct>>()
+            .unwrap();
+
+        for (id1, id2, type_msg, route) in new_messages {
+            let (ns1, index1) = get_node_type(id1, &id_to_type_pos1);
+            let (ns2, index2) = get_node_type(id2, &id_to_type_pos1);
+
+            if ns1 == -1 || ns2 == -1 {
+                logger_int
+                    .lock()
+                    .unwrap()
+                    .log_error(&format!("Error in getting node type"));
+                continue;
+            }
+            vec_model.push(MessageStruct {
                 id1: id1,
                 id2: id2,
                 msg_type: type_msg,
@@ -241,429 +872,563 @@ fn send_message(
                 node_type2: ns2,
                 index1: index1,
                 index2: index2,
-            };
-            if let Some(vec_model) = messages.as_any().downcast_ref::<VecModel<MessageStruct>>() {
-                vec_model.push(message);
-            } else {
-                window.set_messages(slint::ModelRc::new(slint::VecModel::from(vec![message])));
-            }
+                route: route.into(),
+            });
         }
     }) {
         Ok(_) => {
-            logger_.lock().unwrap().log_debug("Message sent to window");
+            logger_.lock().unwrap().log_debug("Messages sent to window");
         }
         Err(e) => {
             logger_
                 .lock()
                 .unwrap()
-                .log_error(&format!("Error sending message to window: {}", e));
+                .log_error(&format!("Error sending messages to window: {}", e));
         }
     }
 }
 
-fn main() -> Result<(), slint::PlatformError> {
-    // initiate logger
-    let logger = initiate_logger(LogLevel::Info);
+// receives the updates of the controller and the DroneEvent for the live view
+struct ViewEvents {
+    weak: Weak<Window>,
+    logger: Arc<Mutex<Logger>>,
+    controller: Arc<Mutex<SimulationController>>,
+    id_to_type_pos: Arc<Mutex<HashMap<i32, (NodeType, i32)>>>,
+    layout: Arc<Mutex<Layout>>,
+    statistics: Arc<Mutex<Statistics>>,
+    sampler: Arc<Mutex<Sampler>>,
+    routes: Arc<Mutex<Routes>>,
+    pending: Vec<(i32, i32, i32, String)>, // messages sent to the window at the next tick
+}
 
-    // initiate slint window
-    let main_window = Window::new()?;
-    let window = main_window.window();
-    window.set_fullscreen(true);
+impl EventHandler for ViewEvents {
+    fn on_update(&mut self, update: ControllerUpdate) {
+        if update == ControllerUpdate::TopologyLoaded {
+            self.statistics.lock().unwrap().reset();
+            self.sampler.lock().unwrap().reset();
+            self.routes.lock().unwrap().reset();
+            self.pending.clear();
+        }
+        let view = ControllerView::of(&self.controller.lock().unwrap());
+        update_view(
+            &self.weak,
+            &self.logger,
+            update,
+            view,
+            self.id_to_type_pos.clone(),
+            self.layout.clone(),
+        );
+    }
 
-    // initial configuration -> default
-    let network_initializer: Arc<Mutex<Result<NetworkInitializer, ConfigError>>> =
-        Arc::new(Mutex::new(NetworkInitializer::new(Some(PATH))));
-    let mut sc_receiver: Arc<Mutex<Option<Receiver<DroneEvent>>>> = Arc::new(Mutex::new(None));
-    let mut sc_senders: Arc<Mutex<Option<HashMap<NodeId, Sender<DroneCommand>>>>> =
-        Arc::new(Mutex::new(None));
-    let mut channels: Arc<Mutex<Option<HashMap<NodeId, Channel<Packet>>>>> =
-        Arc::new(Mutex::new(None));
-    let id_to_type_pos: Arc<Mutex<HashMap<i32, (NodeType, i32)>>> =
-        Arc::new(Mutex::new(HashMap::new())); // {id, (NodeType, position_in_vector)}
+    fn on_event(&mut self, event: DroneEvent) {
+        self.logger
+            .lock()
+            .unwrap()
+            .log_debug(&format!("Event received {:?}", event));
+        self.statistics.lock().unwrap().record(&event);
+        self.routes.lock().unwrap().record(&event);
+
+        // shortcuts are delivered by the controller (FloodRequest are not)
+        if let DroneEvent::ControllerShortcut(ref packet) = event {
+            if !matches!(packet.pack_type, PacketType::FloodRequest(_)) {
+                self.controller.lock().unwrap().forward_shortcut(packet);
+            }
+        }
 
-    if let Ok(ref mut c) = *network_initializer.lock().unwrap() {
-        sc_receiver = Arc::new(Mutex::new(Some((*c).get_controller_recv())));
-        sc_senders = Arc::new(Mutex::new(Some((*c).get_controller_senders())));
-        channels = Arc::new(Mutex::new(Some((*c).get_channels())));
+        // only the events chosen by the sampling policy are animated
+        let kind = self.sampler.lock().unwrap().sample(&event);
+        let (packet, packet_dropped) = match event {
+            DroneEvent::PacketDropped(ref packet) => (packet, true),
+            DroneEvent::PacketSent(ref packet) | DroneEvent::ControllerShortcut(ref packet) => {
+                (packet, false)
+            }
+        };
+        if let (Some(kind), Some((id1, id2))) = (kind, packet_endpoints(packet, packet_dropped)) {
+            let route = route_key(packet.session_id, packet_kind(&packet.pack_type));
+            self.pending
+                .push((id1 as i32, id2 as i32, kind as i32, route));
+        }
+    }
 
-        let nodes = c.get_nodes();
+    // the sampled messages are sent to the window in batches
+    fn on_tick(&mut self) {
+        if !self.pending.is_empty() {
+            let messages = std::mem::take(&mut self.pending);
+            show_messages(
+                &self.weak,
+                &self.logger,
+                messages,
+                self.id_to_type_pos.clone(),
+            );
+        }
+    }
+}
 
-        let mut edges: Vec<EdgeStruct> = vec![];
-        let (drones, clients, servers) =
-            populate_all(&nodes.0, &nodes.2, &nodes.1, &mut edges, &id_to_type_pos);
+// handles of the window shared by the live and the replay mode
+struct ViewState {
+    id_to_type_pos: Arc<Mutex<HashMap<i32, (NodeType, i32)>>>,
+    layout: Arc<Mutex<Layout>>,
+    statistics: Arc<Mutex<Statistics>>,
+    sampler: Arc<Mutex<Sampler>>,
+    routes: Arc<Mutex<Routes>>,
+}
 
-        let weak = main_window.as_weak();
+// callbacks of the view that do not depend on the source of the events, refresh is called after
+// every periodic refresh of the statistics and of the routes
+fn register_view_callbacks(
+    main_window: &Window,
+    logger: &Arc<Mutex<Logger>>,
+    view: &ViewState,
+    refresh: impl Fn(&Window) + 'static,
+) {
+    let weak = main_window.as_weak();
+    let sampler_ = view.sampler.clone();
+    // ON APPLY SAMPLING : handler for the changes of the sampling policy
+    main_window.on_apply_sampling(move |spec| {
         if let Some(window) = weak.upgrade() {
-            window.set_edges(slint::ModelRc::new(slint::VecModel::from(edges)));
-            window.set_clients(slint::ModelRc::new(slint::VecModel::from(clients)));
-            window.set_drones(slint::ModelRc::new(slint::VecModel::from(drones)));
-            window.set_servers(slint::ModelRc::new(slint::VecModel::from(servers)));
+            apply_sampling(&window, &sampler_, &spec);
         }
-    } else {
-        (*logger)
-            .lock()
-            .unwrap()
-            .log_error(&format!("Error in loading the configuration file"));
-    }
+    });
 
-    // thread for running the simulation (joined at the end)
-    let run_sim_thread_handler = run_simulation_thread(logger.clone(), network_initializer.clone());
+    let weak = main_window.as_weak();
+    let statistics_ = view.statistics.clone();
+    let routes_ = view.routes.clone();
+    let id_to_type_pos_ = view.id_to_type_pos.clone();
+    // ON REFRESH STATS : handler for the periodic refresh of the statistics of the selected nodes
+    // and of the routes
+    main_window.on_refresh_stats(move || {
+        if let Some(window) = weak.upgrade() {
+            refresh_stats(&window, &statistics_.lock().unwrap());
+            refresh_routes(&window, &routes_.lock().unwrap(), &id_to_type_pos_);
+            refresh(&window);
+        }
+    });
 
-    // thread for receiving DroneEvent
     let logger_ = logger.clone();
-    let sc_receiver_ = sc_receiver.clone();
-    let channels_ = channels.clone();
     let weak = main_window.as_weak();
-    let id_to_type_pos_ = id_to_type_pos.clone();
+    let layout_ = view.layout.clone();
+    // ON SET LAYOUT : handler to place the nodes with another layout
+    main_window.on_set_layout(move |mode| {
+        if let (Some(window), Some(layout_mode)) = (weak.upgrade(), LayoutMode::from_index(mode)) {
+            let mut layout = layout_.lock().unwrap();
+            layout.set_mode(layout_mode);
+            place_nodes(&window, &layout);
+            window.set_layout_mode(mode);
+            if let Err(e) = layout.save() {
+                logger_
+                    .lock()
+                    .unwrap()
+                    .log_warn(&format!("[ON_SET_LAYOUT] {}", e));
+            }
+        }
+    });
 
-    let downsample_ack = Arc::new(Mutex::new(0));
-    let downsample_msg_frag = Arc::new(Mutex::new(0));
-    let downsample_nack = Arc::new(Mutex::new(0));
-    let downsample_dropped = Arc::new(Mutex::new(0));
-    thread::spawn(move || {
-        loop {
-            if let Some(sc_rec) = sc_receiver_.lock().unwrap().as_ref() {
-                match sc_rec.try_recv() {
-                    // PacketDropped
-                    Ok(DroneEvent::PacketDropped(packet)) => {
-                        logger_
-                            .lock()
-                            .unwrap()
-                            .log_debug(&format!("PacketDropped received {:?}", packet));
-                        let downsample_dropped1 = downsample_dropped.clone();
-                        *downsample_dropped1.lock().unwrap() += 1;
-                        if *downsample_dropped1.lock().unwrap() % 10000 == 0 {
-                            *downsample_dropped1.lock().unwrap() = 0;
-                            send_message(&weak, &logger_, packet, id_to_type_pos_.clone(), true, 5);
-                        }
-                    }
-                    // PacketSent
-                    Ok(DroneEvent::PacketSent(packet)) => {
-                        logger_
-                            .lock()
-                            .unwrap()
-                            .log_debug(&format!("PacketSent received {:?}", packet));
-
-                        match packet.pack_type {
-                            PacketType::MsgFragment(_) => {
-                                *downsample_msg_frag.lock().unwrap() += 1;
-                                if *downsample_msg_frag.lock().unwrap() % 1000 == 0 {
-                                    *downsample_msg_frag.lock().unwrap() = 0;
-                                    send_message(
-                                        &weak,
-                                        &logger_,
-                                        packet,
-                                        id_to_type_pos_.clone(),
-                                        false,
-                                        0,
-                                    );
-                                }
-                            }
-                            PacketType::Ack(_) => {
-                                *downsample_ack.lock().unwrap() += 1;
-                                if *downsample_ack.lock().unwrap() % 1000 == 0 {
-                                    *downsample_ack.lock().unwrap() = 0;
-                                    send_message(
-                                        &weak,
-                                        &logger_,
-                                        packet,
-                                        id_to_type_pos_.clone(),
-                                        false,
-                                        1,
-                                    );
-                                }
-                            }
-                            PacketType::Nack(ref e) => {
-                                match e.nack_type {
-                                    NackType::Dropped => {} // avoid sending Nack because duplicate of PacketDropped
-                                    _ => {
-                                        *downsample_nack.lock().unwrap() += 1;
-                                        if *downsample_nack.lock().unwrap() % 1000 == 0 {
-                                            *downsample_nack.lock().unwrap() = 0;
-                                            send_message(
-                                                &weak,
-                                                &logger_,
-                                                packet,
-                                                id_to_type_pos_.clone(),
-                                                false,
-                                                2,
-                                            );
-                                        }
-                                    }
-                                }
-                            }
-                            PacketType::FloodRequest(ref flood) => {
-                                if flood.path_trace.len() >= 2 {
-                                    send_message(
-                                        &weak,
-                                        &logger_,
-                                        packet,
-                                        id_to_type_pos_.clone(),
-                                        false,
-                                        3,
-                                    );
-                                }
-                            }
-                            PacketType::FloodResponse(_) => {
-                                send_message(
-                                    &weak,
-                                    &logger_,
-                                    packet,
-                                    id_to_type_pos_.clone(),
-                                    false,
-                                    4,
-                                );
-                            }
-                        }
-                    }
-                    // ControllerShortcut
-                    Ok(DroneEvent::ControllerShortcut(packet)) => {
-                        logger_
-                            .lock()
-                            .unwrap()
-                            .log_debug(&format!("ControllerShortcut received {:?}", packet));
-
-                        match packet.pack_type {
-                            PacketType::FloodRequest(_)=>{},
-                            _ =>{
-                                let dest = packet.routing_header.hops[packet.routing_header.hops.len()-1];
-                                if let Some(ref channel) = *channels_.lock().unwrap() {
-                                    if let Some(sender) = channel.get(&(dest as u8)) {
-                                        let res = sender.sender.send(packet.clone());
-                                        match res {
-                                            Ok(_) => {
-                                                logger_.lock().unwrap().log_debug(&format!("{:?} succesfully sent to Node{}", packet, dest));
-                                            }
-                                            Err(e) => {
-                                                logger_.lock().unwrap().log_debug(&format!("Error sending packet to drone {}: {:?}", dest, e));
-                                            }
-                                        }
-                                    } else {
-                                        logger_.lock().unwrap().log_debug(&format!("No sender for drone {}", dest));
-                                    }
-                                } else {
-                                    logger_.lock().unwrap().log_debug("No senders available");
-                                }
-
-                                send_message(&weak, &logger_, packet, id_to_type_pos_.clone(), false, 6);
-                            }
-                        }
-                    }
-                    Err(TryRecvError::Empty) => {
-                        // logger_.lock().unwrap().log_warn("Empty channel");
-                    }
-                    Err(e) => {
-                        logger_
-                            .lock()
-                            .unwrap()
-                            .log_error(&format!("Error receiving message: {}", e));
-                    }
+    let weak = main_window.as_weak();
+    let routes_ = view.routes.clone();
+    let id_to_type_pos_ = view.id_to_type_pos.clone();
+    // ON SELECT ROUTE : handler to highlight the whole route of a session
+    main_window.on_select_route(move |key| {
+        if let Some(window) = weak.upgrade() {
+            match routes_.lock().unwrap().get(&key) {
+                Some(route) => {
+                    set_route(&window, Some(route), &id_to_type_pos_);
+                    window.set_status_message("".into());
                 }
+                None => window.set_status_message("The packet has no source route".into()),
             }
         }
     });
 
-    let logger_ = logger.clone();
     let weak = main_window.as_weak();
-    let senders = sc_senders.clone();
-    let channels_ = channels.clone();
-    // ON CRASH : handler for crash event
-    main_window.on_crash(move || {
-        logger_.lock().unwrap().log_info("[ON_CRASH]");
+    let id_to_type_pos_ = view.id_to_type_pos.clone();
+    // ON DISMISS ROUTE : handler to remove the highlight of the route
+    main_window.on_dismiss_route(move || {
+        if let Some(window) = weak.upgrade() {
+            set_route(&window, None, &id_to_type_pos_);
+        }
+    });
 
+    let weak = main_window.as_weak();
+    // ON FIT VIEW : handler to zoom on the whole topology
+    main_window.on_fit_view(move || {
         if let Some(window) = weak.upgrade() {
-            let id = window.get_id_selected_drone();
+            fit_view(&window);
+        }
+    });
 
-            // send crash command to drone
-            send_drone_command(&senders, id as u8, Box::new(DroneCommand::Crash), &logger_);
+    let weak = main_window.as_weak();
+    let layout_ = view.layout.clone();
+    // ON MOVE NODE : handler for the drag of a node, it stays where it is dropped
+    main_window.on_move_node(move |id, x, y| {
+        if let Some(window) = weak.upgrade() {
+            let mut layout = layout_.lock().unwrap();
+            layout.pin(id as u8, x, y);
+            place_nodes(&window, &layout);
+        }
+    });
 
-            // remove edges communicating with it
-            let edges = window.get_edges();
-            let mut i: usize = 0;
-            let mut to_remove: Vec<usize> = vec![];
-            for edge in edges.iter() {
-                if edge.id1 == id || edge.id2 == id {
-                    to_remove.push(i);
-                }
-                i = i + 1;
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let layout_ = view.layout.clone();
+    // ON SAVE LAYOUT : handler to keep the positions of the nodes in the sidecar of the configuration
+    main_window.on_save_layout(move || {
+        if let Err(e) = layout_.lock().unwrap().save() {
+            logger_
+                .lock()
+                .unwrap()
+                .log_warn(&format!("[ON_SAVE_LAYOUT] {}", e));
+            if let Some(window) = weak.upgrade() {
+                window.set_status_message(e.into());
             }
+        }
+    });
+}
 
-            for index in to_remove.iter().rev() {
-                if let Some(vec_model) = edges.as_any().downcast_ref::<VecModel<EdgeStruct>>() {
-                    vec_model.remove(*index);
-                } else {
-                    logger_
-                        .lock()
-                        .unwrap()
-                        .log_warn(&format!("[ON_CRASH] Problem in downcasting edges"));
-                }
-            }
+// replay mode: the view is driven by a recorded trace instead of a running simulation
+fn run_replay(
+    logger: Arc<Mutex<Logger>>,
+    path: &str,
+    policy: SamplingPolicy,
+) -> Result<(), slint::PlatformError> {
+    let records = match read_trace(path) {
+        Ok(records) => records,
+        Err(e) => {
+            logger
+                .lock()
+                .unwrap()
+                .log_error(&format!("[REPLAY] Error in reading the trace: {}", e));
+            return Ok(());
+        }
+    };
 
-            // remove its id from adjacent of others
-            let drones = window.get_drones();
-            for drone in drones.iter() {
-                let mut i = 0;
-                for adj in drone.adjent.iter() {
-                    if adj == id {
-                        if let Some(vec_model) =
-                            drone.adjent.as_any().downcast_ref::<VecModel<i32>>()
-                        {
-                            vec_model.remove(i);
-                        } else {
-                            logger_
-                                .lock()
-                                .unwrap()
-                                .log_warn(&format!("[ON_CRASH] Problem in downcasting adjacents"));
+    // initiate slint window
+    let main_window = Window::new()?;
+    let window = main_window.window();
+    window.set_fullscreen(true);
+    main_window.set_replay_mode(true);
+
+    let id_to_type_pos: Arc<Mutex<HashMap<i32, (NodeType, i32)>>> =
+        Arc::new(Mutex::new(HashMap::new())); // {id, (NodeType, position_in_vector)}
+    let layout = Arc::new(Mutex::new(Layout::new(LayoutMode::ForceDirected)));
+    let statistics: Arc<Mutex<Statistics>> = Arc::new(Mutex::new(Statistics::default()));
+    main_window.set_sampling(sampling_struct(&policy));
+    let sampler = Arc::new(Mutex::new(Sampler::new(policy)));
+    let routes = Arc::new(Mutex::new(Routes::default()));
+
+    let (update_sender, updates) = unbounded::<ReplayUpdate>();
+    let (controls, replayer) = Replayer::start(records, update_sender);
+
+    // thread for showing the replayed records
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let id_to_type_pos_ = id_to_type_pos.clone();
+    let layout_ = layout.clone();
+    let statistics_ = statistics.clone();
+    let sampler_ = sampler.clone();
+    let routes_ = routes.clone();
+    thread::spawn(move || {
+        let mut paused = true;
+
+        for update in updates.iter() {
+            match update {
+                ReplayUpdate::Topology { topology, reset } => {
+                    if reset {
+                        statistics_.lock().unwrap().reset();
+                        sampler_.lock().unwrap().reset();
+                        routes_.lock().unwrap().reset();
+                    }
+                    let id_to_type_pos = id_to_type_pos_.clone();
+                    let layout = layout_.clone();
+                    let _ = weak.upgrade_in_event_loop(move |window| {
+                        show_topology(&window, &topology, &id_to_type_pos, &layout);
+                        if reset {
+                            window.set_messages(slint::ModelRc::new(slint::VecModel::from(vec![])));
+                            set_route(&window, None, &id_to_type_pos);
                         }
-                        send_drone_command(
-                            &senders,
-                            drone.id as u8,
-                            Box::new(DroneCommand::RemoveSender(id as u8)),
+                    });
+                }
+                ReplayUpdate::Event(record) => {
+                    statistics_.lock().unwrap().record_replayed(&record);
+                    routes_.lock().unwrap().record_replayed(&record);
+
+                    // while stepping every event is shown
+                    let kind = if paused {
+                        replayed_message_kind(&record)
+                    } else {
+                        sampler_.lock().unwrap().sample_replayed(&record)
+                    };
+                    if let (Some(kind), Some((id1, id2))) = (kind, record.endpoints()) {
+                        show_messages(
+                            &weak,
                             &logger_,
+                            vec![(
+                                id1 as i32,
+                                id2 as i32,
+                                kind as i32,
+                                record_route_key(&record).unwrap_or_default(),
+                            )],
+                            id_to_type_pos_.clone(),
                         );
-                        break;
                     }
-                    i = i + 1;
                 }
-                i = 0;
-                for not_adj in drone.not_adjacent.iter() {
-                    if not_adj == id {
-                        if let Some(vec_model) =
-                            drone.not_adjacent.as_any().downcast_ref::<VecModel<i32>>()
-                        {
-                            vec_model.remove(i);
-                        } else {
-                            logger_.lock().unwrap().log_warn(&format!(
-                                "[ON_CRASH] Problem in downcasting not_adjacents"
-                            ));
-                        }
-                        break;
-                    }
-                    i = i + 1;
+                ReplayUpdate::Position {
+                    t,
+                    duration,
+                    paused: is_paused,
+                    speed,
+                } => {
+                    paused = is_paused;
+                    let _ = weak.upgrade_in_event_loop(move |window| {
+                        window.set_replay_time(t as f32 / 1000.0);
+                        window.set_replay_duration(duration as f32 / 1000.0);
+                        window.set_replay_paused(is_paused);
+                        window.set_replay_speed(speed);
+                    });
                 }
             }
+        }
+    });
 
-            let servers = window.get_servers();
-            for server in servers.iter() {
-                let mut i = 0;
-                for adj in server.drones_adjacent.iter() {
-                    if adj == id {
-                        if let Some(vec_model) = server
-                            .drones_adjacent
-                            .as_any()
-                            .downcast_ref::<VecModel<i32>>()
-                        {
-                            vec_model.remove(i);
-                        } else {
-                            logger_
-                                .lock()
-                                .unwrap()
-                                .log_warn(&format!("[ON_CRASH] Problem in downcasting adjacents"));
-                        }
+    let controls_ = controls.clone();
+    // ON REPLAY TOGGLE PAUSE : handler for play and pause of the replay
+    main_window.on_replay_toggle_pause(move || {
+        let _ = controls_.send(ReplayControl::TogglePause);
+    });
 
-                        send_drone_command(
-                            &senders,
-                            server.id as u8,
-                            Box::new(DroneCommand::RemoveSender(id as u8)),
-                            &logger_,
-                        );
+    let controls_ = controls.clone();
+    // ON REPLAY STEP : handler to replay only the next event
+    main_window.on_replay_step(move || {
+        let _ = controls_.send(ReplayControl::Step);
+    });
 
-                        break;
-                    }
-                    i = i + 1;
-                }
-                i = 0;
-                for not_adj in server.drones_not_adjacent.iter() {
-                    if not_adj == id {
-                        if let Some(vec_model) = server
-                            .drones_not_adjacent
-                            .as_any()
-                            .downcast_ref::<VecModel<i32>>()
-                        {
-                            vec_model.remove(i);
-                        } else {
-                            logger_.lock().unwrap().log_warn(&format!(
-                                "[ON_CRASH] Problem in downcasting not_adjacents"
-                            ));
-                        }
-                        break;
-                    }
-                    i = i + 1;
-                }
+    let controls_ = controls.clone();
+    // ON REPLAY SEEK : handler to move the replay to the given second
+    main_window.on_replay_seek(move |seconds| {
+        let _ = controls_.send(ReplayControl::Seek((seconds.max(0.0) * 1000.0) as u64));
+    });
+
+    let controls_ = controls.clone();
+    // ON REPLAY SPEED : handler to change the speed of the replay
+    main_window.on_replay_set_speed(move |speed| {
+        let _ = controls_.send(ReplayControl::Speed(speed.clamp(MIN_SPEED, MAX_SPEED)));
+    });
+
+    let view = ViewState {
+        id_to_type_pos: id_to_type_pos.clone(),
+        layout: layout.clone(),
+        statistics: statistics.clone(),
+        sampler: sampler.clone(),
+        routes: routes.clone(),
+    };
+    register_view_callbacks(&main_window, &logger, &view, |_| {});
+
+    let _res = main_window.run();
+    drop(controls); // the replay thread stops when the controls are closed
+    replayer.join().unwrap();
+    Ok(())
+}
+
+fn main() -> Result<(), slint::PlatformError> {
+    // initiate logger
+    let logger = initiate_logger(LogLevel::Info);
+
+    let args = match Args::parse(PATH) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    if args.lint {
+        std::process::exit(run_lint(&args.config));
+    }
+
+    // generate mode: the configuration is printed, nothing is run
+    if let Some(options) = args.generate {
+        match generate(&options) {
+            Ok(topology) => print!("{}", topology.to_config()),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
             }
+        }
+        return Ok(());
+    }
 
-            let clients = window.get_clients();
-            for client in clients.iter() {
-                let mut i = 0;
-                for adj in client.drones_adjacent.iter() {
-                    if adj == id {
-                        if let Some(vec_model) = client
-                            .drones_adjacent
-                            .as_any()
-                            .downcast_ref::<VecModel<i32>>()
-                        {
-                            vec_model.remove(i);
-                        } else {
-                            logger_
-                                .lock()
-                                .unwrap()
-                                .log_warn(&format!("[ON_CRASH] Problem in downcasting adjacents"));
-                        }
+    // headless mode: same simulation, commands read from script or stdin
+    if args.headless {
+        headless::run_headless(
+            logger,
+            HeadlessOptions {
+                config: args.config,
+                script: args.script,
+                scenario: args.scenario,
+                chaos: args.chaos,
+                allow_partition: args.allow_partition,
+                record: args.record,
+            },
+        );
+        return Ok(());
+    }
 
-                        send_drone_command(
-                            &senders,
-                            client.id as u8,
-                            Box::new(DroneCommand::RemoveSender(id as u8)),
-                            &logger_,
-                        );
-                        break;
-                    }
-                    i = i + 1;
-                }
-                i = 0;
-                for not_adj in client.drones_not_adjacent.iter() {
-                    if not_adj == id {
-                        if let Some(vec_model) = client
-                            .drones_not_adjacent
-                            .as_any()
-                            .downcast_ref::<VecModel<i32>>()
-                        {
-                            vec_model.remove(i);
-                        } else {
-                            logger_.lock().unwrap().log_warn(&format!(
-                                "[ON_CRASH] Problem in downcasting not_adjacents"
-                            ));
-                        }
-                        break;
-                    }
-                    i = i + 1;
+    // replay mode: recorded trace shown in the same view
+    if let Some(trace) = args.replay {
+        let policy = sampling_policy(&logger, None, &args.sampling);
+        return run_replay(logger, &trace, policy);
+    }
+
+    // initiate slint window
+    let main_window = Window::new()?;
+    let window = main_window.window();
+    window.set_fullscreen(true);
+
+    // initial configuration -> default
+    let controller = Arc::new(Mutex::new(SimulationController::new(logger.clone())));
+    let id_to_type_pos: Arc<Mutex<HashMap<i32, (NodeType, i32)>>> =
+        Arc::new(Mutex::new(HashMap::new())); // {id, (NodeType, position_in_vector)}
+    let layout = Arc::new(Mutex::new(Layout::new(LayoutMode::ForceDirected)));
+
+    if args.allow_partition {
+        controller
+            .lock()
+            .unwrap()
+            .set_connectivity_check(ConnectivityCheck::Warn);
+    }
+    if let Some(ref trace) = args.record {
+        let res = controller.lock().unwrap().start_recording(trace);
+        if let Err(e) = res {
+            logger
+                .lock()
+                .unwrap()
+                .log_error(&format!("Cannot record to {}: {}", trace, e));
+        }
+    }
+    main_window.set_recording(controller.lock().unwrap().is_recording());
+    let res = if check_config(&logger, &args.config) {
+        controller.lock().unwrap().load_config(&args.config)
+    } else {
+        Ok(())
+    };
+    match res {
+        Ok(_) => {
+            open_layout(&main_window, &logger, &layout, &args.config);
+            show_topology(
+                &main_window,
+                controller.lock().unwrap().topology(),
+                &id_to_type_pos,
+                &layout,
+            );
+        }
+        Err(e) => {
+            (*logger)
+                .lock()
+                .unwrap()
+                .log_error(&format!("Error in loading the configuration file: {}", e));
+        }
+    }
+
+    // statistics of the DroneEvent of the current simulation
+    let statistics: Arc<Mutex<Statistics>> = Arc::new(Mutex::new(Statistics::default()));
+
+    // events animated in the view
+    let policy = sampling_policy(&logger, Some(&args.config), &args.sampling);
+    main_window.set_sampling(sampling_struct(&policy));
+    let sampler = Arc::new(Mutex::new(Sampler::new(policy)));
+
+    // source routes of the recent sessions, for the highlighted route
+    let routes = Arc::new(Mutex::new(Routes::default()));
+
+    // thread for receiving DroneEvent and the updates of the topology
+    let mut handler = ViewEvents {
+        weak: main_window.as_weak(),
+        logger: logger.clone(),
+        controller: controller.clone(),
+        id_to_type_pos: id_to_type_pos.clone(),
+        layout: layout.clone(),
+        statistics: statistics.clone(),
+        sampler: sampler.clone(),
+        routes: routes.clone(),
+        pending: vec![],
+    };
+    let updates = controller.lock().unwrap().subscribe();
+    let controls = controller.lock().unwrap().event_loop_control();
+    let event_loop =
+        thread::spawn(move || run_event_loop(updates, controls, MESSAGES_INTERVAL, &mut handler));
+
+    // pdr of the drones with a profile, updated every tick
+    let pdr_driver = PdrDriver::start(controller.clone());
+
+    // scenario running on the current simulation (if any)
+    let scheduler: Arc<Mutex<Option<Scheduler>>> = Arc::new(Mutex::new(None));
+    if let Some(ref path) = args.scenario {
+        match start_scenario(&controller, &logger, main_window.as_weak(), path) {
+            Ok(s) => *scheduler.lock().unwrap() = Some(s),
+            Err(e) => logger
+                .lock()
+                .unwrap()
+                .log_error(&format!("[SCENARIO] {}", e)),
+        }
+    }
+
+    // random fault injection (if any)
+    let chaos: Arc<Mutex<Option<ChaosEngine>>> =
+        Arc::new(Mutex::new(args.chaos.clone().map(|options| {
+            ChaosEngine::start(controller.clone(), logger.clone(), options)
+        })));
+    main_window.set_chaos_running(chaos.lock().unwrap().is_some());
+
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    // ON CRASH : handler for crash event
+    main_window.on_crash(move || {
+        logger_.lock().unwrap().log_info("[ON_CRASH]");
+
+        if let Some(window) = weak.upgrade() {
+            let id = window.get_id_selected_drone();
+
+            let res = controller_.lock().unwrap().crash(id as u8);
+            match res {
+                Ok(_) => window.set_status_message("".into()),
+                Err(e) => {
+                    logger_
+                        .lock()
+                        .unwrap()
+                        .log_warn(&format!("[ON_CRASH] {}", e));
+                    window.set_status_message(e.to_string().into());
                 }
             }
+        }
+    });
 
-            // drop its channels and senders
-            if let Some(ref mut channel) = *channels_.lock().unwrap() {
-                channel.remove(&(id as u8));
-            } else {
-                logger_
-                    .lock()
-                    .unwrap()
-                    .log_warn(&format!("[ON_CRASH] No channels map loaded"));
-            }
-            if let Some(ref mut s) = *senders.lock().unwrap() {
-                s.remove(&(id as u8));
-            } else {
-                logger_
-                    .lock()
-                    .unwrap()
-                    .log_warn(&format!("[ON_CRASH] No sender map loaded"));
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    // ON REACTIVATE DRONE : handler for reactivate drone event
+    main_window.on_reactivate_drone(move || {
+        logger_.lock().unwrap().log_info("[ON_REACTIVATE_DRONE]");
+
+        if let Some(window) = weak.upgrade() {
+            let id = window.get_id_selected_drone();
+
+            let res = controller_.lock().unwrap().reactivate(id as u8);
+            match res {
+                Ok(_) => window.set_status_message("".into()),
+                Err(e) => {
+                    logger_
+                        .lock()
+                        .unwrap()
+                        .log_warn(&format!("[ON_REACTIVATE_DRONE] {}", e));
+                    window.set_status_message(e.to_string().into());
+                }
             }
         }
     });
 
-    // Note: the channels are not removed becuase the connection cannot be deleted if it is the last of the drone
     let logger_ = logger.clone();
     let weak = main_window.as_weak();
-    let senders = sc_senders.clone();
+    let controller_ = controller.clone();
     // ON REMOVE EDGE : handler for remove edge event
     main_window.on_remove_edge(move || {
         logger_.lock().unwrap().log_info("[ON_REMOVE_EDGE]");
@@ -672,207 +1437,398 @@ fn main() -> Result<(), slint::PlatformError> {
             let id_1 = window.get_sender_id();
             let id_2 = window.get_receiver_id();
 
-            // send DroneCommand to id_1 and id_2
-            send_drone_command(
-                &senders,
-                id_1 as u8,
-                Box::new(DroneCommand::RemoveSender(id_2 as u8)),
-                &logger_,
-            );
-            send_drone_command(
-                &senders,
-                id_2 as u8,
-                Box::new(DroneCommand::RemoveSender(id_1 as u8)),
-                &logger_,
-            );
+            let res = controller_
+                .lock()
+                .unwrap()
+                .remove_link(id_1 as u8, id_2 as u8);
+            match res {
+                Ok(_) => window.set_status_message("".into()),
+                Err(e) => {
+                    logger_
+                        .lock()
+                        .unwrap()
+                        .log_warn(&format!("[ON_REMOVE_EDGE] {}", e));
+                    window.set_status_message(e.to_string().into());
+                }
+            }
+        }
+    });
 
-            // remove edge from edges
-            let edges = window.get_edges();
-            let mut i = 0;
-            for edge in edges.iter() {
-                if (edge.id1 == id_1 && edge.id2 == id_2) || (edge.id1 == id_2 && edge.id2 == id_1)
-                {
-                    if let Some(vec_model) = edges.as_any().downcast_ref::<VecModel<EdgeStruct>>() {
-                        vec_model.remove(i);
-                    } else {
-                        logger_
-                            .lock()
-                            .unwrap()
-                            .log_warn("[ON_REMOVE_EDGE] Problems in downcasting edges");
-                    }
-                    break;
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    // ON REMOVE EDGE CLIENT SERVER : handler for remove edge client server event
+    main_window.on_remove_edge_client_server(move || {
+        logger_
+            .lock()
+            .unwrap()
+            .log_info("[ON_REMOVE_EDGE_CLIENT_SERVER]");
+
+        if let Some(window) = weak.upgrade() {
+            let id_1 = window.get_sender_id();
+            let id_2 = window.get_receiver_id();
+
+            let res = controller_
+                .lock()
+                .unwrap()
+                .remove_link(id_1 as u8, id_2 as u8);
+            match res {
+                Ok(_) => window.set_status_message("".into()),
+                Err(e) => {
+                    logger_
+                        .lock()
+                        .unwrap()
+                        .log_warn(&format!("[ON_REMOVE_EDGE_CLIENT_SERVER] {}", e));
+                    window.set_status_message(e.to_string().into());
                 }
-                i = i + 1;
             }
+        }
+    });
 
-            // remove adjacents
-            let drones = window.get_drones();
-            for drone in drones.iter() {
-                if drone.id == id_1 {
-                    let mut i = 0;
-                    for adj in drone.adjent.iter() {
-                        if adj == id_2 {
-                            if let Some(vec_model) =
-                                drone.adjent.as_any().downcast_ref::<VecModel<i32>>()
-                            {
-                                vec_model.remove(i);
-                            } else {
-                                logger_
-                                    .lock()
-                                    .unwrap()
-                                    .log_warn("[ON_REMOVE_EDGE] Problems in downcasting adjacent");
-                            }
-                            if let Some(vec_model) =
-                                drone.not_adjacent.as_any().downcast_ref::<VecModel<i32>>()
-                            {
-                                vec_model.push(id_2);
-                            } else {
-                                logger_.lock().unwrap().log_warn(
-                                    "[ON_REMOVE_EDGE] Problems in downcasting not_adjacent",
-                                );
-                            }
-                            break;
-                        }
-                        i = i + 1;
-                    }
-                } else if drone.id == id_2 {
-                    let mut i = 0;
-                    for adj in drone.adjent.iter() {
-                        if adj == id_1 {
-                            if let Some(vec_model) =
-                                drone.adjent.as_any().downcast_ref::<VecModel<i32>>()
-                            {
-                                vec_model.remove(i);
-                            } else {
-                                logger_
-                                    .lock()
-                                    .unwrap()
-                                    .log_warn("[ON_REMOVE_EDGE] Problems in downcasting adjacent");
-                            }
-                            if let Some(vec_model) =
-                                drone.not_adjacent.as_any().downcast_ref::<VecModel<i32>>()
-                            {
-                                vec_model.push(id_1);
-                            } else {
-                                logger_.lock().unwrap().log_warn(
-                                    "[ON_REMOVE_EDGE] Problems in downcasting not_adjacent",
-                                );
-                            }
-                            break;
-                        }
-                        i = i + 1;
-                    }
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    // ON ADD EDGE : handler for add edge event
+    main_window.on_add_edge(move || {
+        logger_.lock().unwrap().log_info("[ON_ADD_EDGE]");
+
+        if let Some(window) = weak.upgrade() {
+            let id_1 = window.get_sender_id();
+            let id_2 = window.get_receiver_id();
+
+            let res = controller_.lock().unwrap().add_link(id_1 as u8, id_2 as u8);
+            match res {
+                Ok(_) => window.set_status_message("".into()),
+                Err(e) => {
+                    logger_
+                        .lock()
+                        .unwrap()
+                        .log_error(&format!("[ON_ADD_EDGE] {}", e));
+                    window.set_status_message(e.to_string().into());
                 }
             }
         }
     });
 
-    // Note: the channels are not removed becuase the connection cannot be deleted if it is the last of the drone
     let logger_ = logger.clone();
     let weak = main_window.as_weak();
-    let senders = sc_senders.clone();
-    // ON REMOVE EDGE CLIENT SERVER : handler for remove edge client server event
-    main_window.on_remove_edge_client_server(move || {
+    let controller_ = controller.clone();
+    // ON ADD EDGE CLIENT SERVER : handler for add edge client server event
+    main_window.on_add_edge_client_server(move || {
         logger_
             .lock()
             .unwrap()
-            .log_info("[ON_REMOVE_EDGE_CLIENT_SERVER]");
+            .log_info("[ON_ADD_EDGE_CLIENT_SERVER]");
 
         if let Some(window) = weak.upgrade() {
             let id_1 = window.get_sender_id();
             let id_2 = window.get_receiver_id();
 
-            // send DroneCommand to id1 and id2
-            send_drone_command(
-                &senders,
-                id_1 as u8,
-                Box::new(DroneCommand::RemoveSender(id_2 as u8)),
-                &logger_,
-            );
+            let res = controller_.lock().unwrap().add_link(id_1 as u8, id_2 as u8);
+            match res {
+                Ok(_) => window.set_status_message("".into()),
+                Err(e) => {
+                    logger_
+                        .lock()
+                        .unwrap()
+                        .log_error(&format!("[ON_ADD_EDGE_CLIENT_SERVER] {}", e));
+                    window.set_status_message(e.to_string().into());
+                }
+            }
+        }
+    });
 
-            send_drone_command(
-                &senders,
-                id_2 as u8,
-                Box::new(DroneCommand::RemoveSender(id_1 as u8)),
-                &logger_,
-            );
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    // ON CHANGE PDR : handler for change packet drop rate
+    main_window.on_change_pdr(move || {
+        logger_.lock().unwrap().log_info("[ON_CHANGE_PDR]");
+        if let Some(window) = weak.upgrade() {
+            let id = window.get_id_selected_drone();
+            let new_pdr = window.get_new_pdr();
 
-            // remove edge
-            let edges = window.get_edges();
-            let mut i = 0;
-            for edge in edges.iter() {
-                if (edge.id1 == id_1 && edge.id2 == id_2) || (edge.id1 == id_2 && edge.id2 == id_1)
-                {
-                    if let Some(vec_model) = edges.as_any().downcast_ref::<VecModel<EdgeStruct>>() {
-                        vec_model.remove(i);
-                    } else {
-                        logger_.lock().unwrap().log_warn(
-                            "[ON_REMOVE_EDGE_CLIENT_SERVER] Problems in downcasting edges",
-                        );
+            let res = controller_.lock().unwrap().set_pdr(id as u8, new_pdr);
+            match res {
+                Ok(_) => window.set_status_message("".into()),
+                Err(e) => {
+                    logger_
+                        .lock()
+                        .unwrap()
+                        .log_warn(&format!("[ON_CHANGE_PDR] {}", e));
+                    window.set_status_message(e.to_string().into());
+                }
+            }
+        }
+    });
+
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    // ON ADD NODE : handler to spawn a new drone, client or server
+    main_window.on_add_node(move |node_type, id, pdr, neighbours| {
+        logger_.lock().unwrap().log_info("[ON_ADD_NODE]");
+
+        if let Some(window) = weak.upgrade() {
+            let node_type = match node_type {
+                0 => NodeType::Drone,
+                1 => NodeType::Client,
+                _ => NodeType::Server,
+            };
+            let neighbours: Result<Vec<u8>, _> = neighbours
+                .split_whitespace()
+                .map(|adj| adj.parse::<u8>())
+                .collect();
+            let res =
+                match neighbours {
+                    Ok(neighbours) if (0..=255).contains(&id) => controller_
+                        .lock()
+                        .unwrap()
+                        .add_node(node_type, id as u8, pdr, &neighbours),
+                    _ => {
+                        window.set_status_message("invalid id or neighbours".into());
+                        return;
                     }
-                    break;
+                };
+            match res {
+                Ok(_) => window.set_status_message("".into()),
+                Err(e) => {
+                    logger_
+                        .lock()
+                        .unwrap()
+                        .log_warn(&format!("[ON_ADD_NODE] {}", e));
+                    window.set_status_message(e.to_string().into());
                 }
-                i = i + 1;
             }
+        }
+    });
 
-            // remove adjacent and not_adjacent
-            let tmp1: i32; // lower -> drone
-            let tmp2: i32; // higher -> client or server
-            if id_1 < id_2 {
-                tmp1 = id_1;
-                tmp2 = id_2;
-            } else {
-                tmp1 = id_2;
-                tmp2 = id_1;
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    // ON REMOVE NODE : handler to remove a node from the simulation
+    main_window.on_remove_node(move |id| {
+        logger_.lock().unwrap().log_info("[ON_REMOVE_NODE]");
+
+        if let Some(window) = weak.upgrade() {
+            let res = controller_.lock().unwrap().remove_node(id as u8);
+            match res {
+                Ok(_) => window.set_status_message("".into()),
+                Err(e) => {
+                    logger_
+                        .lock()
+                        .unwrap()
+                        .log_warn(&format!("[ON_REMOVE_NODE] {}", e));
+                    window.set_status_message(e.to_string().into());
+                }
             }
-            if tmp2 < 30 {
-                //->cliet
-                let clients = window.get_clients();
-                for c in clients.iter() {
-                    if c.id == tmp2 {
-                        if let Some(not_adj) = c
-                            .drones_not_adjacent
-                            .as_any()
-                            .downcast_ref::<VecModel<i32>>()
-                        {
-                            not_adj.push(tmp1);
-                        }
-                        if let Some(adj) =
-                            c.drones_adjacent.as_any().downcast_ref::<VecModel<i32>>()
-                        {
-                            let mut i = 0;
-                            for e in adj.iter() {
-                                if e == tmp1 {
-                                    adj.remove(i);
-                                    break;
-                                }
-                                i = i + 1;
-                            }
-                        }
+        }
+    });
+
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    let sampler_ = sampler.clone();
+    let layout_ = layout.clone();
+    let scheduler_ = scheduler.clone();
+    // ON SELECT NEW FILE : handler for select new file
+    main_window.on_select_new_file(move || {
+        logger_.lock().unwrap().log_info("[ON_SELECT_NEW_FILE]");
+        let mut new_path: String = String::from("");
+
+        // take new path
+        let file = FileDialog::new().pick_file();
+        if let Some(mut path) = file {
+            let path_string = <OsString as Clone>::clone(&path.as_mut_os_string()).into_string();
+            if let Ok(path_string) = path_string {
+                logger_.lock().unwrap().log_info(&format!(
+                    "[ON_SELECT_NEW_FILE] File selected : {}",
+                    path_string
+                ));
+                new_path = path_string;
+            }
+        }
+
+        if new_path.is_empty() || !check_config(&logger_, &new_path) {
+            return;
+        }
+
+        // if it is valid, the controller stops the current simulation and runs the new one
+        stop_scenario(&scheduler_);
+        let res = controller_.lock().unwrap().load_config(&new_path);
+        match res {
+            // the sampling policy of the new file (if any) replaces the current one
+            Ok(_) => {
+                if let Some(window) = weak.upgrade() {
+                    open_layout(&window, &logger_, &layout_, &new_path);
+                }
+                if let Ok(Some(policy)) = SamplingPolicy::from_config_file(&new_path) {
+                    if let Some(window) = weak.upgrade() {
+                        window.set_sampling(sampling_struct(&policy));
                     }
+                    sampler_.lock().unwrap().set_policy(policy);
                 }
-            } else {
-                //->server
-                let servers = window.get_servers();
-                for s in servers.iter() {
-                    if s.id == tmp2 {
-                        if let Some(n_adj) = s
-                            .drones_not_adjacent
-                            .as_any()
-                            .downcast_ref::<VecModel<i32>>()
-                        {
-                            n_adj.push(tmp1);
-                        }
-                        if let Some(adj) =
-                            s.drones_adjacent.as_any().downcast_ref::<VecModel<i32>>()
-                        {
-                            let mut i = 0;
-                            for e in adj.iter() {
-                                if e == tmp1 {
-                                    adj.remove(i);
-                                    break;
-                                }
-                                i = i + 1;
+            }
+            Err(e) => {
+                logger_.lock().unwrap().log_error(&format!(
+                    "[ON_SELECT_NEW_FILE] Error in loading new configuration: {}",
+                    e
+                ));
+                MessageDialog::new()
+                    .set_level(MessageLevel::Error)
+                    .set_title("The configuration cannot be loaded")
+                    .set_description(e.to_string())
+                    .set_buttons(MessageButtons::Ok)
+                    .show();
+            }
+        }
+    });
+
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    // ON TOGGLE RECORDING : handler to start and stop the recording of the trace
+    main_window.on_toggle_recording(move || {
+        logger_.lock().unwrap().log_info("[ON_TOGGLE_RECORDING]");
+        let recording = controller_.lock().unwrap().is_recording();
+
+        // the controller is not locked while the dialog is open
+        if recording {
+            controller_.lock().unwrap().stop_recording();
+        } else if let Some(path) = FileDialog::new().set_file_name("trace.jsonl").save_file() {
+            let res = controller_
+                .lock()
+                .unwrap()
+                .start_recording(&path.to_string_lossy());
+            if let Err(e) = res {
+                logger_
+                    .lock()
+                    .unwrap()
+                    .log_error(&format!("[ON_TOGGLE_RECORDING] {}", e));
+            }
+        }
+
+        if let Some(window) = weak.upgrade() {
+            window.set_recording(controller_.lock().unwrap().is_recording());
+        }
+    });
+
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    let layout_ = layout.clone();
+    // ON SAVE TOPOLOGY : handler to write the current topology to a configuration file
+    main_window.on_save_topology(move || {
+        logger_.lock().unwrap().log_info("[ON_SAVE_TOPOLOGY]");
+
+        let file = FileDialog::new()
+            .add_filter("TOML", &["toml"])
+            .set_file_name("topology.toml")
+            .save_file();
+        if let Some(path) = file {
+            let path = path.to_string_lossy().to_string();
+            // the layout is saved next to the new configuration
+            let res = controller_
+                .lock()
+                .unwrap()
+                .save_topology(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|_| layout_.lock().unwrap().save_as(&sidecar_path(&path)));
+            if let Err(e) = res {
+                logger_
+                    .lock()
+                    .unwrap()
+                    .log_error(&format!("[ON_SAVE_TOPOLOGY] {}", e));
+                if let Some(window) = weak.upgrade() {
+                    window.set_status_message(format!("Cannot save the topology: {}", e).into());
+                }
+            }
+        }
+    });
+
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    let layout_ = layout.clone();
+    let scheduler_ = scheduler.clone();
+    // ON GENERATE TOPOLOGY : handler to generate a new configuration and load it
+    main_window.on_generate_topology(move |spec| {
+        logger_
+            .lock()
+            .unwrap()
+            .log_info(&format!("[ON_GENERATE_TOPOLOGY] {}", spec));
+
+        // the generated file is kept in the temporary directory, "Save topology as..." keeps it
+        let path = std::env::temp_dir().join("generated_topology.toml");
+        let path = path.to_string_lossy().to_string();
+        let res = GeneratorOptions::from_spec(&spec)
+            .and_then(|options| generate(&options))
+            .and_then(|topology| {
+                std::fs::write(&path, topology.to_config()).map_err(|e| format!("{}: {}", path, e))
+            });
+        if let Err(e) = res {
+            logger_
+                .lock()
+                .unwrap()
+                .log_warn(&format!("[ON_GENERATE_TOPOLOGY] {}", e));
+            if let Some(window) = weak.upgrade() {
+                window.set_status_message(e.into());
+            }
+            return;
+        }
+
+        if !check_config(&logger_, &path) {
+            return;
+        }
+        stop_scenario(&scheduler_);
+        let res = controller_.lock().unwrap().load_config(&path);
+        match res {
+            Ok(_) => {
+                if let Some(window) = weak.upgrade() {
+                    open_layout(&window, &logger_, &layout_, &path);
+                    window.set_status_message("".into());
+                }
+            }
+            Err(e) => {
+                logger_.lock().unwrap().log_error(&format!(
+                    "[ON_GENERATE_TOPOLOGY] Error in loading the generated configuration: {}",
+                    e
+                ));
+                if let Some(window) = weak.upgrade() {
+                    window.set_status_message(e.to_string().into());
+                }
+            }
+        }
+    });
+
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    let scheduler_ = scheduler.clone();
+    // ON TOGGLE SCENARIO : handler to run a scenario file or to stop the running one
+    main_window.on_toggle_scenario(move || {
+        logger_.lock().unwrap().log_info("[ON_TOGGLE_SCENARIO]");
+        let mut scheduler = scheduler_.lock().unwrap();
+
+        match scheduler.take() {
+            Some(running) if !running.is_finished() => running.stop(),
+            _ => {
+                let file = FileDialog::new().add_filter("TOML", &["toml"]).pick_file();
+                if let Some(path) = file {
+                    match start_scenario(
+                        &controller_,
+                        &logger_,
+                        weak.clone(),
+                        &path.to_string_lossy(),
+                    ) {
+                        Ok(s) => *scheduler = Some(s),
+                        Err(e) => {
+                            logger_
+                                .lock()
+                                .unwrap()
+                                .log_error(&format!("[ON_TOGGLE_SCENARIO] {}", e));
+                            if let Some(window) = weak.upgrade() {
+                                window.set_status_message(e.into());
                             }
                         }
                     }
@@ -881,531 +1837,359 @@ fn main() -> Result<(), slint::PlatformError> {
         }
     });
 
-    // Note: the channels are not created becuase the connection the drones already exists, they are only cloned
     let logger_ = logger.clone();
     let weak = main_window.as_weak();
-    let senders = sc_senders.clone();
-    let channels_ = channels.clone();
-    let id_to_type_pos_ = id_to_type_pos.clone();
-    // ON ADD EDGE : handler for add edge event
-    main_window.on_add_edge(move || {
-        logger_.lock().unwrap().log_info("[ON_ADD_EDGE]");
+    let controller_ = controller.clone();
+    let chaos_ = chaos.clone();
+    // ON TOGGLE CHAOS : handler to start and stop the random fault injection
+    main_window.on_toggle_chaos(move |spec| {
+        logger_.lock().unwrap().log_info("[ON_TOGGLE_CHAOS]");
+        let mut chaos = chaos_.lock().unwrap();
 
         if let Some(window) = weak.upgrade() {
-            let id_1 = window.get_sender_id();
-            let id_2 = window.get_receiver_id();
-
-            let mut sender_id_1: Option<Sender<Packet>> = None;
-            let mut sender_id_2: Option<Sender<Packet>> = None;
+            match chaos.take() {
+                Some(engine) if !engine.is_finished() => engine.stop(),
+                _ => match ChaosOptions::from_spec(&spec) {
+                    Ok(options) => {
+                        *chaos = Some(ChaosEngine::start(
+                            controller_.clone(),
+                            logger_.clone(),
+                            options,
+                        ));
+                        window.set_status_message("".into());
+                    }
+                    Err(e) => {
+                        logger_
+                            .lock()
+                            .unwrap()
+                            .log_warn(&format!("[ON_TOGGLE_CHAOS] {}", e));
+                        window.set_status_message(format!("Chaos: {}", e).into());
+                    }
+                },
+            }
+            window.set_chaos_running(chaos.is_some());
+        }
+    });
 
-            // retrieve channels to send in the droneCommand
-            if let Some(ref mut channel) = *channels_.lock().unwrap() {
-                if let Some(ch_1) = (*channel).get(&(id_1 as u8)) {
-                    sender_id_1 = Some(ch_1.sender.clone());
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    // ON SET LINK PROFILE : handler to change the emulated delay, jitter and bandwidth of a link
+    main_window.on_set_link_profile(
+        move |from, to, delay, jitter, distribution, bandwidth, both| {
+            logger_.lock().unwrap().log_info("[ON_SET_LINK_PROFILE]");
+
+            if let Some(window) = weak.upgrade() {
+                if !(0..=255).contains(&from) || !(0..=255).contains(&to) {
+                    window.set_status_message("invalid link".into());
+                    return;
+                }
+                let distribution = if distribution == 1 {
+                    Jitter::Normal
                 } else {
-                    logger_
-                        .lock()
-                        .unwrap()
-                        .log_error("[ON_ADD_EDGE] No sender for id1");
+                    Jitter::Uniform
+                };
+                let profile = match LinkProfile::from_millis(delay, jitter, distribution, bandwidth)
+                {
+                    Ok(profile) => profile,
+                    Err(e) => {
+                        window.set_status_message(e.into());
+                        return;
+                    }
+                };
+
+                let mut controller = controller_.lock().unwrap();
+                let mut res = controller.set_link_profile(from as u8, to as u8, profile);
+                if both && res.is_ok() {
+                    res = controller.set_link_profile(to as u8, from as u8, profile);
+                }
+                match res {
+                    Ok(_) => window.set_status_message("".into()),
+                    Err(e) => {
+                        logger_
+                            .lock()
+                            .unwrap()
+                            .log_warn(&format!("[ON_SET_LINK_PROFILE] {}", e));
+                        window.set_status_message(e.to_string().into());
+                    }
                 }
+            }
+        },
+    );
+
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    // ON TAP LINK : handler to capture every packet of a link
+    main_window.on_tap_link(move |id1, id2| {
+        logger_.lock().unwrap().log_info("[ON_TAP_LINK]");
 
-                if let Some(ch_2) = channel.get(&(id_2 as u8)) {
-                    sender_id_2 = Some(ch_2.sender.clone());
-                } else {
+        if let Some(window) = weak.upgrade() {
+            if !(0..=255).contains(&id1) || !(0..=255).contains(&id2) {
+                window.set_status_message("invalid link".into());
+                return;
+            }
+            let res = controller_.lock().unwrap().tap_link(id1 as u8, id2 as u8);
+            match res {
+                Ok(_) => window.set_status_message("".into()),
+                Err(e) => {
                     logger_
                         .lock()
                         .unwrap()
-                        .log_error("[ON_ADD_EDGE] No sender for id2");
+                        .log_warn(&format!("[ON_TAP_LINK] {}", e));
+                    window.set_status_message(e.to_string().into());
                 }
-            } else {
-                logger_
-                    .lock()
-                    .unwrap()
-                    .log_error("[ON_ADD_EDGE] No sender map loaded");
             }
+        }
+    });
 
-            // send DroneCommand to id_1 and id_2
-            if let Some(s_id_2) = sender_id_2 {
-                send_drone_command(
-                    &senders,
-                    id_1 as u8,
-                    Box::new(DroneCommand::AddSender(id_2 as u8, s_id_2)),
-                    &logger_,
-                );
-            } else {
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    // ON UNTAP LINK : handler to stop the capture of a link
+    main_window.on_untap_link(move |id1, id2| {
+        logger_.lock().unwrap().log_info("[ON_UNTAP_LINK]");
+
+        if let Some(window) = weak.upgrade() {
+            let res = controller_.lock().unwrap().untap_link(id1 as u8, id2 as u8);
+            if let Err(e) = res {
                 logger_
                     .lock()
                     .unwrap()
-                    .log_error(&format!("[ON_ADD_EDGE] No sender for drone {}", id_2));
+                    .log_warn(&format!("[ON_UNTAP_LINK] {}", e));
+                window.set_status_message(e.to_string().into());
             }
+        }
+    });
 
-            if let Some(s_id_1) = sender_id_1 {
-                send_drone_command(
-                    &senders,
-                    id_2 as u8,
-                    Box::new(DroneCommand::AddSender(id_1 as u8, s_id_1)),
-                    &logger_,
-                );
-            } else {
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    // ON EXPORT CAPTURE : handler to write the packets of a capture to a JSON Lines file
+    main_window.on_export_capture(move |id1, id2| {
+        logger_.lock().unwrap().log_info("[ON_EXPORT_CAPTURE]");
+
+        let capture = controller_
+            .lock()
+            .unwrap()
+            .captures()
+            .into_iter()
+            .find(|(a, b, _, _)| (*a as i32, *b as i32) == (id1, id2))
+            .map(|(_, _, _, capture)| capture);
+        let Some(capture) = capture else {
+            return;
+        };
+        let file = FileDialog::new()
+            .add_filter("JSON Lines", &["jsonl"])
+            .set_file_name(format!("capture_{}_{}.jsonl", id1, id2))
+            .save_file();
+        if let Some(path) = file {
+            let res = capture.lock().unwrap().export(&path.to_string_lossy());
+            if let Err(e) = res {
                 logger_
                     .lock()
                     .unwrap()
-                    .log_error(&format!("[ON_ADD_EDGE] No sender for drone {}", id_1));
-            }
-
-            // add edge
-            let edges = window.get_edges();
-            let (_, index1) = get_node_type(id_1, &id_to_type_pos_);
-            let (_, index2) = get_node_type(id_2, &id_to_type_pos_);
-            if let Some(edge) = edges.as_any().downcast_ref::<VecModel<EdgeStruct>>() {
-                if id_1 < id_2 {
-                    edge.push(EdgeStruct {
-                        id1: id_1,
-                        id2: id_2,
-                        node_type1: 0,
-                        node_type2: 0,
-                        index1: index1,
-                        index2: index2,
-                    });
-                } else {
-                    edge.push(EdgeStruct {
-                        id1: id_2,
-                        id2: id_1,
-                        node_type1: 0,
-                        node_type2: 0,
-                        index1: index2,
-                        index2: index1,
-                    });
-                }
-            }
-
-            // add adjacent to nodes (and remove from not_adjacent)
-            let drones = window.get_drones();
-            if let Some(drone) = drones.as_any().downcast_ref::<VecModel<DroneStruct>>() {
-                for d in drone.iter() {
-                    if d.id == id_1 {
-                        if let Some(adj) = d.adjent.as_any().downcast_ref::<VecModel<i32>>() {
-                            adj.push(id_2);
-                        }
-                        if let Some(n_adj) = d.not_adjacent.as_any().downcast_ref::<VecModel<i32>>()
-                        {
-                            let mut i = 0;
-                            for e in n_adj.iter() {
-                                if e == id_2 {
-                                    n_adj.remove(i);
-                                    break;
-                                }
-                                i = i + 1;
-                            }
-                        }
-                    }
-                    if d.id == id_2 {
-                        if let Some(adj) = d.adjent.as_any().downcast_ref::<VecModel<i32>>() {
-                            adj.push(id_1);
-                        }
-                        if let Some(n_adj) = d.not_adjacent.as_any().downcast_ref::<VecModel<i32>>()
-                        {
-                            let mut i = 0;
-                            for e in n_adj.iter() {
-                                if e == id_1 {
-                                    n_adj.remove(i);
-                                    break;
-                                }
-                                i = i + 1;
-                            }
-                        }
-                    }
+                    .log_error(&format!("[ON_EXPORT_CAPTURE] {}", e));
+                if let Some(window) = weak.upgrade() {
+                    window.set_status_message(format!("Cannot export the capture: {}", e).into());
                 }
             }
         }
     });
 
-    // Note: the channels are not created becuase the connection the drones already exists, they are only cloned
+    let logger_ = logger.clone();
+    let controller_ = controller.clone();
+    // ON DISCARD CAPTURE : handler to forget the packets of a capture
+    main_window.on_discard_capture(move |id1, id2| {
+        logger_.lock().unwrap().log_info("[ON_DISCARD_CAPTURE]");
+        controller_
+            .lock()
+            .unwrap()
+            .discard_capture(id1 as u8, id2 as u8);
+    });
+
     let logger_ = logger.clone();
     let weak = main_window.as_weak();
-    let senders = sc_senders.clone();
-    let channels_ = channels.clone();
-    let id_to_type_pos_ = id_to_type_pos.clone();
-    // ON ADD EDGE CLIENT SERVER : handler for add edge client server event
-    main_window.on_add_edge_client_server(move || {
+    let controller_ = controller.clone();
+    // ON INJECT PACKET : handler for the injections typed in the console
+    main_window.on_inject_packet(move |line| {
         logger_
             .lock()
             .unwrap()
-            .log_info("[ON_ADD_EDGE_CLIENT_SERVER]");
-        if let Some(window) = weak.upgrade() {
-            let id_1 = window.get_sender_id();
-            let id_2 = window.get_receiver_id();
-
-            let mut sender_id_1: Option<Sender<Packet>> = None;
-            let mut sender_id_2: Option<Sender<Packet>> = None;
+            .log_info(&format!("[ON_INJECT_PACKET] {}", line));
 
-            // retrieve channels to send in the DroneCommand
-            if let Some(ref mut channel) = *channels_.lock().unwrap() {
-                if let Some(ch_1) = (*channel).get(&(id_1 as u8)) {
-                    sender_id_1 = Some(ch_1.sender.clone());
-                } else {
+        if let Some(window) = weak.upgrade() {
+            let res = match parse_command(&line) {
+                Ok(Some(command @ Command::Inject(_))) => controller_
+                    .lock()
+                    .unwrap()
+                    .execute(&command)
+                    .map_err(|e| e.to_string()),
+                Ok(Some(_)) => Err(format!(
+                    "only injections are accepted in the console, not '{}'",
+                    line.trim()
+                )),
+                Ok(None) => Ok(()),
+                Err(e) => Err(e),
+            };
+            match res {
+                Ok(_) => window.set_status_message("".into()),
+                Err(e) => {
                     logger_
                         .lock()
                         .unwrap()
-                        .log_error("[ON_ADD_EDGE_CLIENT_SERVER] No sender for id1");
+                        .log_warn(&format!("[ON_INJECT_PACKET] {}", e));
+                    window.set_status_message(e.into());
                 }
+            }
+        }
+    });
 
-                if let Some(ch_2) = channel.get(&(id_2 as u8)) {
-                    sender_id_2 = Some(ch_2.sender.clone());
-                } else {
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    // ON SET PDR PROFILE : handler for the pdr profile of a drone (empty spec for a static pdr)
+    main_window.on_set_pdr_profile(move |id, spec| {
+        logger_
+            .lock()
+            .unwrap()
+            .log_info(&format!("[ON_SET_PDR_PROFILE] {} {}", id, spec));
+
+        if let Some(window) = weak.upgrade() {
+            let res = match spec.trim() {
+                "" => Ok(None),
+                spec => PdrProfile::from_spec(spec).map(Some),
+            }
+            .and_then(|profile| {
+                controller_
+                    .lock()
+                    .unwrap()
+                    .set_pdr_profile(id as u8, profile)
+                    .map_err(|e| e.to_string())
+            });
+            match res {
+                Ok(_) => window.set_status_message("".into()),
+                Err(e) => {
                     logger_
                         .lock()
                         .unwrap()
-                        .log_error("[ON_ADD_EDGE_CLIENT_SERVER] No sender for id2");
+                        .log_warn(&format!("[ON_SET_PDR_PROFILE] {}", e));
+                    window.set_status_message(e.into());
                 }
-            } else {
-                logger_
-                    .lock()
-                    .unwrap()
-                    .log_error("[ON_ADD_EDGE_CLIENT_SERVER] No sender map loaded");
-            }
-
-            // COMMUNICATE ADDITION TO DRONES to id_1 and id_2
-            if let Some(s_id_2) = sender_id_2 {
-                send_drone_command(
-                    &senders,
-                    id_1 as u8,
-                    Box::new(DroneCommand::AddSender(id_2 as u8, s_id_2)),
-                    &logger_,
-                );
-            }
-
-            if let Some(s_id_1) = sender_id_1 {
-                send_drone_command(
-                    &senders,
-                    id_2 as u8,
-                    Box::new(DroneCommand::AddSender(id_1 as u8, s_id_1)),
-                    &logger_,
-                );
             }
+        }
+    });
 
-            // add edge
-            let edges = window.get_edges();
-            if let Some(edge) = edges.as_any().downcast_ref::<VecModel<EdgeStruct>>() {
-                let (nt1, index1) = get_node_type(id_1, &id_to_type_pos_);
-                let (nt2, index2) = get_node_type(id_2, &id_to_type_pos_);
-
-                if id_1 < id_2 {
-                    edge.push(EdgeStruct {
-                        id1: id_1,
-                        id2: id_2,
-                        node_type1: nt1,
-                        node_type2: nt2,
-                        index1: index1,
-                        index2: index2,
-                    });
-                } else {
-                    edge.push(EdgeStruct {
-                        id1: id_2,
-                        id2: id_1,
-                        node_type1: nt2,
-                        node_type2: nt1,
-                        index1: index2,
-                        index2: index1,
-                    });
+    let weak = main_window.as_weak();
+    // ON TOGGLE SELECTION : handler for the shift-click on a node
+    main_window.on_toggle_selection(move |id| {
+        if let Some(window) = weak.upgrade() {
+            let mut selection: Vec<i32> = window.get_selection().iter().collect();
+            match selection.iter().position(|s| *s == id) {
+                Some(i) => {
+                    selection.remove(i);
                 }
-            } else {
-                logger_
-                    .lock()
-                    .unwrap()
-                    .log_warn("[ON_ADD_EDGE_CLIENT_SERVER] Problems in downcasting edges");
+                None => selection.push(id),
             }
+            set_selection(&window, selection);
+        }
+    });
 
-            // add adjacent (and remove from not_adjacent)
-            let tmp1: i32; // lower -> drone
-            let tmp2: i32; // higher -> client or server
-            if id_1 < id_2 {
-                tmp1 = id_1;
-                tmp2 = id_2;
-            } else {
-                tmp1 = id_2;
-                tmp2 = id_1;
-            }
-            if tmp2 < 30 {
-                //->clinet
-                let clients = window.get_clients();
-                for c in clients.iter() {
-                    if c.id == tmp2 {
-                        if let Some(adj) =
-                            c.drones_adjacent.as_any().downcast_ref::<VecModel<i32>>()
-                        {
-                            adj.push(tmp1);
-                        }
-                        if let Some(n_adj) = c
-                            .drones_not_adjacent
-                            .as_any()
-                            .downcast_ref::<VecModel<i32>>()
-                        {
-                            let mut i = 0;
-                            for e in n_adj.iter() {
-                                if e == tmp1 {
-                                    n_adj.remove(i);
-                                    break;
-                                }
-                                i = i + 1;
-                            }
-                        }
-                    }
-                }
+    let weak = main_window.as_weak();
+    // ON SELECT RECT : handler for the rubber band selection
+    main_window.on_select_rect(move |x1, y1, x2, y2, add| {
+        if let Some(window) = weak.upgrade() {
+            let mut selection: Vec<i32> = if add {
+                window.get_selection().iter().collect()
             } else {
-                //->server
-                let servers = window.get_servers();
-                for s in servers.iter() {
-                    if s.id == tmp2 {
-                        if let Some(adj) =
-                            s.drones_adjacent.as_any().downcast_ref::<VecModel<i32>>()
-                        {
-                            adj.push(tmp1);
-                        }
-                        if let Some(n_adj) = s
-                            .drones_not_adjacent
-                            .as_any()
-                            .downcast_ref::<VecModel<i32>>()
-                        {
-                            let mut i = 0;
-                            for e in n_adj.iter() {
-                                if e == tmp1 {
-                                    n_adj.remove(i);
-                                    break;
-                                }
-                                i = i + 1;
-                            }
-                        }
-                    }
+                vec![]
+            };
+            for id in nodes_in_rect(&window, x1, y1, x2, y2) {
+                if !selection.contains(&id) {
+                    selection.push(id);
                 }
             }
+            set_selection(&window, selection);
         }
     });
 
-    let logger_ = logger.clone();
     let weak = main_window.as_weak();
-    let senders = sc_senders.clone();
-    // ON CHANGE PDR : handler for change packet drop rate
-    main_window.on_change_pdr(move || {
-        logger_.lock().unwrap().log_info("[ON_CHANGE_PDR]");
+    // ON SELECT GROUP : handler to select the members of a group of the configuration
+    main_window.on_select_group(move |name| {
         if let Some(window) = weak.upgrade() {
-            let id = window.get_id_selected_drone();
-            let new_pdr = window.get_new_pdr();
-
-            send_drone_command(
-                &senders,
-                id as u8,
-                Box::new(DroneCommand::SetPacketDropRate(new_pdr)),
-                &logger_,
-            );
+            let members = window
+                .get_groups()
+                .iter()
+                .find(|g| g.name == name)
+                .map(|g| g.members.iter().collect())
+                .unwrap_or_default();
+            set_selection(&window, members);
         }
     });
 
-    let logger_ = logger.clone();
     let weak = main_window.as_weak();
-    let senders = sc_senders.clone();
-    let sc_receiver_ = sc_receiver.clone();
-    let channels_ = channels.clone();
-    let id_to_type_pos_ = id_to_type_pos.clone();
-    let network_initializer_: Arc<Mutex<Result<NetworkInitializer, ConfigError>>> =
-        network_initializer.clone();
-
-    // ON SELECT NEW FILE : handler for select new file
-    main_window.on_select_new_file(move || {
-        logger_.lock().unwrap().log_info("[ON_SELECT_NEW_FILE]");
-        let mut new_path: String = String::from("");
-        let mut failed: bool = true;
-
-        // take new path
-        let file = FileDialog::new().pick_file();
-        if let Some(mut path) = file {
-            let path_string = <OsString as Clone>::clone(&path.as_mut_os_string()).into_string();
-            if let Ok(path_string) = path_string {
-                logger_.lock().unwrap().log_info(&format!(
-                    "[ON_SELECT_NEW_FILE] File selected : {}",
-                    path_string
-                ));
-                new_path = path_string;
-            }
+    // ON CLEAR SELECTION : handler to empty the selection
+    main_window.on_clear_selection(move || {
+        if let Some(window) = weak.upgrade() {
+            set_selection(&window, vec![]);
         }
+    });
 
-        // if it is valid, clean up all structures and send DroneCommand to kill drones and RemoveChannels
-        // then remove senders and channels for each of them
-        match NetworkInitializer::new(Some(&new_path)) {
-            Ok(net_init) => {
-                if let Some(window) = weak.upgrade() {
-                    let drones = window.get_drones();
-
-                    for drone in drones.iter() {
-                        send_drone_command(
-                            &senders,
-                            drone.id as u8,
-                            Box::new(DroneCommand::Crash),
-                            &logger_,
-                        );
-
-                        for adj in drone.adjent.iter() {
-                            send_drone_command(
-                                &senders,
-                                adj as u8,
-                                Box::new(DroneCommand::RemoveSender(drone.id as u8)),
-                                &logger_,
-                            );
-                        }
-
-                        if let Some(ref mut channel) = *channels_.lock().unwrap() {
-                            channel.remove(&(drone.id as u8));
-                        } else {
-                            logger_
-                                .lock()
-                                .unwrap()
-                                .log_warn("[ON_SELECT_NEW_FILE] No channels map loaded");
-                        }
-                        if let Some(ref mut s) = *senders.lock().unwrap() {
-                            s.remove(&(drone.id as u8));
-                        } else {
-                            logger_
-                                .lock()
-                                .unwrap()
-                                .log_warn("[ON_SELECT_NEW_FILE] No sender map loaded");
-                        }
-                    }
-                    window.set_drones(slint::ModelRc::new(slint::VecModel::from(vec![])));
-
-                    let clients = window.get_clients();
-                    for client in clients.iter() {
-                        send_drone_command(
-                            &senders,
-                            client.id as u8,
-                            Box::new(DroneCommand::Crash),
-                            &logger_,
-                        );
-
-                        if let Some(ref mut channel) = *channels_.lock().unwrap() {
-                            channel.remove(&(client.id as u8));
-                        } else {
-                            logger_
-                                .lock()
-                                .unwrap()
-                                .log_warn("[ON_SELECT_NEW_FILE] No channels map loaded");
-                        }
-                        if let Some(ref mut s) = *senders.lock().unwrap() {
-                            s.remove(&(client.id as u8));
-                        } else {
-                            logger_
-                                .lock()
-                                .unwrap()
-                                .log_warn("[ON_SELECT_NEW_FILE] No senders map loaded");
-                        }
-                    }
-                    window.set_clients(slint::ModelRc::new(slint::VecModel::from(vec![])));
-
-                    let servers = window.get_servers();
-                    for server in servers.iter() {
-                        send_drone_command(
-                            &senders,
-                            server.id as u8,
-                            Box::new(DroneCommand::Crash),
-                            &logger_,
-                        );
+    let logger_ = logger.clone();
+    let weak = main_window.as_weak();
+    let controller_ = controller.clone();
+    // ON GROUP ACTION : handler for the actions on every selected node
+    main_window.on_group_action(move |action, pdr| {
+        logger_.lock().unwrap().log_info("[ON_GROUP_ACTION]");
 
-                        if let Some(ref mut channel) = *channels_.lock().unwrap() {
-                            channel.remove(&(server.id as u8));
-                        } else {
-                            logger_
-                                .lock()
-                                .unwrap()
-                                .log_warn("[ON_SELECT_NEW_FILE] No channels map loaded");
-                        }
-                        if let Some(ref mut s) = *senders.lock().unwrap() {
-                            s.remove(&(server.id as u8));
-                        } else {
-                            logger_
-                                .lock()
-                                .unwrap()
-                                .log_warn("[ON_SELECT_NEW_FILE] No senders map loaded");
-                        }
-                    }
-                    window.set_servers(slint::ModelRc::new(slint::VecModel::from(vec![])));
-                    window.set_edges(slint::ModelRc::new(slint::VecModel::from(vec![])));
+        if let Some(window) = weak.upgrade() {
+            let action = match action {
+                0 => GroupAction::Crash,
+                1 => GroupAction::Reactivate,
+                2 if !(0.0..=1.0).contains(&pdr) => {
+                    window.set_status_message(format!("pdr {} is not in [0,1]", pdr).into());
+                    return;
                 }
-
-                while network_initializer_.try_lock().is_err() {
-                    // wait for the other simulation to terminate
+                2 => GroupAction::Pdr(pdr),
+                3 => GroupAction::Clique,
+                4 => GroupAction::Chain,
+                _ => GroupAction::Disconnect,
+            };
+            let ids: Vec<u8> = window.get_selection().iter().map(|id| id as u8).collect();
+            let res = controller_
+                .lock()
+                .unwrap()
+                .apply_group(&GroupTarget::Nodes(ids), &action);
+            match res {
+                Ok(_) => window.set_status_message("".into()),
+                Err(e) => {
+                    logger_
+                        .lock()
+                        .unwrap()
+                        .log_warn(&format!("[ON_GROUP_ACTION] {}", e));
+                    window.set_status_message(e.to_string().into());
                 }
-                *network_initializer_.lock().unwrap() = Ok(net_init);
-                failed = false;
-            }
-            Err(e) => {
-                logger_.lock().unwrap().log_error(&format!(
-                    "[ON_SELECT_NEW_FILE] Error in loading new configuration: {}",
-                    e
-                ));
             }
         }
+    });
 
-        // set up new configuration
-        if !failed {
-            if let Ok(ref mut c) = *network_initializer_.lock().unwrap() {
-                *sc_receiver_.lock().unwrap() = Some((*c).get_controller_recv());
-                *senders.lock().unwrap() = Some((*c).get_controller_senders());
-                *channels_.lock().unwrap() = Some((*c).get_channels());
-                id_to_type_pos_.lock().unwrap().clear();
-
-                let nodes = c.get_nodes();
-
-                let mut edges: Vec<EdgeStruct> = vec![];
-                let (drones, clients, servers) =
-                    populate_all(nodes.0, nodes.2, nodes.1, &mut edges, &id_to_type_pos_);
-                println!("id_to_type {:?}", *id_to_type_pos_.lock().unwrap());
-
-                if let Some(window) = weak.upgrade() {
-                    window.set_edges(slint::ModelRc::new(slint::VecModel::from(edges)));
-                    window.set_clients(slint::ModelRc::new(slint::VecModel::from(clients)));
-                    window.set_drones(slint::ModelRc::new(slint::VecModel::from(drones)));
-                    window.set_servers(slint::ModelRc::new(slint::VecModel::from(servers)));
-                    window.set_messages(slint::ModelRc::new(slint::VecModel::from(vec![])));
-                }
-            }
-
-            // run configuration in a separate thread
-            let network_initializer_run_simulation = network_initializer_.clone();
-            let logger1 = logger_.clone();
-            let _ = std::fs::remove_dir_all("db/");
-
-            thread::spawn(move || {
-                if let Ok(ref mut c) = *network_initializer_run_simulation.lock().unwrap() {
-                    match c.run_simulation(None, None) {
-                        Ok(_) => {
-                            logger1
-                                .lock()
-                                .unwrap()
-                                .log_debug("[ON_SELECT_NEW_FILE] Simulation correctly ended");
-                        }
-                        Err(e) => {
-                            logger1.lock().unwrap().log_error(&format!(
-                                "[ON_SELECT_NEW_FILE] Simulation uncorrectly ended : {}",
-                                e
-                            ));
-                        }
-                    }
-                } else {
-                    println!(
-                        "[SIMULATION CONTROLLER] Error getting network initializer lock in run sim"
-                    );
-                    logger1.lock().unwrap().log_error(
-                        "[ON_SELECT_NEW_FILE] Error getting network initializer lock in run sim",
-                    );
-                }
-            });
-        }
+    let view = ViewState {
+        id_to_type_pos: id_to_type_pos.clone(),
+        layout: layout.clone(),
+        statistics: statistics.clone(),
+        sampler: sampler.clone(),
+        routes: routes.clone(),
+    };
+    let controller_ = controller.clone();
+    // the packet captures are refreshed with the statistics
+    register_view_callbacks(&main_window, &logger, &view, move |window| {
+        let captures = controller_.lock().unwrap().captures();
+        show_captures(window, &captures);
     });
 
     // Set up Ctrl+C handler
     let logger_ = logger.clone();
-    let weak = main_window.as_weak();
-    let channels_ = channels.clone();
-    let senders = sc_senders.clone();
+    let controller_ = controller.clone();
 
     ctrlc::set_handler(move || {
         logger_
@@ -1413,121 +2197,27 @@ fn main() -> Result<(), slint::PlatformError> {
             .unwrap()
             .log_info("Received Ctrl+C, shutting down gracefully...");
 
-        let senders1 = senders.clone();
-        let channels1 = channels_.clone();
-        let logger1 = logger_.clone();
-        match weak.upgrade_in_event_loop(move |window| {
-            let drones = window.get_drones();
-
-            // send DroneCommand crash to all of them and remove all connections
-            for drone in drones.iter() {
-                send_drone_command(
-                    &senders1,
-                    drone.id as u8,
-                    Box::new(DroneCommand::Crash),
-                    &logger1,
-                );
-
-                for adj in drone.adjent.iter() {
-                    send_drone_command(
-                        &senders1,
-                        adj as u8,
-                        Box::new(DroneCommand::RemoveSender(drone.id as u8)),
-                        &logger1,
-                    );
-                }
-
-                if let Some(ref mut channel) = *channels1.lock().unwrap() {
-                    channel.remove(&(drone.id as u8));
-                } else {
-                    logger1
-                        .lock()
-                        .unwrap()
-                        .log_warn("[cntrl+c] No channels map loaded");
-                }
-                if let Some(ref mut s) = *senders1.lock().unwrap() {
-                    s.remove(&(drone.id as u8));
-                } else {
-                    logger1
-                        .lock()
-                        .unwrap()
-                        .log_warn("[cntrl+c] No sender map loaded");
-                }
-            }
-
-            let clients = window.get_clients();
-            for client in clients.iter() {
-                send_drone_command(
-                    &senders1,
-                    client.id as u8,
-                    Box::new(DroneCommand::Crash),
-                    &logger1,
-                );
-
-                if let Some(ref mut channel) = *channels1.lock().unwrap() {
-                    channel.remove(&(client.id as u8));
-                } else {
-                    logger1
-                        .lock()
-                        .unwrap()
-                        .log_warn("[cntrl+c] No channels map loaded");
-                }
-                if let Some(ref mut s) = *senders1.lock().unwrap() {
-                    s.remove(&(client.id as u8));
-                } else {
-                    logger1
-                        .lock()
-                        .unwrap()
-                        .log_warn("[cntrl+c] No senders map loaded");
-                }
-            }
-
-            let servers = window.get_servers();
-            for server in servers.iter() {
-                send_drone_command(
-                    &senders1,
-                    server.id as u8,
-                    Box::new(DroneCommand::Crash),
-                    &logger1,
-                );
-
-                if let Some(ref mut channel) = *channels1.lock().unwrap() {
-                    channel.remove(&(server.id as u8));
-                } else {
-                    logger1
-                        .lock()
-                        .unwrap()
-                        .log_warn("[cntrl+c] No channels map loaded");
-                }
-                if let Some(ref mut s) = *senders1.lock().unwrap() {
-                    s.remove(&(server.id as u8));
-                } else {
-                    logger1
-                        .lock()
-                        .unwrap()
-                        .log_warn("[cntrl+c] No senders map loaded");
-                }
-            }
-        }) {
-            Ok(_) => {
-                logger_
-                    .lock()
-                    .unwrap()
-                    .log_debug("[cntrl+c] Shutting down gracefully...");
-            }
-            Err(x) => {
-                logger_
-                    .lock()
-                    .unwrap()
-                    .log_error(&format!("[cntrl+c] Error shutting down... {}", x));
-            }
-        }
+        // send DroneCommand crash to all of them and remove all connections
+        controller_.lock().unwrap().shutdown();
+        controller_.lock().unwrap().stop_recording();
+        controller_.lock().unwrap().stop_event_loops();
 
         std::process::exit(0);
     })
     .expect("Error setting Ctrl+C handler");
 
     let _res = main_window.run();
-    run_sim_thread_handler.join().unwrap(); //handler of the first running simulation
+    stop_scenario(&scheduler);
+    if let Some(engine) = chaos.lock().unwrap().take() {
+        engine.stop();
+    }
+    pdr_driver.stop();
+    controller.lock().unwrap().stop_recording();
+    controller.lock().unwrap().stop_event_loops();
+    let _ = event_loop.join();
+    let run_sim_thread_handler = controller.lock().unwrap().take_simulation_thread();
+    if let Some(handle) = run_sim_thread_handler {
+        handle.join().unwrap(); //handler of the first running simulation
+    }
     Ok(())
 }
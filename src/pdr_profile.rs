@@ -0,0 +1,490 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{unbounded, RecvTimeoutError, Sender};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+
+use wg_internal::network::NodeId;
+
+use crate::controller::SimulationController;
+
+// period of the updates of the driven pdr, also the step of the gilbert-elliott chain
+pub const TICK: Duration = Duration::from_millis(200);
+
+// pdr of a drone as a function of the time since the profile was set, durations are in seconds
+// in the specs (e.g. "sine,mean=0.3,amplitude=0.2,period=10")
+#[derive(Debug, Clone, PartialEq)]
+pub enum PdrProfile {
+    Step {
+        before: f32,
+        after: f32,
+        at: Duration,
+    },
+    Ramp {
+        from: f32,
+        to: f32,
+        duration: Duration, // then the pdr stays at `to`
+    },
+    Sine {
+        mean: f32,
+        amplitude: f32,
+        period: Duration,
+    },
+    Square {
+        low: f32,
+        high: f32,
+        period: Duration,
+        duty: f32, // fraction of the period at `high`
+    },
+    // two-state bursty loss, the probabilities are per tick
+    GilbertElliott {
+        good: f32,
+        bad: f32,
+        p_good_bad: f64,
+        p_bad_good: f64,
+    },
+}
+
+fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, key))
+}
+
+fn parse_pdr(key: &str, value: &str) -> Result<f32, String> {
+    let pdr: f32 = parse_value(key, value)?;
+    if !(0.0..=1.0).contains(&pdr) {
+        return Err(format!("{} {} is not in [0,1]", key, pdr));
+    }
+    Ok(pdr)
+}
+
+fn parse_seconds(key: &str, value: &str) -> Result<Duration, String> {
+    let seconds: f64 = parse_value(key, value)?;
+    // also rejects the durations too long to be represented
+    Duration::try_from_secs_f64(seconds)
+        .map_err(|_| format!("{} must be a positive number of seconds", key))
+}
+
+fn parse_probability(key: &str, value: &str) -> Result<f64, String> {
+    let p: f64 = parse_value(key, value)?;
+    if !(0.0..=1.0).contains(&p) {
+        return Err(format!("{} {} is not in [0,1]", key, p));
+    }
+    Ok(p)
+}
+
+impl PdrProfile {
+    // kind followed by key=value separated by commas, the missing keys keep the default value
+    pub fn from_spec(spec: &str) -> Result<PdrProfile, String> {
+        let mut items = spec.split(',').map(str::trim).filter(|i| !i.is_empty());
+        let mut profile = match items.next() {
+            Some("step") => PdrProfile::Step {
+                before: 0.0,
+                after: 1.0,
+                at: Duration::from_secs(10),
+            },
+            Some("ramp") => PdrProfile::Ramp {
+                from: 0.0,
+                to: 1.0,
+                duration: Duration::from_secs(60),
+            },
+            Some("sine") => PdrProfile::Sine {
+                mean: 0.5,
+                amplitude: 0.5,
+                period: Duration::from_secs(30),
+            },
+            Some("square") => PdrProfile::Square {
+                low: 0.0,
+                high: 1.0,
+                period: Duration::from_secs(10),
+                duty: 0.5,
+            },
+            Some("gilbert_elliott") | Some("ge") => PdrProfile::GilbertElliott {
+                good: 0.0,
+                bad: 0.9,
+                p_good_bad: 0.05,
+                p_bad_good: 0.3,
+            },
+            Some(other) => return Err(format!("unknown pdr profile '{}'", other)),
+            None => return Err("missing pdr profile".to_string()),
+        };
+
+        for item in items {
+            let Some((key, value)) = item.split_once('=') else {
+                return Err(format!("expected key=value in '{}'", item));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            match (&mut profile, key) {
+                (PdrProfile::Step { before, .. }, "before") => *before = parse_pdr(key, value)?,
+                (PdrProfile::Step { after, .. }, "after") => *after = parse_pdr(key, value)?,
+                (PdrProfile::Step { at, .. }, "at") => *at = parse_seconds(key, value)?,
+                (PdrProfile::Ramp { from, .. }, "from") => *from = parse_pdr(key, value)?,
+                (PdrProfile::Ramp { to, .. }, "to") => *to = parse_pdr(key, value)?,
+                (PdrProfile::Ramp { duration, .. }, "duration") => {
+                    *duration = parse_seconds(key, value)?
+                }
+                (PdrProfile::Sine { mean, .. }, "mean") => *mean = parse_pdr(key, value)?,
+                (PdrProfile::Sine { amplitude, .. }, "amplitude") => {
+                    *amplitude = parse_pdr(key, value)?
+                }
+                (PdrProfile::Sine { period, .. }, "period")
+                | (PdrProfile::Square { period, .. }, "period") => {
+                    *period = parse_seconds(key, value)?
+                }
+                (PdrProfile::Square { low, .. }, "low") => *low = parse_pdr(key, value)?,
+                (PdrProfile::Square { high, .. }, "high") => *high = parse_pdr(key, value)?,
+                (PdrProfile::Square { duty, .. }, "duty") => *duty = parse_pdr(key, value)?,
+                (PdrProfile::GilbertElliott { good, .. }, "good") => *good = parse_pdr(key, value)?,
+                (PdrProfile::GilbertElliott { bad, .. }, "bad") => *bad = parse_pdr(key, value)?,
+                (PdrProfile::GilbertElliott { p_good_bad, .. }, "p_good_bad") => {
+                    *p_good_bad = parse_probability(key, value)?
+                }
+                (PdrProfile::GilbertElliott { p_bad_good, .. }, "p_bad_good") => {
+                    *p_bad_good = parse_probability(key, value)?
+                }
+                _ => return Err(format!("unknown key '{}' for this pdr profile", key)),
+            }
+        }
+
+        match profile {
+            PdrProfile::Sine { period, .. } | PdrProfile::Square { period, .. }
+                if period.is_zero() =>
+            {
+                Err("period must be greater than 0".to_string())
+            }
+            _ => Ok(profile),
+        }
+    }
+}
+
+// same syntax accepted by from_spec, with every key
+impl fmt::Display for PdrProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PdrProfile::Step { before, after, at } => write!(
+                f,
+                "step,before={},after={},at={}",
+                before,
+                after,
+                at.as_secs_f64()
+            ),
+            PdrProfile::Ramp { from, to, duration } => write!(
+                f,
+                "ramp,from={},to={},duration={}",
+                from,
+                to,
+                duration.as_secs_f64()
+            ),
+            PdrProfile::Sine {
+                mean,
+                amplitude,
+                period,
+            } => write!(
+                f,
+                "sine,mean={},amplitude={},period={}",
+                mean,
+                amplitude,
+                period.as_secs_f64()
+            ),
+            PdrProfile::Square {
+                low,
+                high,
+                period,
+                duty,
+            } => write!(
+                f,
+                "square,low={},high={},period={},duty={}",
+                low,
+                high,
+                period.as_secs_f64(),
+                duty
+            ),
+            PdrProfile::GilbertElliott {
+                good,
+                bad,
+                p_good_bad,
+                p_bad_good,
+            } => write!(
+                f,
+                "gilbert_elliott,good={},bad={},p_good_bad={},p_bad_good={}",
+                good, bad, p_good_bad, p_bad_good
+            ),
+        }
+    }
+}
+
+// profile running on a drone, the gilbert-elliott chain is stepped once per elapsed tick
+#[derive(Debug, Clone)]
+pub struct DrivenPdr {
+    pub profile: PdrProfile,
+    start: Instant,
+    ticks: u64,
+    bad: bool,
+    rng: StdRng,
+}
+
+impl DrivenPdr {
+    pub fn new(id: NodeId, profile: PdrProfile) -> DrivenPdr {
+        DrivenPdr {
+            profile,
+            start: Instant::now(),
+            ticks: 0,
+            bad: false,
+            rng: StdRng::seed_from_u64(id as u64),
+        }
+    }
+
+    // pdr at the given time, rounded to 0.01 so that small variations are not sent to the drone
+    pub fn pdr(&mut self, now: Instant) -> f32 {
+        let elapsed = now.saturating_duration_since(self.start);
+        let t = elapsed.as_secs_f64();
+        let pdr = match self.profile {
+            PdrProfile::Step { before, after, at } => {
+                if elapsed < at {
+                    before
+                } else {
+                    after
+                }
+            }
+            PdrProfile::Ramp { from, to, duration } => {
+                let progress = if duration.is_zero() {
+                    1.0
+                } else {
+                    (t / duration.as_secs_f64()).min(1.0)
+                };
+                from + (to - from) * progress as f32
+            }
+            PdrProfile::Sine {
+                mean,
+                amplitude,
+                period,
+            } => {
+                let phase = 2.0 * std::f64::consts::PI * t / period.as_secs_f64();
+                mean + amplitude * phase.sin() as f32
+            }
+            PdrProfile::Square {
+                low,
+                high,
+                period,
+                duty,
+            } => {
+                let phase = (t / period.as_secs_f64()).fract();
+                if phase < duty as f64 {
+                    high
+                } else {
+                    low
+                }
+            }
+            PdrProfile::GilbertElliott {
+                good,
+                bad,
+                p_good_bad,
+                p_bad_good,
+            } => {
+                let ticks = (elapsed.as_millis() / TICK.as_millis()) as u64;
+                while self.ticks < ticks {
+                    let p = if self.bad { p_bad_good } else { p_good_bad };
+                    if self.rng.gen_bool(p) {
+                        self.bad = !self.bad;
+                    }
+                    self.ticks += 1;
+                }
+                if self.bad {
+                    bad
+                } else {
+                    good
+                }
+            }
+        };
+        (pdr.clamp(0.0, 1.0) * 100.0).round() / 100.0
+    }
+}
+
+#[derive(Deserialize)]
+struct ProfiledDrone {
+    id: NodeId,
+    pdr_profile: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProfiledConfig {
+    #[serde(default)]
+    drones: Vec<ProfiledDrone>,
+}
+
+// pdr_profile keys of the [[drones]] of a configuration file, e.g.
+//   pdr_profile = "square,low=0,high=0.8,period=4"
+pub fn read_pdr_profiles(path: &str) -> Result<HashMap<NodeId, PdrProfile>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let config: ProfiledConfig =
+        toml::from_str(&content).map_err(|e| format!("{}: {}", path, e.message()))?;
+
+    let mut profiles = HashMap::new();
+    for drone in config.drones {
+        if let Some(spec) = drone.pdr_profile {
+            let profile = PdrProfile::from_spec(&spec)
+                .map_err(|e| format!("pdr profile of drone {}: {}", drone.id, e))?;
+            profiles.insert(drone.id, profile);
+        }
+    }
+    Ok(profiles)
+}
+
+// thread updating the pdr of the drones with a profile every tick
+pub struct PdrDriver {
+    stop: Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl PdrDriver {
+    pub fn start(controller: Arc<Mutex<SimulationController>>) -> PdrDriver {
+        let (stop, stopped) = unbounded::<()>();
+
+        let handle = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(TICK) {
+                controller.lock().unwrap().update_pdr_profiles();
+            }
+        });
+
+        PdrDriver { stop, handle }
+    }
+
+    pub fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.handle.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(driven: &mut DrivenPdr, seconds: f64) -> f32 {
+        let now = driven.start + Duration::from_secs_f64(seconds);
+        driven.pdr(now)
+    }
+
+    #[test]
+    fn parses_specs() {
+        assert_eq!(
+            PdrProfile::from_spec("step,before=0.1,at=2.5").unwrap(),
+            PdrProfile::Step {
+                before: 0.1,
+                after: 1.0,
+                at: Duration::from_millis(2500),
+            }
+        );
+        assert_eq!(
+            PdrProfile::from_spec(" ge , bad=0.5 ").unwrap(),
+            PdrProfile::GilbertElliott {
+                good: 0.0,
+                bad: 0.5,
+                p_good_bad: 0.05,
+                p_bad_good: 0.3,
+            }
+        );
+        assert!(matches!(
+            PdrProfile::from_spec("ramp,duration=0"),
+            Ok(PdrProfile::Ramp { duration, .. }) if duration.is_zero()
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_specs() {
+        for spec in [
+            "",
+            "triangle",
+            "step,at",
+            "step,mean=0.5",
+            "step,before=1.5",
+            "step,after=nan",
+            "step,at=-1",
+            "step,at=inf",
+            "step,at=nan",
+            "step,at=1e300",
+            "ramp,duration=-inf",
+            "sine,period=0",
+            "square,period=0",
+            "square,duty=-0.1",
+            "ge,p_good_bad=2",
+            "ge,p_bad_good=nan",
+        ] {
+            assert!(PdrProfile::from_spec(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn display_is_parsed_back() {
+        for spec in [
+            "step,before=0.2,after=0.7,at=1.5",
+            "ramp,from=1,to=0,duration=30",
+            "sine,mean=0.3,amplitude=0.2,period=10",
+            "square,low=0,high=0.8,period=4,duty=0.25",
+            "gilbert_elliott,good=0.01,bad=0.9,p_good_bad=0.1,p_bad_good=0.5",
+        ] {
+            let profile = PdrProfile::from_spec(spec).unwrap();
+            assert_eq!(profile.to_string(), spec);
+            assert_eq!(
+                PdrProfile::from_spec(&profile.to_string()).unwrap(),
+                profile
+            );
+        }
+    }
+
+    #[test]
+    fn evaluates_profiles() {
+        let profile = |spec| DrivenPdr::new(1, PdrProfile::from_spec(spec).unwrap());
+
+        let mut step = profile("step,before=0.2,after=0.7,at=2");
+        assert_eq!(at(&mut step, 1.9), 0.2);
+        assert_eq!(at(&mut step, 2.0), 0.7);
+
+        let mut ramp = profile("ramp,from=1,to=0,duration=10");
+        assert_eq!(at(&mut ramp, 0.0), 1.0);
+        assert_eq!(at(&mut ramp, 2.5), 0.75);
+        assert_eq!(at(&mut ramp, 100.0), 0.0);
+
+        let mut sine = profile("sine,mean=0.5,amplitude=0.5,period=4");
+        assert_eq!(at(&mut sine, 0.0), 0.5);
+        assert_eq!(at(&mut sine, 1.0), 1.0);
+        assert_eq!(at(&mut sine, 3.0), 0.0);
+
+        let mut square = profile("square,low=0.1,high=0.9,period=4,duty=0.25");
+        assert_eq!(at(&mut square, 0.5), 0.9);
+        assert_eq!(at(&mut square, 1.5), 0.1);
+        assert_eq!(at(&mut square, 4.5), 0.9);
+
+        // the pdr is clamped to [0,1]
+        let mut sine = profile("sine,mean=0.9,amplitude=0.5,period=4");
+        assert_eq!(at(&mut sine, 1.0), 1.0);
+    }
+
+    #[test]
+    fn gilbert_elliott_switches_states() {
+        let profile = |spec| DrivenPdr::new(1, PdrProfile::from_spec(spec).unwrap());
+
+        let mut stuck = profile("ge,good=0.1,bad=0.9,p_good_bad=0,p_bad_good=0");
+        assert_eq!(at(&mut stuck, 60.0), 0.1);
+
+        // the state flips on every tick
+        let mut flipping = profile("ge,good=0.1,bad=0.9,p_good_bad=1,p_bad_good=1");
+        let tick = TICK.as_secs_f64();
+        assert_eq!(at(&mut flipping, tick), 0.9);
+        assert_eq!(at(&mut flipping, 2.0 * tick), 0.1);
+        assert_eq!(at(&mut flipping, 5.0 * tick), 0.9);
+
+        // same drone, same chain
+        let mut a = profile("ge,p_good_bad=0.3,p_bad_good=0.3");
+        let mut b = profile("ge,p_good_bad=0.3,p_bad_good=0.3");
+        for i in 1..50 {
+            let t = i as f64 * tick;
+            assert_eq!(at(&mut a, t), at(&mut b, t));
+        }
+    }
+}
@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+
+use wg_internal::network::NodeId;
+
+use crate::command::{parse_command, Command};
+use crate::topology::{Node, Topology};
+use crate::trace::TraceRecord;
+
+pub const MIN_SPEED: f32 = 0.25;
+pub const MAX_SPEED: f32 = 16.0;
+
+// interval between two Position updates while playing
+const POSITION_INTERVAL: Duration = Duration::from_millis(200);
+
+// commands accepted by the replay thread
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayControl {
+    Play,
+    Pause,
+    TogglePause,
+    Step,      // replay up to the next event, then pause
+    Seek(u64), // milliseconds since the start of the trace
+    Speed(f32),
+}
+
+// what the view has to show while the trace is replayed
+#[derive(Debug, Clone)]
+pub enum ReplayUpdate {
+    // the topology changed, after a seek (reset) the shown messages must be cleared
+    Topology {
+        topology: Topology,
+        reset: bool,
+    },
+    Event(TraceRecord),
+    Position {
+        t: u64,
+        duration: u64,
+        paused: bool,
+        speed: f32,
+    },
+}
+
+// replays the records of a trace at the recorded pace, scaled by the speed
+pub struct Replayer {
+    records: Vec<TraceRecord>,
+    next: usize, // index of the next record to replay
+    topology: Topology,
+    crashed_neighbours: HashMap<NodeId, Vec<NodeId>>,
+    paused: bool,
+    speed: f32,
+    position: u64,   // time of the trace when anchor was taken
+    anchor: Instant, // wall clock instant corresponding to position
+    updates: Sender<ReplayUpdate>,
+}
+
+impl Replayer {
+    // the replay starts paused at the first topology of the trace
+    pub fn start(
+        records: Vec<TraceRecord>,
+        updates: Sender<ReplayUpdate>,
+    ) -> (Sender<ReplayControl>, JoinHandle<()>) {
+        let (controls, control_receiver) = unbounded::<ReplayControl>();
+        let mut replayer = Replayer {
+            records,
+            next: 0,
+            topology: Topology::default(),
+            crashed_neighbours: HashMap::new(),
+            paused: true,
+            speed: 1.0,
+            position: 0,
+            anchor: Instant::now(),
+            updates,
+        };
+
+        let handle = thread::spawn(move || replayer.run(control_receiver));
+        (controls, handle)
+    }
+
+    fn duration(&self) -> u64 {
+        self.records.last().map_or(0, |r| r.time())
+    }
+
+    // current time of the trace
+    fn now(&self) -> u64 {
+        if self.paused {
+            return self.position;
+        }
+        let elapsed = self.anchor.elapsed().as_secs_f32() * self.speed * 1000.0;
+        (self.position + elapsed as u64).min(self.duration())
+    }
+
+    // wall clock instant at which the record at time t is due
+    fn deadline(&self, t: u64) -> Instant {
+        let delay = t.saturating_sub(self.position) as f32 / 1000.0 / self.speed;
+        self.anchor + Duration::from_secs_f32(delay)
+    }
+
+    fn set_anchor(&mut self) {
+        self.position = self.now();
+        self.anchor = Instant::now();
+    }
+
+    fn run(&mut self, controls: Receiver<ReplayControl>) {
+        // show the first topology of the trace
+        self.seek(0);
+        self.send_position();
+
+        let mut last_position = Instant::now();
+        loop {
+            let control = match self.records.get(self.next) {
+                Some(record) if !self.paused => {
+                    let deadline = self.deadline(record.time());
+                    let wait = deadline
+                        .min(last_position + POSITION_INTERVAL)
+                        .saturating_duration_since(Instant::now());
+                    match controls.recv_timeout(wait) {
+                        Ok(control) => Some(control),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                _ => match controls.recv() {
+                    Ok(control) => Some(control),
+                    Err(_) => break, // the view is closed
+                },
+            };
+
+            match control {
+                Some(control) => self.handle(control),
+                None => {
+                    // replay all the records that are due
+                    let now = Instant::now();
+                    while let Some(record) = self.records.get(self.next) {
+                        if self.deadline(record.time()) > now {
+                            break;
+                        }
+                        self.replay_next();
+                    }
+                    if self.next >= self.records.len() {
+                        // end of the trace
+                        self.position = self.duration();
+                        self.paused = true;
+                    }
+                }
+            }
+
+            if control.is_some() || last_position.elapsed() >= POSITION_INTERVAL || self.paused {
+                self.send_position();
+                last_position = Instant::now();
+            }
+        }
+    }
+
+    fn handle(&mut self, control: ReplayControl) {
+        match control {
+            ReplayControl::Play => {
+                if self.next >= self.records.len() {
+                    // restart a finished trace
+                    self.seek(0);
+                }
+                self.anchor = Instant::now();
+                self.paused = false;
+            }
+            ReplayControl::Pause => {
+                self.set_anchor();
+                self.paused = true;
+            }
+            ReplayControl::TogglePause => {
+                let control = if self.paused {
+                    ReplayControl::Play
+                } else {
+                    ReplayControl::Pause
+                };
+                self.handle(control);
+            }
+            ReplayControl::Step => {
+                self.set_anchor();
+                self.paused = true;
+                while let Some(record) = self.records.get(self.next) {
+                    let is_event = matches!(record, TraceRecord::Event { .. });
+                    self.position = record.time();
+                    self.replay_next();
+                    if is_event {
+                        break;
+                    }
+                }
+            }
+            ReplayControl::Seek(t) => {
+                self.seek(t);
+            }
+            ReplayControl::Speed(speed) => {
+                self.set_anchor();
+                self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+            }
+        }
+    }
+
+    // rebuild the topology at time t, the events before t are skipped
+    fn seek(&mut self, t: u64) {
+        let t = t.min(self.duration());
+        self.topology = Topology::default();
+        self.crashed_neighbours.clear();
+        self.next = 0;
+
+        while let Some(record) = self.records.get(self.next) {
+            // a topology recorded exactly at t is already part of the state at t
+            let is_topology = matches!(record, TraceRecord::Topology { .. });
+            if record.time() > t || (record.time() == t && !is_topology) {
+                break;
+            }
+            let record = record.clone();
+            self.apply(&record);
+            self.next += 1;
+        }
+
+        self.position = t;
+        self.anchor = Instant::now();
+        let _ = self.updates.send(ReplayUpdate::Topology {
+            topology: self.topology.clone(),
+            reset: true,
+        });
+    }
+
+    fn replay_next(&mut self) {
+        let Some(record) = self.records.get(self.next).cloned() else {
+            return;
+        };
+        self.next += 1;
+
+        let update = match record {
+            TraceRecord::Event { .. } => ReplayUpdate::Event(record),
+            _ => {
+                let reset = matches!(record, TraceRecord::Topology { .. });
+                self.apply(&record);
+                ReplayUpdate::Topology {
+                    topology: self.topology.clone(),
+                    reset,
+                }
+            }
+        };
+        let _ = self.updates.send(update);
+    }
+
+    // apply a recorded topology or action to the replayed topology
+    fn apply(&mut self, record: &TraceRecord) {
+        match record {
+            TraceRecord::Topology { nodes, .. } => {
+                self.topology = Topology::from_nodes(nodes.clone());
+                self.crashed_neighbours.clear();
+            }
+            TraceRecord::Action { action, .. } => match parse_command(action) {
+                Ok(Some(Command::Crash(id))) => {
+                    let neighbours = self.topology.crash(id);
+                    self.crashed_neighbours.insert(id, neighbours);
+                }
+                Ok(Some(Command::Reactivate(id))) => {
+                    let neighbours = self.crashed_neighbours.remove(&id).unwrap_or_default();
+                    self.topology.reactivate(id, &neighbours);
+                }
+                Ok(Some(Command::Pdr(id, pdr))) => self.topology.set_pdr(id, pdr),
+                Ok(Some(Command::Link(id1, id2))) => self.topology.add_link(id1, id2),
+                Ok(Some(Command::Unlink(id1, id2))) => self.topology.remove_link(id1, id2),
+                Ok(Some(Command::Add(node_type, id, pdr, neighbours))) => {
+                    self.topology.add_node(Node {
+                        id,
+                        node_type,
+                        pdr,
+                        crashed: false,
+                        neighbours,
+                    });
+                }
+                Ok(Some(Command::Remove(id))) => {
+                    self.topology.remove_node(id);
+                    self.crashed_neighbours.remove(&id);
+                }
+                _ => {}
+            },
+            TraceRecord::Event { .. } => {}
+        }
+    }
+
+    fn send_position(&self) {
+        let _ = self.updates.send(ReplayUpdate::Position {
+            t: self.now(),
+            duration: self.duration(),
+            paused: self.paused,
+            speed: self.speed,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::topology::NodeType;
+    use crate::trace::EventKind;
+
+    fn node(id: NodeId, node_type: NodeType, neighbours: Vec<NodeId>) -> Node {
+        Node {
+            id,
+            node_type,
+            pdr: 0.0,
+            crashed: false,
+            neighbours,
+        }
+    }
+
+    fn event(t: u64) -> TraceRecord {
+        TraceRecord::Event {
+            t,
+            event: EventKind::PacketSent,
+            session_id: t,
+            fragment_index: None,
+            packet_type: "FloodRequest".to_string(),
+            nack_type: None,
+            hops: vec![10, 1, 20],
+            hop_index: 1,
+            path_trace: vec![],
+        }
+    }
+
+    // client 10 - drone 1 - server 20, the drone crashes at 1s and comes back at 3s
+    fn trace() -> Vec<TraceRecord> {
+        vec![
+            TraceRecord::Topology {
+                t: 0,
+                nodes: vec![
+                    node(1, NodeType::Drone, vec![10, 20]),
+                    node(10, NodeType::Client, vec![1]),
+                    node(20, NodeType::Server, vec![1]),
+                ],
+            },
+            TraceRecord::Action {
+                t: 1000,
+                action: "crash 1".to_string(),
+            },
+            event(2000),
+            TraceRecord::Action {
+                t: 3000,
+                action: "reactivate 1".to_string(),
+            },
+            event(4000),
+        ]
+    }
+
+    // send the controls, then close the channel and collect everything the replayer emitted
+    fn replay(controls: &[ReplayControl]) -> Vec<ReplayUpdate> {
+        let (updates, receiver) = unbounded();
+        let (sender, handle) = Replayer::start(trace(), updates);
+        for control in controls {
+            sender.send(*control).unwrap();
+        }
+        drop(sender);
+        handle.join().unwrap();
+        receiver.try_iter().collect()
+    }
+
+    fn drone_state(update: &ReplayUpdate) -> (bool, Vec<NodeId>, bool) {
+        let ReplayUpdate::Topology { topology, reset } = update else {
+            panic!("expected a topology, got {:?}", update);
+        };
+        let drone = topology.get(1).unwrap();
+        (drone.crashed, drone.neighbours.clone(), *reset)
+    }
+
+    fn position(update: &ReplayUpdate) -> (u64, bool, f32) {
+        let ReplayUpdate::Position {
+            t,
+            duration,
+            paused,
+            speed,
+        } = update
+        else {
+            panic!("expected a position, got {:?}", update);
+        };
+        assert_eq!(*duration, 4000);
+        (*t, *paused, *speed)
+    }
+
+    #[test]
+    fn seek_rebuilds_the_topology() {
+        let updates = replay(&[
+            ReplayControl::Seek(2500),
+            ReplayControl::Seek(3500),
+            ReplayControl::Seek(10_000),
+        ]);
+
+        assert_eq!(updates.len(), 8);
+        assert_eq!(drone_state(&updates[0]), (false, vec![10, 20], true));
+        assert_eq!(position(&updates[1]), (0, true, 1.0));
+        assert_eq!(drone_state(&updates[2]), (true, vec![], true));
+        assert_eq!(position(&updates[3]), (2500, true, 1.0));
+        assert_eq!(drone_state(&updates[4]), (false, vec![10, 20], true));
+        assert_eq!(position(&updates[5]), (3500, true, 1.0));
+        // seeking past the end stops at the last record
+        assert_eq!(drone_state(&updates[6]), (false, vec![10, 20], true));
+        assert_eq!(position(&updates[7]), (4000, true, 1.0));
+        assert!(!updates.iter().any(|u| matches!(u, ReplayUpdate::Event(_))));
+    }
+
+    #[test]
+    fn step_stops_after_each_event() {
+        let updates = replay(&[
+            ReplayControl::Step,
+            ReplayControl::Step,
+            ReplayControl::Step,
+        ]);
+
+        assert_eq!(updates.len(), 9);
+        assert_eq!(drone_state(&updates[0]), (false, vec![10, 20], true));
+        assert_eq!(position(&updates[1]), (0, true, 1.0));
+        assert_eq!(drone_state(&updates[2]), (true, vec![], false));
+        assert!(matches!(updates[3], ReplayUpdate::Event(ref r) if r.time() == 2000));
+        assert_eq!(position(&updates[4]), (2000, true, 1.0));
+        assert_eq!(drone_state(&updates[5]), (false, vec![10, 20], false));
+        assert!(matches!(updates[6], ReplayUpdate::Event(ref r) if r.time() == 4000));
+        assert_eq!(position(&updates[7]), (4000, true, 1.0));
+        // nothing left to replay
+        assert_eq!(position(&updates[8]), (4000, true, 1.0));
+    }
+
+    #[test]
+    fn speed_is_clamped() {
+        let updates = replay(&[
+            ReplayControl::Speed(100.0),
+            ReplayControl::Speed(0.0),
+            ReplayControl::Speed(2.0),
+        ]);
+
+        let speeds: Vec<f32> = updates[1..].iter().map(|u| position(u).2).collect();
+        assert_eq!(speeds, vec![1.0, MAX_SPEED, MIN_SPEED, 2.0]);
+    }
+
+    #[test]
+    fn toggle_pause_plays_and_pauses() {
+        // at the lowest speed the first action is 4s away, nothing is replayed in between
+        let updates = replay(&[
+            ReplayControl::Speed(MIN_SPEED),
+            ReplayControl::TogglePause,
+            ReplayControl::TogglePause,
+        ]);
+
+        let paused: Vec<bool> = updates[1..].iter().map(|u| position(u).1).collect();
+        assert_eq!(paused.first(), Some(&true));
+        assert!(!paused[2]);
+        assert_eq!(paused.last(), Some(&true));
+        assert!(updates[1..]
+            .iter()
+            .all(|u| matches!(u, ReplayUpdate::Position { .. })));
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use wg_internal::controller::DroneEvent;
+use wg_internal::network::NodeId;
+
+use crate::stats::packet_kind;
+use crate::trace::{EventKind, TraceRecord};
+
+// routes kept for the session list, the oldest ones are forgotten
+pub const MAX_ROUTES: usize = 100;
+
+// names of the packet types, indexed by stats::packet_kind
+const KIND_NAMES: [&str; 5] = ["fragment", "ack", "nack", "flood_request", "flood_response"];
+
+// source route of the last packet of a session with a given type (the acks of a session
+// travel on the reversed route, so they are kept apart from the fragments)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub session_id: u64,
+    pub kind: usize,
+    pub hops: Vec<NodeId>,
+    pub hop_index: usize, // hop the packet is going to, or the one that dropped it
+    pub dropped: bool,
+}
+
+// "session 1234 fragment: 20 > 3 > 7 > 30 (at 7)"
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hops: Vec<String> = self.hops.iter().map(|id| id.to_string()).collect();
+        write!(
+            f,
+            "session {} {}: {} ({} {})",
+            self.session_id,
+            KIND_NAMES[self.kind],
+            hops.join(" > "),
+            if self.dropped { "dropped at" } else { "at" },
+            self.hops[self.hop_index]
+        )
+    }
+}
+
+impl Route {
+    pub fn key(&self) -> String {
+        route_key(self.session_id, self.kind)
+    }
+}
+
+// identifies a route in the window, e.g. "1234:fragment"
+pub fn route_key(session_id: u64, kind: usize) -> String {
+    format!("{}:{}", session_id, KIND_NAMES[kind])
+}
+
+// key of the route of a recorded event
+pub fn record_route_key(record: &TraceRecord) -> Option<String> {
+    match (record, record.packet_kind()) {
+        (TraceRecord::Event { session_id, .. }, Some(kind)) => Some(route_key(*session_id, kind)),
+        _ => None,
+    }
+}
+
+pub fn parse_route_key(key: &str) -> Option<(u64, usize)> {
+    let (session_id, kind) = key.split_once(':')?;
+    let kind = KIND_NAMES.iter().position(|name| *name == kind)?;
+    Some((session_id.parse().ok()?, kind))
+}
+
+// recent routes seen in the DroneEvent, the most recent last
+#[derive(Debug, Clone, Default)]
+pub struct Routes {
+    routes: VecDeque<Route>,
+}
+
+impl Routes {
+    pub fn reset(&mut self) {
+        self.routes.clear();
+    }
+
+    pub fn record(&mut self, event: &DroneEvent) {
+        let (packet, dropped) = match event {
+            DroneEvent::PacketDropped(packet) => (packet, true),
+            DroneEvent::PacketSent(packet) | DroneEvent::ControllerShortcut(packet) => {
+                (packet, false)
+            }
+        };
+        self.update(
+            packet.session_id,
+            packet_kind(&packet.pack_type),
+            &packet.routing_header.hops,
+            packet.routing_header.hop_index,
+            dropped,
+        );
+    }
+
+    // same as record, for an event read from a trace
+    pub fn record_replayed(&mut self, record: &TraceRecord) {
+        if let (
+            TraceRecord::Event {
+                event,
+                session_id,
+                hops,
+                hop_index,
+                ..
+            },
+            Some(kind),
+        ) = (record, record.packet_kind())
+        {
+            let dropped = *event == EventKind::PacketDropped;
+            self.update(*session_id, kind, hops, *hop_index, dropped);
+        }
+    }
+
+    // flood requests have no source route and are not kept
+    fn update(
+        &mut self,
+        session_id: u64,
+        kind: usize,
+        hops: &[NodeId],
+        hop_index: usize,
+        dropped: bool,
+    ) {
+        if hops.len() < 2 {
+            return;
+        }
+        self.routes
+            .retain(|r| (r.session_id, r.kind) != (session_id, kind));
+        self.routes.push_back(Route {
+            session_id,
+            kind,
+            hops: hops.to_vec(),
+            hop_index: hop_index.min(hops.len() - 1),
+            dropped,
+        });
+        if self.routes.len() > MAX_ROUTES {
+            self.routes.pop_front();
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Route> {
+        let (session_id, kind) = parse_route_key(key)?;
+        self.routes
+            .iter()
+            .find(|r| (r.session_id, r.kind) == (session_id, kind))
+    }
+
+    // the most recent first
+    pub fn recent(&self) -> impl Iterator<Item = &Route> {
+        self.routes.iter().rev()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wg_internal::network::SourceRoutingHeader;
+    use wg_internal::packet::{Ack, Fragment, Packet, PacketType};
+
+    fn packet(session_id: u64, hop_index: usize, pack_type: PacketType) -> Packet {
+        Packet {
+            routing_header: SourceRoutingHeader {
+                hop_index,
+                hops: vec![20, 3, 7, 30],
+            },
+            session_id,
+            pack_type,
+        }
+    }
+
+    fn fragment() -> PacketType {
+        PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: 0,
+            data: [0; 128],
+        })
+    }
+
+    #[test]
+    fn routes_are_kept_by_session_and_kind() {
+        let mut routes = Routes::default();
+        routes.record(&DroneEvent::PacketSent(packet(1234, 1, fragment())));
+        routes.record(&DroneEvent::PacketDropped(packet(1234, 2, fragment())));
+        let ack = PacketType::Ack(Ack { fragment_index: 0 });
+        routes.record(&DroneEvent::PacketSent(packet(1234, 1, ack)));
+
+        // the fragment route was updated in place, the ack has its own key
+        assert_eq!(routes.recent().count(), 2);
+        let route = routes.get("1234:fragment").unwrap();
+        assert_eq!(route.key(), "1234:fragment");
+        assert_eq!(
+            route.to_string(),
+            "session 1234 fragment: 20 > 3 > 7 > 30 (dropped at 7)"
+        );
+        assert_eq!(routes.recent().next().unwrap().key(), "1234:ack");
+        assert_eq!(parse_route_key("1234:ack"), Some((1234, 1)));
+        assert_eq!(parse_route_key("1234:packet"), None);
+        assert!(routes.get("99:fragment").is_none());
+
+        for session_id in 0..MAX_ROUTES as u64 {
+            routes.record(&DroneEvent::PacketSent(packet(session_id, 1, fragment())));
+        }
+        // the oldest routes are forgotten
+        assert_eq!(routes.recent().count(), MAX_ROUTES);
+        assert!(routes.get("1234:ack").is_none());
+        assert_eq!(routes.recent().next().unwrap().key(), "99:fragment");
+    }
+}
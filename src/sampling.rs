@@ -0,0 +1,470 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use wg_internal::controller::DroneEvent;
+use wg_internal::network::NodeId;
+use wg_internal::packet::{NackType, PacketType};
+
+use crate::stats::packet_endpoints;
+use crate::trace::{EventKind, TraceRecord};
+
+// number of kinds of animated messages
+pub const MESSAGE_KINDS: usize = 7;
+
+// keys of the sampling ratios, in the order of the kinds of messages (msg_type of the view):
+// msgFragment, ack, nack, floodRequest, floodResponse, dropped, shortcut
+pub const MESSAGE_KEYS: [&str; MESSAGE_KINDS] = [
+    "msg_fragment",
+    "ack",
+    "nack",
+    "flood_request",
+    "flood_response",
+    "dropped",
+    "shortcut",
+];
+
+// kind of message animated for an event, None if the event is never animated
+pub fn message_kind(event: &DroneEvent) -> Option<usize> {
+    match event {
+        DroneEvent::PacketDropped(_) => Some(5),
+        DroneEvent::ControllerShortcut(packet) => match packet.pack_type {
+            PacketType::FloodRequest(_) => None,
+            _ => Some(6),
+        },
+        DroneEvent::PacketSent(packet) => match packet.pack_type {
+            PacketType::MsgFragment(_) => Some(0),
+            PacketType::Ack(_) => Some(1),
+            // avoid showing Nack Dropped because duplicate of PacketDropped
+            PacketType::Nack(ref nack) => match nack.nack_type {
+                NackType::Dropped => None,
+                _ => Some(2),
+            },
+            PacketType::FloodRequest(ref flood) if flood.path_trace.len() >= 2 => Some(3),
+            PacketType::FloodRequest(_) => None,
+            PacketType::FloodResponse(_) => Some(4),
+        },
+    }
+}
+
+// same as message_kind, for an event read from a trace
+pub fn replayed_message_kind(record: &TraceRecord) -> Option<usize> {
+    let TraceRecord::Event {
+        event,
+        packet_type,
+        nack_type,
+        path_trace,
+        ..
+    } = record
+    else {
+        return None;
+    };
+
+    match (event, packet_type.as_str()) {
+        (EventKind::PacketDropped, _) => Some(5),
+        (EventKind::ControllerShortcut, "flood_request") => None,
+        (EventKind::ControllerShortcut, _) => Some(6),
+        (EventKind::PacketSent, "msg_fragment") => Some(0),
+        (EventKind::PacketSent, "ack") => Some(1),
+        (EventKind::PacketSent, "nack") if nack_type.as_deref() != Some("Dropped") => Some(2),
+        (EventKind::PacketSent, "flood_request") if path_trace.len() >= 2 => Some(3),
+        (EventKind::PacketSent, "flood_response") => Some(4),
+        _ => None,
+    }
+}
+
+// which events are animated in the view, it can be read from the [sampling] table of a
+// configuration file, e.g.
+//
+// [sampling]
+// dropped = 100          # one PacketDropped every 100 is animated (0 = never)
+// edge_rate = 5          # at most 5 animations per second on each edge (0 = no limit)
+// always_nodes = [4]     # events sent or received by these nodes are always animated
+// always_sessions = [12]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplingPolicy {
+    pub msg_fragment: u64,
+    pub ack: u64,
+    pub nack: u64,
+    pub flood_request: u64,
+    pub flood_response: u64,
+    pub dropped: u64,
+    pub shortcut: u64,
+    pub edge_rate: u32,
+    pub always_nodes: Vec<NodeId>,
+    pub always_sessions: Vec<u64>,
+}
+
+// the sampling used before the policy was configurable
+impl Default for SamplingPolicy {
+    fn default() -> Self {
+        SamplingPolicy {
+            msg_fragment: 1000,
+            ack: 1000,
+            nack: 1000,
+            flood_request: 1,
+            flood_response: 1,
+            dropped: 10000,
+            shortcut: 1,
+            edge_rate: 0,
+            always_nodes: vec![],
+            always_sessions: vec![],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SamplingTable {
+    sampling: Option<SamplingPolicy>,
+}
+
+impl SamplingPolicy {
+    // one message of the kind every ratio is animated, 0 means never
+    pub fn ratio(&self, kind: usize) -> u64 {
+        match kind {
+            0 => self.msg_fragment,
+            1 => self.ack,
+            2 => self.nack,
+            3 => self.flood_request,
+            4 => self.flood_response,
+            5 => self.dropped,
+            _ => self.shortcut,
+        }
+    }
+
+    fn ratio_mut(&mut self, key: &str) -> Option<&mut u64> {
+        match key {
+            "msg_fragment" => Some(&mut self.msg_fragment),
+            "ack" => Some(&mut self.ack),
+            "nack" => Some(&mut self.nack),
+            "flood_request" => Some(&mut self.flood_request),
+            "flood_response" => Some(&mut self.flood_response),
+            "dropped" => Some(&mut self.dropped),
+            "shortcut" => Some(&mut self.shortcut),
+            _ => None,
+        }
+    }
+
+    // policy in the [sampling] table of a TOML file, None if the table is missing
+    pub fn from_config_file(path: &str) -> Result<Option<SamplingPolicy>, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let table: SamplingTable =
+            toml::from_str(&content).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(table.sampling)
+    }
+
+    // change the policy with a list of key=value separated by commas, e.g.
+    // "dropped=100,edge_rate=5,always_nodes=3 4", lists are separated by spaces
+    pub fn apply_spec(&mut self, spec: &str) -> Result<(), String> {
+        for item in spec.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            let Some((key, value)) = item.split_once('=') else {
+                return Err(format!("expected key=value in '{}'", item));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let invalid = |_| format!("invalid value '{}' for {}", value, key);
+
+            match key {
+                "edge_rate" => self.edge_rate = value.parse().map_err(invalid)?,
+                "always_nodes" => {
+                    self.always_nodes = value
+                        .split_whitespace()
+                        .map(|v| v.parse().map_err(invalid))
+                        .collect::<Result<_, _>>()?;
+                }
+                "always_sessions" => {
+                    self.always_sessions = value
+                        .split_whitespace()
+                        .map(|v| v.parse().map_err(invalid))
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => match self.ratio_mut(key) {
+                    Some(ratio) => *ratio = value.parse().map_err(invalid)?,
+                    None => return Err(format!("unknown sampling key '{}'", key)),
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+// applies a SamplingPolicy to the stream of events
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    policy: SamplingPolicy,
+    counters: [u64; MESSAGE_KINDS],
+    start: Instant,
+    edges: HashMap<(NodeId, NodeId), (u64, u32)>, // (second, animations in that second)
+}
+
+impl Sampler {
+    pub fn new(policy: SamplingPolicy) -> Sampler {
+        Sampler {
+            policy,
+            counters: [0; MESSAGE_KINDS],
+            start: Instant::now(),
+            edges: HashMap::new(),
+        }
+    }
+
+    pub fn policy(&self) -> &SamplingPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: SamplingPolicy) {
+        *self = Sampler::new(policy);
+    }
+
+    pub fn reset(&mut self) {
+        self.counters = [0; MESSAGE_KINDS];
+        self.edges.clear();
+    }
+
+    // kind of message to animate for the event, None if it is not sampled
+    pub fn sample(&mut self, event: &DroneEvent) -> Option<usize> {
+        let kind = message_kind(event)?;
+        let (packet, packet_dropped) = match event {
+            DroneEvent::PacketSent(packet) | DroneEvent::ControllerShortcut(packet) => {
+                (packet, false)
+            }
+            DroneEvent::PacketDropped(packet) => (packet, true),
+        };
+        let endpoints = packet_endpoints(packet, packet_dropped)?;
+        self.sample_kind(kind, endpoints, packet.session_id)
+            .then_some(kind)
+    }
+
+    // same as sample, for an event read from a trace
+    pub fn sample_replayed(&mut self, record: &TraceRecord) -> Option<usize> {
+        let kind = replayed_message_kind(record)?;
+        let TraceRecord::Event { session_id, .. } = record else {
+            return None;
+        };
+        let endpoints = record.endpoints()?;
+        self.sample_kind(kind, endpoints, *session_id)
+            .then_some(kind)
+    }
+
+    fn sample_kind(&mut self, kind: usize, (from, to): (NodeId, NodeId), session_id: u64) -> bool {
+        if self.policy.always_nodes.contains(&from)
+            || self.policy.always_nodes.contains(&to)
+            || self.policy.always_sessions.contains(&session_id)
+        {
+            return true;
+        }
+
+        let ratio = self.policy.ratio(kind);
+        if ratio == 0 {
+            return false;
+        }
+        self.counters[kind] += 1;
+        if self.counters[kind] % ratio != 0 {
+            return false;
+        }
+        self.counters[kind] = 0;
+
+        // at most edge_rate animations per second on each edge (in both directions)
+        if self.policy.edge_rate > 0 {
+            let second = self.start.elapsed().as_secs();
+            let edge = (from.min(to), from.max(to));
+            let (s, count) = self.edges.entry(edge).or_insert((second, 0));
+            if *s != second {
+                *s = second;
+                *count = 0;
+            }
+            if *count >= self.policy.edge_rate {
+                return false;
+            }
+            *count += 1;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wg_internal::network::SourceRoutingHeader;
+    use wg_internal::packet::{Ack, FloodRequest, FloodResponse, Nack, NodeType, Packet};
+
+    fn packet(session_id: u64, pack_type: PacketType) -> Packet {
+        Packet {
+            routing_header: SourceRoutingHeader {
+                hop_index: 1,
+                hops: vec![10, 1, 20],
+            },
+            session_id,
+            pack_type,
+        }
+    }
+
+    fn ack(session_id: u64) -> DroneEvent {
+        DroneEvent::PacketSent(packet(
+            session_id,
+            PacketType::Ack(Ack { fragment_index: 0 }),
+        ))
+    }
+
+    fn nack(nack_type: NackType) -> Packet {
+        packet(
+            1,
+            PacketType::Nack(Nack {
+                fragment_index: 0,
+                nack_type,
+            }),
+        )
+    }
+
+    fn flood_request(path_trace: Vec<NodeId>) -> Packet {
+        packet(
+            1,
+            PacketType::FloodRequest(FloodRequest {
+                flood_id: 1,
+                initiator_id: 10,
+                path_trace: path_trace
+                    .into_iter()
+                    .map(|id| (id, NodeType::Drone))
+                    .collect(),
+            }),
+        )
+    }
+
+    // positions (from 1) of the sampled events among n copies of the event
+    fn sampled(sampler: &mut Sampler, event: &DroneEvent, n: usize) -> Vec<usize> {
+        (1..=n)
+            .filter(|_| sampler.sample(event).is_some())
+            .collect()
+    }
+
+    #[test]
+    fn default_policy_is_the_baseline() {
+        let mut sampler = Sampler::new(SamplingPolicy::default());
+
+        assert_eq!(sampled(&mut sampler, &ack(1), 2999), vec![1000, 2000]);
+        let dropped = DroneEvent::PacketDropped(nack(NackType::Dropped));
+        assert_eq!(sampled(&mut sampler, &dropped, 10000), vec![10000]);
+        // the Nack Dropped duplicates the PacketDropped
+        let nack_dropped = DroneEvent::PacketSent(nack(NackType::Dropped));
+        assert!(sampled(&mut sampler, &nack_dropped, 1000).is_empty());
+        let nack_routing = DroneEvent::PacketSent(nack(NackType::DestinationIsDrone));
+        assert_eq!(sampled(&mut sampler, &nack_routing, 1000), vec![1000]);
+
+        // floods and shortcuts are always shown, except the first hop of a flood
+        let first_hop = DroneEvent::PacketSent(flood_request(vec![10]));
+        assert_eq!(sampler.sample(&first_hop), None);
+        let flood = DroneEvent::PacketSent(flood_request(vec![10, 1]));
+        assert_eq!(sampler.sample(&flood), Some(3));
+        let response = DroneEvent::PacketSent(packet(
+            1,
+            PacketType::FloodResponse(FloodResponse {
+                flood_id: 1,
+                path_trace: vec![],
+            }),
+        ));
+        assert_eq!(sampler.sample(&response), Some(4));
+        let shortcut =
+            DroneEvent::ControllerShortcut(packet(1, PacketType::Ack(Ack { fragment_index: 0 })));
+        assert_eq!(sampler.sample(&shortcut), Some(6));
+        let flood_shortcut = DroneEvent::ControllerShortcut(flood_request(vec![10, 1]));
+        assert_eq!(sampler.sample(&flood_shortcut), None);
+    }
+
+    #[test]
+    fn always_nodes_and_sessions_override_the_ratios() {
+        let mut policy = SamplingPolicy {
+            ack: 0,
+            ..SamplingPolicy::default()
+        };
+        let mut sampler = Sampler::new(policy.clone());
+        assert!(sampled(&mut sampler, &ack(1), 10).is_empty());
+
+        policy.always_sessions = vec![7];
+        sampler.set_policy(policy.clone());
+        assert!(sampled(&mut sampler, &ack(1), 10).is_empty());
+        assert_eq!(sampled(&mut sampler, &ack(7), 10).len(), 10);
+
+        policy.always_sessions.clear();
+        policy.always_nodes = vec![1];
+        sampler.set_policy(policy);
+        assert_eq!(sampled(&mut sampler, &ack(1), 10).len(), 10);
+    }
+
+    #[test]
+    fn edge_rate_limits_the_animations() {
+        let policy = SamplingPolicy {
+            ack: 1,
+            edge_rate: 3,
+            ..SamplingPolicy::default()
+        };
+        let mut sampler = Sampler::new(policy);
+        assert_eq!(sampled(&mut sampler, &ack(1), 10), vec![1, 2, 3]);
+
+        // the limit is per edge, in both directions
+        let mut back = packet(1, PacketType::Ack(Ack { fragment_index: 0 }));
+        back.routing_header.hops = vec![1, 10];
+        assert!(sampled(&mut sampler, &DroneEvent::PacketSent(back), 5).is_empty());
+        let mut other = packet(1, PacketType::Ack(Ack { fragment_index: 0 }));
+        other.routing_header.hop_index = 2;
+        assert_eq!(
+            sampled(&mut sampler, &DroneEvent::PacketSent(other), 5),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn parses_specs() {
+        let mut policy = SamplingPolicy::default();
+        policy
+            .apply_spec("dropped=100, edge_rate=5,always_nodes=3 4,always_sessions=12")
+            .unwrap();
+        assert_eq!(policy.dropped, 100);
+        assert_eq!(policy.edge_rate, 5);
+        assert_eq!(policy.always_nodes, vec![3, 4]);
+        assert_eq!(policy.always_sessions, vec![12]);
+        assert_eq!(policy.ack, SamplingPolicy::default().ack);
+
+        for spec in [
+            "dropped",
+            "speed=2",
+            "ack=-1",
+            "always_nodes=3 x",
+            "edge_rate=1.5",
+        ] {
+            assert!(
+                SamplingPolicy::default().apply_spec(spec).is_err(),
+                "{}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn reads_the_sampling_table() {
+        let path = std::env::temp_dir().join("reads_the_sampling_table.toml");
+        let path = path.to_string_lossy().to_string();
+
+        std::fs::write(
+            &path,
+            "[[drone]]\nid = 1\nconnected_node_ids = []\npdr = 0.0\n\n\
+             [sampling]\nack = 10\nalways_nodes = [4]\n",
+        )
+        .unwrap();
+        let policy = SamplingPolicy::from_config_file(&path).unwrap().unwrap();
+        assert_eq!(policy.ack, 10);
+        assert_eq!(policy.always_nodes, vec![4]);
+        assert_eq!(policy.dropped, SamplingPolicy::default().dropped);
+
+        std::fs::write(
+            &path,
+            "[[drone]]\nid = 1\nconnected_node_ids = []\npdr = 0.0\n",
+        )
+        .unwrap();
+        assert_eq!(SamplingPolicy::from_config_file(&path), Ok(None));
+
+        std::fs::write(&path, "[sampling]\nack = \"often\"\n").unwrap();
+        assert!(SamplingPolicy::from_config_file(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
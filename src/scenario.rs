@@ -0,0 +1,185 @@
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{unbounded, RecvTimeoutError, Sender};
+use serde::Deserialize;
+
+use logger::Logger;
+
+use crate::command::{parse_command, Command};
+use crate::controller::SimulationController;
+
+// action of a scenario, executed `at` after the start of the scenario
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub at: Duration,
+    pub command: Command,
+}
+
+// scenario file, e.g.
+// actions = ["t=5s crash 4", "t=8s pdr 7 0.5", "t=12s unlink 1 8", "t=20s link 2 10"]
+#[derive(Deserialize)]
+struct ScenarioFile {
+    actions: Vec<String>,
+}
+
+// time of a step: "500ms", "5s", "1.5s" or "2m"
+fn parse_time(value: &str) -> Option<Duration> {
+    let (number, unit) = value.split_at(value.find(|c: char| c.is_alphabetic())?);
+    let number: f64 = number.parse().ok().filter(|n: &f64| *n >= 0.0)?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" => number * 60.0,
+        _ => return None,
+    };
+    // inf and times too far in the future are rejected
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+// parse "t=<time> <command>", the command has the same syntax of the headless scripts
+pub fn parse_step(line: &str) -> Result<Step, String> {
+    let line = line.trim();
+    let Some((time, command)) = line
+        .strip_prefix("t=")
+        .and_then(|rest| rest.split_once(char::is_whitespace))
+    else {
+        return Err(format!("expected 't=<time> <command>' in '{}'", line));
+    };
+    let at = parse_time(time).ok_or_else(|| format!("invalid time '{}' in '{}'", time, line))?;
+    match parse_command(command)? {
+        Some(Command::Wait(_)) | Some(Command::Quit) | None => Err(format!(
+            "'{}' cannot be scheduled in '{}'",
+            command.trim(),
+            line
+        )),
+        Some(command) => Ok(Step { at, command }),
+    }
+}
+
+// read a scenario file, the steps are sorted by time
+pub fn read_scenario(path: &str) -> Result<Vec<Step>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let file: ScenarioFile = toml::from_str(&content).map_err(|e| format!("{}: {}", path, e))?;
+
+    let mut steps = vec![];
+    for (n, action) in file.actions.iter().enumerate() {
+        steps.push(parse_step(action).map_err(|e| format!("{}: action {}: {}", path, n + 1, e))?);
+    }
+    steps.sort_by_key(|s| s.at); // stable, steps at the same time keep their order
+    Ok(steps)
+}
+
+// thread executing the steps of a scenario at their time, through the controller
+pub struct Scheduler {
+    stop: Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl Scheduler {
+    // on_done is called by the scheduler thread when the scenario ends or is stopped
+    pub fn start<F: FnOnce() + Send + 'static>(
+        controller: Arc<Mutex<SimulationController>>,
+        logger: Arc<Mutex<Logger>>,
+        steps: Vec<Step>,
+        on_done: F,
+    ) -> Scheduler {
+        let (stop, stopped) = unbounded::<()>();
+
+        let handle = thread::spawn(move || {
+            let start = Instant::now();
+            for step in steps {
+                // wait for the time of the step, unless the scenario is stopped
+                match stopped.recv_timeout(step.at.saturating_sub(start.elapsed())) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => {
+                        logger.lock().unwrap().log_info("[SCENARIO] Stopped");
+                        on_done();
+                        return;
+                    }
+                }
+
+                let res = controller.lock().unwrap().execute(&step.command);
+                match res {
+                    Ok(_) => logger.lock().unwrap().log_info(&format!(
+                        "[SCENARIO] t={:.1}s {} applied",
+                        step.at.as_secs_f32(),
+                        step.command
+                    )),
+                    Err(e) => logger.lock().unwrap().log_warn(&format!(
+                        "[SCENARIO] t={:.1}s {}: {}",
+                        step.at.as_secs_f32(),
+                        step.command,
+                        e
+                    )),
+                }
+            }
+            logger.lock().unwrap().log_info("[SCENARIO] Completed");
+            on_done();
+        });
+
+        Scheduler { stop, handle }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    // stop the remaining steps and wait for the thread
+    pub fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.handle.join();
+    }
+
+    // wait until every step has been executed
+    pub fn join(self) {
+        let _ = self.handle.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_steps() {
+        assert_eq!(
+            parse_step("t=5s crash 4"),
+            Ok(Step {
+                at: Duration::from_secs(5),
+                command: Command::Crash(4),
+            })
+        );
+        assert_eq!(
+            parse_step("  t=1.5s pdr 7 0.5  # comment"),
+            Ok(Step {
+                at: Duration::from_millis(1500),
+                command: Command::Pdr(7, 0.5),
+            })
+        );
+        assert_eq!(parse_time("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_time("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_time("0s"), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn invalid_steps_are_rejected() {
+        for line in [
+            "",
+            "crash 4",
+            "t=5s",
+            "t=5 crash 4",
+            "t=5min crash 4",
+            "t=-1s crash 4",
+            "t=infs crash 4",
+            "t=1e30s crash 4",
+            "t=5s fly 4",
+            "t=5s wait 2",
+            "t=5s quit",
+            "t=5s # only a comment",
+        ] {
+            assert!(parse_step(line).is_err(), "{}", line);
+        }
+    }
+}
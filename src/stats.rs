@@ -0,0 +1,322 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+use wg_internal::controller::DroneEvent;
+use wg_internal::network::NodeId;
+use wg_internal::packet::{Packet, PacketType};
+
+use crate::trace::{EventKind, TraceRecord};
+
+// number of PacketType variants, used as index of the counters
+pub const PACKET_KINDS: usize = 5;
+
+// index of the PacketType in the counters: msgFragment, ack, nack, floodRequest, floodResponse
+pub fn packet_kind(pack_type: &PacketType) -> usize {
+    match pack_type {
+        PacketType::MsgFragment(_) => 0,
+        PacketType::Ack(_) => 1,
+        PacketType::Nack(_) => 2,
+        PacketType::FloodRequest(_) => 3,
+        PacketType::FloodResponse(_) => 4,
+    }
+}
+
+// sender and receiver of the hop the event refers to
+// (if the packet is dropped, the node that dropped it comes first)
+pub fn packet_endpoints(packet: &Packet, packet_dropped: bool) -> Option<(NodeId, NodeId)> {
+    let hops = &packet.routing_header.hops;
+    let hop_index = packet.routing_header.hop_index;
+
+    if packet_dropped {
+        return Some((*hops.get(hop_index)?, *hops.get(hop_index.checked_sub(1)?)?));
+    }
+    match packet.pack_type {
+        PacketType::FloodRequest(ref flood) => {
+            // in case of FloodRequest, sender and receiver are taken from the path_trace
+            let len = flood.path_trace.len();
+            Some((
+                flood.path_trace.get(len.checked_sub(2)?)?.0,
+                flood.path_trace.get(len - 1)?.0,
+            ))
+        }
+        _ => Some((*hops.get(hop_index.checked_sub(1)?)?, *hops.get(hop_index)?)),
+    }
+}
+
+// number of events in each second of the sliding window
+#[derive(Debug, Clone, Default)]
+struct Rate {
+    buckets: VecDeque<(u64, u64)>, // (second since the start, count)
+}
+
+impl Rate {
+    fn add(&mut self, second: u64, window: u64) {
+        match self.buckets.back_mut() {
+            Some((s, count)) if *s == second => *count += 1,
+            _ => self.buckets.push_back((second, 1)),
+        }
+        self.prune(second, window);
+    }
+
+    fn prune(&mut self, second: u64, window: u64) {
+        while let Some((s, _)) = self.buckets.front() {
+            if *s + window <= second {
+                self.buckets.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn per_second(&self, second: u64, window: u64) -> f32 {
+        let count: u64 = self
+            .buckets
+            .iter()
+            .filter(|(s, _)| *s + window > second)
+            .map(|(_, c)| c)
+            .sum();
+        count as f32 / window as f32
+    }
+}
+
+// accumulated counters of a node or of a directed edge, indexed by packet_kind
+#[derive(Debug, Clone, Default)]
+pub struct Counters {
+    pub sent: [u64; PACKET_KINDS],
+    pub dropped: [u64; PACKET_KINDS],
+    pub shortcut: [u64; PACKET_KINDS],
+    sent_rate: Rate,
+    dropped_rate: Rate,
+    fragment_rate: Rate, // MsgFragment sent
+}
+
+impl Counters {
+    pub fn total_sent(&self) -> u64 {
+        self.sent.iter().sum()
+    }
+
+    pub fn total_dropped(&self) -> u64 {
+        self.dropped.iter().sum()
+    }
+
+    pub fn total_shortcut(&self) -> u64 {
+        self.shortcut.iter().sum()
+    }
+}
+
+// statistics of the DroneEvent received during the current simulation
+#[derive(Debug, Clone)]
+pub struct Statistics {
+    start: Instant,
+    window: u64, // seconds of the sliding window used for the rates
+    nodes: HashMap<NodeId, Counters>,
+    edges: HashMap<(NodeId, NodeId), Counters>,
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        Statistics::new(5)
+    }
+}
+
+impl Statistics {
+    pub fn new(window: u64) -> Statistics {
+        Statistics {
+            start: Instant::now(),
+            window: window.max(1),
+            nodes: HashMap::new(),
+            edges: HashMap::new(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Statistics::new(self.window);
+    }
+
+    fn second(&self) -> u64 {
+        self.start.elapsed().as_secs()
+    }
+
+    pub fn record(&mut self, event: &DroneEvent) {
+        let (packet, event_kind) = match event {
+            DroneEvent::PacketSent(packet) => (packet, EventKind::PacketSent),
+            DroneEvent::PacketDropped(packet) => (packet, EventKind::PacketDropped),
+            DroneEvent::ControllerShortcut(packet) => (packet, EventKind::ControllerShortcut),
+        };
+        let packet_dropped = event_kind == EventKind::PacketDropped;
+        if let Some((from, to)) = packet_endpoints(packet, packet_dropped) {
+            self.record_hop(event_kind, packet_kind(&packet.pack_type), from, to);
+        }
+    }
+
+    // same as record, for an event read from a trace
+    pub fn record_replayed(&mut self, record: &TraceRecord) {
+        if let (TraceRecord::Event { event, .. }, Some(kind), Some((from, to))) =
+            (record, record.packet_kind(), record.endpoints())
+        {
+            self.record_hop(*event, kind, from, to);
+        }
+    }
+
+    fn record_hop(&mut self, event: EventKind, kind: usize, from: NodeId, to: NodeId) {
+        let second = self.second();
+        let window = self.window;
+
+        // the edge of a dropped packet is the one it was received from
+        let edge = if event == EventKind::PacketDropped {
+            (to, from)
+        } else {
+            (from, to)
+        };
+        for counters in [
+            self.nodes.entry(from).or_default(),
+            self.edges.entry(edge).or_default(),
+        ] {
+            match event {
+                EventKind::PacketSent => {
+                    counters.sent[kind] += 1;
+                    counters.sent_rate.add(second, window);
+                    if kind == 0 {
+                        counters.fragment_rate.add(second, window);
+                    }
+                }
+                EventKind::PacketDropped => {
+                    counters.dropped[kind] += 1;
+                    counters.dropped_rate.add(second, window);
+                }
+                EventKind::ControllerShortcut => counters.shortcut[kind] += 1,
+            }
+        }
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&Counters> {
+        self.nodes.get(&id)
+    }
+
+    pub fn edge(&self, from: NodeId, to: NodeId) -> Option<&Counters> {
+        self.edges.get(&(from, to))
+    }
+
+    // directed edges leaving the node, sorted by destination
+    pub fn outgoing(&self, id: NodeId) -> Vec<(NodeId, &Counters)> {
+        let mut edges: Vec<(NodeId, &Counters)> = self
+            .edges
+            .iter()
+            .filter(|((from, _), _)| *from == id)
+            .map(|((_, to), c)| (*to, c))
+            .collect();
+        edges.sort_by_key(|(to, _)| *to);
+        edges
+    }
+
+    // packets sent per second over the sliding window
+    pub fn sent_rate(&self, counters: &Counters) -> f32 {
+        counters.sent_rate.per_second(self.second(), self.window)
+    }
+
+    // packets dropped per second over the sliding window
+    pub fn dropped_rate(&self, counters: &Counters) -> f32 {
+        counters.dropped_rate.per_second(self.second(), self.window)
+    }
+
+    // fragments sent per second over the sliding window
+    pub fn fragment_rate(&self, counters: &Counters) -> f32 {
+        counters
+            .fragment_rate
+            .per_second(self.second(), self.window)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use wg_internal::network::SourceRoutingHeader;
+    use wg_internal::packet::{Ack, Fragment};
+
+    fn packet(hop_index: usize, pack_type: PacketType) -> Packet {
+        Packet {
+            routing_header: SourceRoutingHeader {
+                hop_index,
+                hops: vec![10, 1, 20],
+            },
+            session_id: 1,
+            pack_type,
+        }
+    }
+
+    fn ack(hop_index: usize) -> Packet {
+        packet(hop_index, PacketType::Ack(Ack { fragment_index: 0 }))
+    }
+
+    fn fragment(hop_index: usize) -> Packet {
+        packet(
+            hop_index,
+            PacketType::MsgFragment(Fragment {
+                fragment_index: 0,
+                total_n_fragments: 1,
+                length: 0,
+                data: [0; 128],
+            }),
+        )
+    }
+
+    #[test]
+    fn events_are_counted_on_their_edge() {
+        let mut stats = Statistics::new(5);
+        stats.record(&DroneEvent::PacketSent(ack(1)));
+        stats.record(&DroneEvent::PacketSent(fragment(1)));
+        // dropped by drone 1, the edge is the one the packet came from
+        stats.record(&DroneEvent::PacketDropped(ack(1)));
+        stats.record(&DroneEvent::ControllerShortcut(ack(2)));
+
+        let client = stats.node(10).unwrap();
+        assert_eq!(client.sent, [1, 1, 0, 0, 0]);
+        assert_eq!(client.total_dropped(), 0);
+
+        let drone = stats.node(1).unwrap();
+        assert_eq!(drone.total_sent(), 0);
+        assert_eq!(drone.dropped, [0, 1, 0, 0, 0]);
+        assert_eq!(drone.shortcut, [0, 1, 0, 0, 0]);
+
+        let edge = stats.edge(10, 1).unwrap();
+        assert_eq!(edge.total_sent(), 2);
+        assert_eq!(edge.total_dropped(), 1);
+        assert_eq!(edge.total_shortcut(), 0);
+        assert!(stats.edge(1, 10).is_none());
+        assert_eq!(stats.edge(1, 20).unwrap().total_shortcut(), 1);
+
+        assert_eq!(stats.sent_rate(edge), 2.0 / 5.0);
+        assert_eq!(stats.fragment_rate(edge), 1.0 / 5.0);
+        assert_eq!(stats.dropped_rate(edge), 1.0 / 5.0);
+
+        let outgoing: Vec<NodeId> = stats.outgoing(1).iter().map(|(to, _)| *to).collect();
+        assert_eq!(outgoing, vec![20]);
+    }
+
+    #[test]
+    fn rates_decay_out_of_the_window() {
+        let mut rate = Rate::default();
+        rate.add(0, 3);
+        rate.add(0, 3);
+        rate.add(2, 3);
+        assert_eq!(rate.per_second(2, 3), 1.0);
+        // the events of second 0 leave the window
+        assert_eq!(rate.per_second(3, 3), 1.0 / 3.0);
+        assert_eq!(rate.per_second(5, 3), 0.0);
+        rate.add(5, 3);
+        assert_eq!(rate.buckets.len(), 1);
+
+        // the counters stay, only the rates decay
+        let mut stats = Statistics::new(2);
+        stats.record(&DroneEvent::PacketSent(ack(1)));
+        if let Some(start) = stats.start.checked_sub(Duration::from_secs(2)) {
+            stats.start = start;
+            let edge = stats.edge(10, 1).unwrap();
+            assert_eq!(stats.sent_rate(edge), 0.0);
+            assert_eq!(edge.total_sent(), 1);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use serde::Serialize;
+
+use wg_internal::network::NodeId;
+use wg_internal::packet::{Packet, PacketType};
+
+use crate::link::packet_size;
+use crate::trace::describe_packet;
+
+// packets kept by a capture, the oldest ones are dropped
+pub const MAX_CAPTURED: usize = 10_000;
+
+// packet seen on a tapped link, t is in milliseconds since the start of the capture
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CapturedPacket {
+    pub t: u64,
+    pub from: NodeId,
+    pub to: NodeId,
+    pub session_id: u64,
+    pub packet_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fragment_index: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_n_fragments: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nack_type: Option<String>,
+    pub hops: Vec<NodeId>,
+    pub hop_index: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub path_trace: Vec<NodeId>,
+    pub payload: usize, // bytes of data of a MsgFragment, 0 for the other packets
+    pub size: usize,    // approximate size on the wire
+}
+
+// every packet crossing a tapped link, in both directions
+pub struct Capture {
+    start: Instant,
+    packets: VecDeque<CapturedPacket>,
+    total: u64,
+}
+
+pub type SharedCapture = Arc<Mutex<Capture>>;
+
+impl Default for Capture {
+    fn default() -> Self {
+        Capture {
+            start: Instant::now(),
+            packets: VecDeque::new(),
+            total: 0,
+        }
+    }
+}
+
+impl Capture {
+    pub fn shared() -> SharedCapture {
+        Arc::new(Mutex::new(Capture::default()))
+    }
+
+    pub fn record(&mut self, from: NodeId, to: NodeId, packet: &Packet) {
+        let (packet_type, fragment_index, nack_type, path_trace) = describe_packet(packet);
+        let (total_n_fragments, payload) = match packet.pack_type {
+            PacketType::MsgFragment(ref fragment) => {
+                (Some(fragment.total_n_fragments), fragment.length as usize)
+            }
+            _ => (None, 0),
+        };
+
+        if self.packets.len() == MAX_CAPTURED {
+            self.packets.pop_front();
+        }
+        self.packets.push_back(CapturedPacket {
+            t: self.start.elapsed().as_millis() as u64,
+            from,
+            to,
+            session_id: packet.session_id,
+            packet_type: packet_type.to_string(),
+            fragment_index,
+            total_n_fragments,
+            nack_type,
+            hops: packet.routing_header.hops.clone(),
+            hop_index: packet.routing_header.hop_index,
+            path_trace,
+            payload,
+            size: packet_size(packet),
+        });
+        self.total += 1;
+    }
+
+    pub fn packets(&self) -> impl Iterator<Item = &CapturedPacket> {
+        self.packets.iter()
+    }
+
+    // packets seen since the start of the capture, including the ones no longer kept
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    // write the kept packets as JSON Lines, like the traces
+    pub fn export(&self, path: &str) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        for packet in &self.packets {
+            serde_json::to_writer(&mut file, packet)?;
+            file.write_all(b"\n")?;
+        }
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wg_internal::network::SourceRoutingHeader;
+    use wg_internal::packet::{Ack, Fragment};
+
+    fn packet(session_id: u64, pack_type: PacketType) -> Packet {
+        Packet {
+            routing_header: SourceRoutingHeader {
+                hop_index: 1,
+                hops: vec![10, 1, 20],
+            },
+            session_id,
+            pack_type,
+        }
+    }
+
+    #[test]
+    fn oldest_packets_are_evicted() {
+        let mut capture = Capture::default();
+        let ack = |session_id| packet(session_id, PacketType::Ack(Ack { fragment_index: 0 }));
+        for session_id in 0..MAX_CAPTURED as u64 + 5 {
+            capture.record(10, 1, &ack(session_id));
+        }
+
+        assert_eq!(capture.total(), MAX_CAPTURED as u64 + 5);
+        assert_eq!(capture.packets().count(), MAX_CAPTURED);
+        assert_eq!(capture.packets().next().unwrap().session_id, 5);
+        assert_eq!(
+            capture.packets().last().unwrap().session_id,
+            MAX_CAPTURED as u64 + 4
+        );
+    }
+
+    #[test]
+    fn export_writes_one_object_per_line() {
+        let path = std::env::temp_dir().join("export_writes_one_object_per_line.jsonl");
+        let path = path.to_string_lossy().to_string();
+        let mut capture = Capture::default();
+        capture.record(
+            10,
+            1,
+            &packet(
+                1,
+                PacketType::MsgFragment(Fragment {
+                    fragment_index: 2,
+                    total_n_fragments: 3,
+                    length: 5,
+                    data: [0; 128],
+                }),
+            ),
+        );
+        capture.record(
+            1,
+            10,
+            &packet(1, PacketType::Ack(Ack { fragment_index: 2 })),
+        );
+
+        capture.export(&path).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let lines: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["from"], 10);
+        assert_eq!(lines[0]["total_n_fragments"], 3);
+        assert_eq!(lines[0]["payload"], 5);
+        assert_eq!(lines[1]["to"], 10);
+        assert_eq!(lines[1]["fragment_index"], 2);
+        assert_eq!(lines[1]["payload"], 0);
+        // the missing options are left out
+        assert!(lines[1].get("total_n_fragments").is_none());
+        assert!(lines[1].get("path_trace").is_none());
+    }
+}
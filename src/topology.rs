@@ -0,0 +1,444 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use wg_internal::network::NodeId;
+
+use network_initializer::parsed_nodes::{ParsedClient, ParsedDrone, ParsedServer};
+
+use crate::pdr_profile::PdrProfile;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeType {
+    Drone,
+    Client,
+    Server,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    pub id: NodeId,
+    pub node_type: NodeType,
+    pub pdr: f32, // meaningful only for drones
+    pub crashed: bool,
+    pub neighbours: Vec<NodeId>,
+}
+
+// nodes of the network and their links, kept in the same order of the configuration file
+#[derive(Debug, Clone, Default)]
+pub struct Topology {
+    nodes: Vec<Node>,
+}
+
+impl Topology {
+    pub fn from_parsed(
+        parsed_drones: &[ParsedDrone],
+        parsed_clients: &[ParsedClient],
+        parsed_servers: &[ParsedServer],
+    ) -> Topology {
+        let mut nodes = vec![];
+        for drone in parsed_drones {
+            nodes.push(Node {
+                id: drone.id,
+                node_type: NodeType::Drone,
+                pdr: drone.pdr,
+                crashed: false,
+                neighbours: drone.connected_drone_ids.clone(),
+            });
+        }
+        for client in parsed_clients {
+            nodes.push(Node {
+                id: client.id,
+                node_type: NodeType::Client,
+                pdr: 0.0,
+                crashed: false,
+                neighbours: client.connected_drone_ids.clone(),
+            });
+        }
+        for server in parsed_servers {
+            nodes.push(Node {
+                id: server.id,
+                node_type: NodeType::Server,
+                pdr: 0.0,
+                crashed: false,
+                neighbours: server.connected_drone_ids.clone(),
+            });
+        }
+        Topology { nodes }
+    }
+
+    pub fn from_nodes(nodes: Vec<Node>) -> Topology {
+        Topology { nodes }
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.iter()
+    }
+
+    pub fn drones(&self) -> impl Iterator<Item = &Node> {
+        self.of_type(NodeType::Drone)
+    }
+
+    pub fn clients(&self) -> impl Iterator<Item = &Node> {
+        self.of_type(NodeType::Client)
+    }
+
+    pub fn servers(&self) -> impl Iterator<Item = &Node> {
+        self.of_type(NodeType::Server)
+    }
+
+    fn of_type(&self, node_type: NodeType) -> impl Iterator<Item = &Node> {
+        self.nodes.iter().filter(move |n| n.node_type == node_type)
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.iter().find(|n| n.id == id)
+    }
+
+    fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.iter_mut().find(|n| n.id == id)
+    }
+
+    // position of the node among the nodes of the same type
+    pub fn position(&self, id: NodeId) -> Option<(NodeType, usize)> {
+        let node = self.get(id)?;
+        let position = self.of_type(node.node_type).position(|n| n.id == id)?;
+        Some((node.node_type, position))
+    }
+
+    // a node is active if it exists and it has not crashed
+    pub fn is_active(&self, id: NodeId) -> bool {
+        self.get(id).is_some_and(|n| !n.crashed)
+    }
+
+    pub fn are_linked(&self, id1: NodeId, id2: NodeId) -> bool {
+        self.get(id1).is_some_and(|n| n.neighbours.contains(&id2))
+    }
+
+    // undirected links between active nodes, each one returned once
+    pub fn links(&self) -> Vec<(NodeId, NodeId)> {
+        let mut links = vec![];
+        for node in self.nodes.iter().filter(|n| !n.crashed) {
+            for adj in &node.neighbours {
+                if !links.contains(&(*adj, node.id)) && self.is_active(*adj) {
+                    links.push((node.id, *adj));
+                }
+            }
+        }
+        links
+    }
+
+    pub fn add_link(&mut self, id1: NodeId, id2: NodeId) {
+        if let Some(n) = self.get_mut(id1) {
+            if !n.neighbours.contains(&id2) {
+                n.neighbours.push(id2);
+            }
+        }
+        if let Some(n) = self.get_mut(id2) {
+            if !n.neighbours.contains(&id1) {
+                n.neighbours.push(id1);
+            }
+        }
+    }
+
+    pub fn remove_link(&mut self, id1: NodeId, id2: NodeId) {
+        if let Some(n) = self.get_mut(id1) {
+            n.neighbours.retain(|x| *x != id2);
+        }
+        if let Some(n) = self.get_mut(id2) {
+            n.neighbours.retain(|x| *x != id1);
+        }
+    }
+
+    // add a new node after the ones of the same type, linked to its neighbours
+    pub fn add_node(&mut self, node: Node) {
+        let id = node.id;
+        let neighbours = node.neighbours.clone();
+        let position = self
+            .nodes
+            .iter()
+            .rposition(|n| n.node_type == node.node_type)
+            .map_or(self.nodes.len(), |p| p + 1);
+        self.nodes.insert(position, node);
+        for adj in neighbours {
+            self.add_link(id, adj);
+        }
+    }
+
+    // remove a node and all its links
+    pub fn remove_node(&mut self, id: NodeId) {
+        self.nodes.retain(|n| n.id != id);
+        for n in self.nodes.iter_mut() {
+            n.neighbours.retain(|x| *x != id);
+        }
+    }
+
+    pub fn set_pdr(&mut self, id: NodeId, pdr: f32) {
+        if let Some(n) = self.get_mut(id) {
+            n.pdr = pdr;
+        }
+    }
+
+    // mark the node as crashed and remove all its links, the removed neighbours are returned
+    pub fn crash(&mut self, id: NodeId) -> Vec<NodeId> {
+        let neighbours = match self.get_mut(id) {
+            Some(n) => {
+                n.crashed = true;
+                std::mem::take(&mut n.neighbours)
+            }
+            None => return vec![],
+        };
+        for adj in &neighbours {
+            if let Some(n) = self.get_mut(*adj) {
+                n.neighbours.retain(|x| *x != id);
+            }
+        }
+        neighbours
+    }
+
+    // mark a crashed node as active again and link it to the given neighbours still active
+    pub fn reactivate(&mut self, id: NodeId, neighbours: &[NodeId]) {
+        match self.get_mut(id) {
+            Some(n) => n.crashed = false,
+            None => return,
+        }
+        for adj in neighbours {
+            if self.is_active(*adj) {
+                self.add_link(id, *adj);
+            }
+        }
+    }
+
+    // active nodes in the network-initializer format ([[drones]], [[clients]], [[servers]]),
+    // crashed drones are left out
+    pub fn to_config(&self) -> String {
+        self.to_config_with(&HashMap::new(), &BTreeMap::new())
+    }
+
+    // same as to_config, with the pdr profiles of the drones and the named groups
+    pub fn to_config_with(
+        &self,
+        profiles: &HashMap<NodeId, PdrProfile>,
+        groups: &BTreeMap<String, Vec<NodeId>>,
+    ) -> String {
+        let mut config = String::new();
+        for (node_type, table) in [
+            (NodeType::Drone, "drones"),
+            (NodeType::Client, "clients"),
+            (NodeType::Server, "servers"),
+        ] {
+            for node in self.of_type(node_type).filter(|n| !n.crashed) {
+                let neighbours: Vec<String> = node
+                    .neighbours
+                    .iter()
+                    .filter(|adj| self.is_active(**adj))
+                    .map(|adj| adj.to_string())
+                    .collect();
+                config.push_str(&format!("[[{}]]\n", table));
+                config.push_str(&format!("id = {}\n", node.id));
+                config.push_str(&format!(
+                    "connected_drone_ids = [{}]\n",
+                    neighbours.join(",")
+                ));
+                if node_type == NodeType::Drone {
+                    config.push_str(&format!("pdr = {}\n", node.pdr));
+                    if let Some(profile) = profiles.get(&node.id) {
+                        config.push_str(&format!("pdr_profile = \"{}\"\n", profile));
+                    }
+                }
+                config.push('\n');
+            }
+        }
+        if !groups.is_empty() {
+            config.push_str("[groups]\n");
+            for (name, ids) in groups {
+                let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+                config.push_str(&format!("{} = [{}]\n", name, ids.join(",")));
+            }
+        }
+        config
+    }
+
+    // servers reachable by a client, only active drones can forward packets
+    fn reachable_servers(&self, client: NodeId) -> Vec<NodeId> {
+        let mut servers = vec![];
+        let mut visited = vec![client];
+        let mut queue = VecDeque::from([client]);
+
+        while let Some(id) = queue.pop_front() {
+            let Some(node) = self.get(id) else { continue };
+            for adj in &node.neighbours {
+                if visited.contains(adj) || !self.is_active(*adj) {
+                    continue;
+                }
+                visited.push(*adj);
+                match self.get(*adj).map(|n| n.node_type) {
+                    Some(NodeType::Drone) => queue.push_back(*adj),
+                    Some(NodeType::Server) => servers.push(*adj),
+                    _ => {}
+                }
+            }
+        }
+        servers
+    }
+
+    // (client, server) pairs of active nodes that cannot communicate through the drones
+    pub fn unreachable_pairs(&self) -> Vec<(NodeId, NodeId)> {
+        let mut pairs = vec![];
+        for client in self.clients().filter(|c| !c.crashed) {
+            let reachable = self.reachable_servers(client.id);
+            for server in self.servers().filter(|s| !s.crashed) {
+                if !reachable.contains(&server.id) {
+                    pairs.push((client.id, server.id));
+                }
+            }
+        }
+        pairs
+    }
+
+    // pairs that would be disconnected by the change applied to a copy of the topology
+    pub fn partitioned_by<F: FnOnce(&mut Topology)>(&self, change: F) -> Vec<(NodeId, NodeId)> {
+        let before = self.unreachable_pairs();
+        let mut after = self.clone();
+        change(&mut after);
+        after
+            .unreachable_pairs()
+            .into_iter()
+            .filter(|pair| !before.contains(pair))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use network_initializer::NetworkInitializer;
+
+    use crate::group::read_groups;
+    use crate::pdr_profile::read_pdr_profiles;
+
+    fn node(id: NodeId, node_type: NodeType, neighbours: &[NodeId]) -> Node {
+        Node {
+            id,
+            node_type,
+            pdr: 0.1,
+            crashed: false,
+            neighbours: neighbours.to_vec(),
+        }
+    }
+
+    // client 10 - drone 1, two paths 1 - 2 - 4 and 1 - 3 - 4, server 20 - drones 2 and 4
+    fn network() -> Topology {
+        Topology::from_nodes(vec![
+            node(1, NodeType::Drone, &[10, 2, 3]),
+            node(2, NodeType::Drone, &[1, 4, 20]),
+            node(3, NodeType::Drone, &[1, 4]),
+            node(4, NodeType::Drone, &[2, 3, 20]),
+            node(10, NodeType::Client, &[1]),
+            node(20, NodeType::Server, &[2, 4]),
+        ])
+    }
+
+    #[test]
+    fn connected_network_has_no_unreachable_pairs() {
+        assert!(network().unreachable_pairs().is_empty());
+    }
+
+    #[test]
+    fn clients_do_not_forward_packets() {
+        // the second client can reach the server only through the first one
+        let mut topology = network();
+        topology.add_node(node(11, NodeType::Client, &[]));
+        topology.add_link(10, 11);
+        assert_eq!(topology.unreachable_pairs(), vec![(11, 20)]);
+    }
+
+    #[test]
+    fn crashed_nodes_are_not_counted() {
+        let mut topology = network();
+        topology.crash(20);
+        assert!(topology.unreachable_pairs().is_empty());
+
+        let mut topology = network();
+        topology.crash(10);
+        assert!(topology.unreachable_pairs().is_empty());
+    }
+
+    #[test]
+    fn unlink_with_an_alternative_path_does_not_partition() {
+        let topology = network();
+        assert!(topology.partitioned_by(|t| t.remove_link(1, 2)).is_empty());
+        assert!(topology.partitioned_by(|t| t.remove_link(2, 20)).is_empty());
+        assert!(topology
+            .partitioned_by(|t| {
+                t.crash(3);
+            })
+            .is_empty());
+    }
+
+    #[test]
+    fn partitioning_changes_are_detected() {
+        let topology = network();
+        assert_eq!(
+            topology.partitioned_by(|t| t.remove_link(10, 1)),
+            vec![(10, 20)]
+        );
+        assert_eq!(
+            topology.partitioned_by(|t| {
+                t.crash(1);
+            }),
+            vec![(10, 20)]
+        );
+        assert_eq!(
+            topology.partitioned_by(|t| {
+                t.remove_link(1, 2);
+                t.remove_link(1, 3);
+            }),
+            vec![(10, 20)]
+        );
+        assert_eq!(
+            topology.partitioned_by(|t| t.remove_node(1)),
+            vec![(10, 20)]
+        );
+        // the topology itself is not changed
+        assert!(topology.unreachable_pairs().is_empty());
+    }
+
+    #[test]
+    fn already_unreachable_pairs_are_not_reported_again() {
+        let mut topology = network();
+        topology.remove_link(10, 1);
+        assert_eq!(topology.unreachable_pairs(), vec![(10, 20)]);
+        assert!(topology.partitioned_by(|t| t.remove_link(2, 4)).is_empty());
+    }
+
+    // the pdr profiles and the groups are extra keys for the network initializer, a saved
+    // topology must still be accepted by it
+    #[test]
+    fn saved_config_is_loaded_by_the_network_initializer() {
+        let original = NetworkInitializer::new(Some("config_files/star.toml")).unwrap();
+        let (drones, clients, servers) = original.get_nodes();
+        let topology = Topology::from_parsed(drones, clients, servers);
+
+        let profiles = HashMap::from([(
+            1,
+            PdrProfile::from_spec("square,low=0,high=0.8,period=4").unwrap(),
+        )]);
+        let groups = BTreeMap::from([("left_wing".to_string(), vec![1, 4, 7])]);
+        let path = std::env::temp_dir().join("saved_config_is_loaded.toml");
+        let path = path.to_string_lossy().to_string();
+        std::fs::write(&path, topology.to_config_with(&profiles, &groups)).unwrap();
+
+        let loaded = NetworkInitializer::new(Some(&path)).unwrap();
+        let (drones, clients, servers) = loaded.get_nodes();
+        assert_eq!(
+            Topology::from_parsed(drones, clients, servers).links(),
+            topology.links()
+        );
+        assert_eq!(read_pdr_profiles(&path).unwrap(), profiles);
+        assert_eq!(read_groups(&path).unwrap(), groups);
+        let _ = std::fs::remove_file(&path);
+    }
+}
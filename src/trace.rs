@@ -0,0 +1,420 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{unbounded, RecvTimeoutError, Sender};
+use serde::{Deserialize, Serialize};
+
+use wg_internal::controller::DroneEvent;
+use wg_internal::network::NodeId;
+use wg_internal::packet::{Packet, PacketType};
+
+use crate::command::Command;
+use crate::controller::ControllerUpdate;
+use crate::topology::{Node, Topology};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    PacketSent,
+    PacketDropped,
+    ControllerShortcut,
+}
+
+// one line of a trace file (JSON Lines), t is in milliseconds since the start of the recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+pub enum TraceRecord {
+    // topology loaded (or already running when the recording started)
+    Topology {
+        t: u64,
+        nodes: Vec<Node>,
+    },
+    // DroneEvent received from the simulation
+    Event {
+        t: u64,
+        event: EventKind,
+        session_id: u64,
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        fragment_index: Option<u64>,
+        packet_type: String,
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        nack_type: Option<String>,
+        hops: Vec<NodeId>,
+        hop_index: usize,
+        #[serde(skip_serializing_if = "Vec::is_empty", default)]
+        path_trace: Vec<NodeId>, // only for FloodRequest and FloodResponse
+    },
+    // action of the controller, in the syntax of the commands (e.g. "crash 4")
+    Action {
+        t: u64,
+        action: String,
+    },
+}
+
+impl TraceRecord {
+    pub fn time(&self) -> u64 {
+        match self {
+            TraceRecord::Topology { t, .. }
+            | TraceRecord::Event { t, .. }
+            | TraceRecord::Action { t, .. } => *t,
+        }
+    }
+
+    pub fn from_event(t: u64, event: &DroneEvent) -> TraceRecord {
+        let (kind, packet) = match event {
+            DroneEvent::PacketSent(packet) => (EventKind::PacketSent, packet),
+            DroneEvent::PacketDropped(packet) => (EventKind::PacketDropped, packet),
+            DroneEvent::ControllerShortcut(packet) => (EventKind::ControllerShortcut, packet),
+        };
+        let (packet_type, fragment_index, nack_type, path_trace) = describe_packet(packet);
+
+        TraceRecord::Event {
+            t,
+            event: kind,
+            session_id: packet.session_id,
+            fragment_index,
+            packet_type: packet_type.to_string(),
+            nack_type,
+            hops: packet.routing_header.hops.clone(),
+            hop_index: packet.routing_header.hop_index,
+            path_trace,
+        }
+    }
+
+    // index of the packet type in the statistics, the same of stats::packet_kind
+    pub fn packet_kind(&self) -> Option<usize> {
+        let TraceRecord::Event { packet_type, .. } = self else {
+            return None;
+        };
+        match packet_type.as_str() {
+            "msg_fragment" => Some(0),
+            "ack" => Some(1),
+            "nack" => Some(2),
+            "flood_request" => Some(3),
+            "flood_response" => Some(4),
+            _ => None,
+        }
+    }
+
+    // sender and receiver of the hop of a recorded event, as stats::packet_endpoints
+    pub fn endpoints(&self) -> Option<(NodeId, NodeId)> {
+        let TraceRecord::Event {
+            event,
+            packet_type,
+            hops,
+            hop_index,
+            path_trace,
+            ..
+        } = self
+        else {
+            return None;
+        };
+
+        if *event == EventKind::PacketDropped {
+            return Some((
+                *hops.get(*hop_index)?,
+                *hops.get(hop_index.checked_sub(1)?)?,
+            ));
+        }
+        if packet_type == "flood_request" {
+            let len = path_trace.len();
+            return Some((
+                *path_trace.get(len.checked_sub(2)?)?,
+                *path_trace.get(len - 1)?,
+            ));
+        }
+        Some((
+            *hops.get(hop_index.checked_sub(1)?)?,
+            *hops.get(*hop_index)?,
+        ))
+    }
+}
+
+// read all the records of a trace file, a malformed line is reported with its number
+pub fn read_trace(path: &str) -> io::Result<Vec<TraceRecord>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut records = vec![];
+
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: TraceRecord = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}: {}", path, number + 1, e),
+            )
+        })?;
+        records.push(record);
+    }
+
+    // records are written by a single thread, but keep them ordered anyway
+    records.sort_by_key(|r| r.time());
+    Ok(records)
+}
+
+// name, fragment index, nack type and path trace of a packet
+pub(crate) fn describe_packet(
+    packet: &Packet,
+) -> (&'static str, Option<u64>, Option<String>, Vec<NodeId>) {
+    match packet.pack_type {
+        PacketType::MsgFragment(ref fragment) => {
+            ("msg_fragment", Some(fragment.fragment_index), None, vec![])
+        }
+        PacketType::Ack(ref ack) => ("ack", Some(ack.fragment_index), None, vec![]),
+        PacketType::Nack(ref nack) => (
+            "nack",
+            Some(nack.fragment_index),
+            Some(format!("{:?}", nack.nack_type)),
+            vec![],
+        ),
+        PacketType::FloodRequest(ref flood) => (
+            "flood_request",
+            None,
+            None,
+            flood.path_trace.iter().map(|(id, _)| *id).collect(),
+        ),
+        PacketType::FloodResponse(ref flood) => (
+            "flood_response",
+            None,
+            None,
+            flood.path_trace.iter().map(|(id, _)| *id).collect(),
+        ),
+    }
+}
+
+const FLUSH_PERIOD: Duration = Duration::from_secs(1);
+
+// writes the records of the simulation to a trace file from a dedicated thread
+pub struct Recorder {
+    start: Instant,
+    sender: Sender<TraceRecord>,
+    writer: JoinHandle<()>,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> io::Result<Recorder> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let (sender, receiver) = unbounded::<TraceRecord>();
+
+        // the file is flushed every second so that a killed process leaves a usable trace,
+        // also when the records never stop coming
+        let writer = thread::spawn(move || {
+            let mut last_flush = Instant::now();
+            loop {
+                match receiver.recv_timeout(FLUSH_PERIOD) {
+                    Ok(record) => {
+                        if serde_json::to_writer(&mut file, &record).is_ok() {
+                            let _ = file.write_all(b"\n");
+                        }
+                        if last_flush.elapsed() >= FLUSH_PERIOD {
+                            let _ = file.flush();
+                            last_flush = Instant::now();
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        let _ = file.flush();
+                        last_flush = Instant::now();
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        let _ = file.flush();
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Recorder {
+            start: Instant::now(),
+            sender,
+            writer,
+        })
+    }
+
+    fn now(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    pub fn record_event(&self, event: &DroneEvent) {
+        let _ = self.sender.send(TraceRecord::from_event(self.now(), event));
+    }
+
+    pub fn record_topology(&self, topology: &Topology) {
+        let _ = self.sender.send(TraceRecord::Topology {
+            t: self.now(),
+            nodes: topology.nodes().cloned().collect(),
+        });
+    }
+
+    pub fn record_action(&self, command: &Command) {
+        let _ = self.sender.send(TraceRecord::Action {
+            t: self.now(),
+            action: command.to_string(),
+        });
+    }
+
+    // record the change of the topology notified by the controller
+    pub fn record_update(&self, update: &ControllerUpdate, topology: &Topology) {
+        match *update {
+            ControllerUpdate::TopologyLoaded => self.record_topology(topology),
+            ControllerUpdate::Crashed(id) => self.record_action(&Command::Crash(id)),
+            ControllerUpdate::Reactivated(id) => self.record_action(&Command::Reactivate(id)),
+            ControllerUpdate::LinkAdded(id1, id2) => self.record_action(&Command::Link(id1, id2)),
+            ControllerUpdate::LinkRemoved(id1, id2) => {
+                self.record_action(&Command::Unlink(id1, id2))
+            }
+            ControllerUpdate::PdrChanged(id, pdr) => self.record_action(&Command::Pdr(id, pdr)),
+            ControllerUpdate::NodeAdded(id) => {
+                if let Some(node) = topology.get(id) {
+                    self.record_action(&Command::Add(
+                        node.node_type,
+                        id,
+                        node.pdr,
+                        node.neighbours.clone(),
+                    ));
+                }
+            }
+            ControllerUpdate::NodeRemoved(id) => self.record_action(&Command::Remove(id)),
+            ControllerUpdate::LinkProfileChanged(..)
+            | ControllerUpdate::TapChanged(..)
+            | ControllerUpdate::PdrProfileChanged(_)
+            | ControllerUpdate::Warning(_) => {}
+        }
+    }
+
+    // write the remaining records and close the file
+    pub fn finish(self) {
+        drop(self.sender);
+        let _ = self.writer.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wg_internal::network::SourceRoutingHeader;
+    use wg_internal::packet::{Nack, NackType};
+
+    use crate::topology::NodeType;
+
+    fn nack(session_id: u64, hop_index: usize, hops: Vec<NodeId>) -> Packet {
+        Packet {
+            routing_header: SourceRoutingHeader { hop_index, hops },
+            session_id,
+            pack_type: PacketType::Nack(Nack {
+                fragment_index: 3,
+                nack_type: NackType::Dropped,
+            }),
+        }
+    }
+
+    #[test]
+    fn records_are_read_back() {
+        let path = std::env::temp_dir().join("records_are_read_back.jsonl");
+        let path = path.to_string_lossy().to_string();
+        let topology = Topology::from_nodes(vec![
+            Node {
+                id: 1,
+                node_type: NodeType::Drone,
+                pdr: 0.25,
+                crashed: false,
+                neighbours: vec![10],
+            },
+            Node {
+                id: 10,
+                node_type: NodeType::Client,
+                pdr: 0.0,
+                crashed: false,
+                neighbours: vec![1],
+            },
+        ]);
+
+        let recorder = Recorder::create(&path).unwrap();
+        recorder.record_topology(&topology);
+        recorder.record_event(&DroneEvent::PacketDropped(nack(42, 1, vec![10, 1, 20])));
+        recorder.record_action(&Command::Crash(1));
+        recorder.finish();
+        let records = read_trace(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(records.len(), 3);
+        assert!(matches!(
+            records[0],
+            TraceRecord::Topology { ref nodes, .. } if nodes == &topology.nodes().cloned().collect::<Vec<Node>>()
+        ));
+        let TraceRecord::Event {
+            event,
+            session_id,
+            fragment_index,
+            ref packet_type,
+            ref nack_type,
+            ref hops,
+            hop_index,
+            ref path_trace,
+            ..
+        } = records[1]
+        else {
+            panic!("expected an event, got {:?}", records[1]);
+        };
+        assert_eq!(event, EventKind::PacketDropped);
+        assert_eq!(session_id, 42);
+        assert_eq!(fragment_index, Some(3));
+        assert_eq!(packet_type, "nack");
+        assert_eq!(nack_type.as_deref(), Some("Dropped"));
+        assert_eq!(hops, &vec![10, 1, 20]);
+        assert_eq!(hop_index, 1);
+        assert!(path_trace.is_empty());
+        assert!(matches!(
+            records[2],
+            TraceRecord::Action { ref action, .. } if action == "crash 1"
+        ));
+        assert!(records
+            .windows(2)
+            .all(|pair| pair[0].time() <= pair[1].time()));
+
+        // a dropped packet is reported by the node at the hop index to the previous one
+        assert_eq!(records[1].endpoints(), Some((1, 10)));
+        assert_eq!(records[1].packet_kind(), Some(2));
+    }
+
+    #[test]
+    fn record_format() {
+        let record =
+            TraceRecord::from_event(7, &DroneEvent::PacketSent(nack(1, 2, vec![10, 1, 20])));
+        let line = serde_json::to_string(&record).unwrap();
+        assert!(line.contains(r#""record":"event""#), "{}", line);
+        assert!(line.contains(r#""event":"packet_sent""#), "{}", line);
+        // the empty path trace is not written
+        assert!(!line.contains("path_trace"), "{}", line);
+        assert_eq!(serde_json::from_str::<TraceRecord>(&line).unwrap(), record);
+
+        let line = r#"{"record":"action","t":5,"action":"unlink 1 2"}"#;
+        assert_eq!(
+            serde_json::from_str::<TraceRecord>(line).unwrap(),
+            TraceRecord::Action {
+                t: 5,
+                action: "unlink 1 2".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn malformed_lines_are_reported() {
+        let path = std::env::temp_dir().join("malformed_lines_are_reported.jsonl");
+        let path = path.to_string_lossy().to_string();
+        std::fs::write(
+            &path,
+            "{\"record\":\"action\",\"t\":5,\"action\":\"crash 1\"}\n\nnot json\n",
+        )
+        .unwrap();
+        let res = read_trace(&path);
+        let _ = std::fs::remove_file(&path);
+        let e = res.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains(":3:"), "{}", e);
+    }
+}